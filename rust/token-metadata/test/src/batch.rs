@@ -0,0 +1,281 @@
+use {
+    super::{backoff, check_payer_balance, throttle},
+    crate::{
+        error::{classify_send_error, exit_with_error, report_item_error, CliError, SendFailure},
+        retry::{record_attempt, record_retry, RetryReason},
+        rpc::FailoverClient,
+        summary::Summary,
+    },
+    clap::ArgMatches,
+    serde_json::{json, Value},
+    solana_clap_utils::input_parsers::value_of,
+    solana_client::client_error::ClientError,
+    solana_sdk::{
+        bs58,
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::{Signature, Signer},
+        transaction::Transaction,
+    },
+    std::{fs, str::FromStr, time::Duration},
+};
+
+/// Transactions a bulk command built instead of sending, for `--output-unsigned-batch`. Each
+/// entry holds the transaction serialized the way `--sign-only` prints it, the signers it
+/// needs and an empty `signatures` list for them to add `PUBKEY=SIGNATURE` pairs to.
+pub struct UnsignedBatch {
+    path: String,
+    recent_blockhash: Hash,
+    entries: Vec<Value>,
+}
+
+impl UnsignedBatch {
+    /// A batch for `--output-unsigned-batch`, when given. Every transaction in it shares one
+    /// blockhash, so the batch has to be signed and broadcast before that expires, within a
+    /// minute or two. A durable nonce would not help, as the first transaction to land would
+    /// advance it under the rest; `broadcast_batch` refuses the batch once it has expired.
+    pub fn from_matches(app_matches: &ArgMatches, client: &FailoverClient) -> Option<Self> {
+        let path = app_matches.value_of("output_unsigned_batch")?;
+        let recent_blockhash = client
+            .get_recent_blockhash()
+            .unwrap_or_else(|err| exit_with_error(err.into(), json!({ "batch": path })))
+            .0;
+        Some(UnsignedBatch {
+            path: path.to_owned(),
+            recent_blockhash,
+            entries: vec![],
+        })
+    }
+
+    /// Adds `transaction`, labelled with the item it was built for.
+    pub fn push(&mut self, label: &str, mut transaction: Transaction) {
+        transaction.message.recent_blockhash = self.recent_blockhash;
+        let signers: Vec<String> = transaction.message.account_keys
+            [..transaction.message.header.num_required_signatures as usize]
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        let serialized = bincode::serialize(&transaction).unwrap();
+        self.entries.push(json!({
+            "label": label,
+            "transaction": bs58::encode(serialized).into_string(),
+            "signers": signers,
+            "signatures": [],
+        }));
+    }
+
+    pub fn write(&self) {
+        let batch = json!({
+            "blockhash": self.recent_blockhash.to_string(),
+            "transactions": self.entries,
+        });
+        if let Err(err) = fs::write(&self.path, serde_json::to_string_pretty(&batch).unwrap()) {
+            exit_with_error(CliError::from(err), json!({ "batch": self.path }));
+        }
+        println!(
+            "Wrote {} unsigned transactions to {}, sign and broadcast them before blockhash {} \
+             expires in a minute or two",
+            self.entries.len(),
+            self.path,
+            self.recent_blockhash
+        );
+    }
+}
+
+/// Decodes a batch entry into its transaction with the `PUBKEY=SIGNATURE` pairs of its
+/// `signatures` list filled in, failing unless every required signature is present and valid.
+pub fn signed_transaction(entry: &Value) -> Result<Transaction, String> {
+    let encoded = entry["transaction"]
+        .as_str()
+        .ok_or("Entry has no transaction")?;
+    let bytes = bs58::decode(encoded)
+        .into_vec()
+        .map_err(|err| format!("Transaction is not base58: {}", err))?;
+    let mut transaction: Transaction = bincode::deserialize(&bytes)
+        .map_err(|err| format!("Transaction does not decode: {}", err))?;
+    for pair in entry["signatures"].as_array().into_iter().flatten() {
+        let pair = pair.as_str().unwrap_or_default();
+        let (pubkey, signature) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("{} is not PUBKEY=SIGNATURE", pair))?;
+        let pubkey = Pubkey::from_str(pubkey).map_err(|err| format!("{}: {}", pair, err))?;
+        let signature =
+            Signature::from_str(signature).map_err(|err| format!("{}: {}", pair, err))?;
+        let position = transaction
+            .get_signing_keypair_positions(&[pubkey])
+            .unwrap()[0]
+            .ok_or_else(|| format!("{} does not sign this transaction", pubkey))?;
+        transaction.signatures[position] = signature;
+    }
+    let message = transaction.message_data();
+    for (pubkey, signature) in transaction
+        .message
+        .account_keys
+        .iter()
+        .zip(&transaction.signatures)
+    {
+        if *signature == Signature::default() {
+            return Err(format!("Missing the signature of {}", pubkey));
+        }
+        if !signature.verify(pubkey.as_ref(), &message) {
+            return Err(format!("Invalid signature for {}", pubkey));
+        }
+    }
+    Ok(transaction)
+}
+
+/// Sends `transaction` until it confirms, giving up after `retries` retryable failures. The
+/// transaction is signed elsewhere, so it is sent again as is rather than re-signed.
+fn broadcast(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    transaction: &Transaction,
+    retries: usize,
+) -> Result<Signature, ClientError> {
+    let mut attempt = 0;
+    loop {
+        check_payer_balance(app_matches, client, &transaction.message.account_keys[0]);
+        throttle(app_matches);
+        record_attempt();
+        let err = match client.send_and_confirm_transaction(transaction) {
+            Ok(signature) => return Ok(signature),
+            Err(err) => err,
+        };
+        match classify_send_error(&err, &[]) {
+            SendFailure::Retryable if attempt < retries => {
+                record_retry(RetryReason::of(&err));
+                attempt += 1;
+                eprintln!("Send failed: {}. Retry {} of {}.", err, attempt, retries);
+                backoff(app_matches, Duration::from_millis(1000));
+            }
+            _ => return Err(err),
+        }
+    }
+}
+
+/// Whether `blockhash` can still land a transaction, exiting when the cluster cannot tell.
+fn blockhash_valid(client: &FailoverClient, blockhash: &Hash, context: &Value) -> bool {
+    client
+        .is_blockhash_valid(blockhash)
+        .unwrap_or_else(|err| exit_with_error(err.into(), context.clone()))
+}
+
+/// Submits the transactions of a batch written by `--output-unsigned-batch` once they have been
+/// signed, printing the outcome of each. A batch whose blockhash has expired is refused, and
+/// transactions left when it expires part way through are skipped.
+pub fn broadcast_batch(app_matches: &ArgMatches, _payer: Box<dyn Signer>, client: FailoverClient) {
+    let path = app_matches.value_of("file").unwrap();
+    let retries = value_of::<usize>(app_matches, "retries").unwrap();
+    let context = json!({ "file": path });
+    let contents =
+        fs::read_to_string(path).unwrap_or_else(|err| exit_with_error(err.into(), context.clone()));
+    let batch: Value = serde_json::from_str(&contents).unwrap_or_else(|err| {
+        exit_with_error(
+            CliError::InvalidInput(format!("{} is not a batch: {}", path, err)),
+            context.clone(),
+        )
+    });
+    let entries = batch["transactions"].as_array().unwrap_or_else(|| {
+        exit_with_error(
+            CliError::InvalidInput(format!("{} has no transactions", path)),
+            context.clone(),
+        )
+    });
+    let blockhash = batch["blockhash"]
+        .as_str()
+        .and_then(|blockhash| Hash::from_str(blockhash).ok())
+        .unwrap_or_else(|| {
+            exit_with_error(
+                CliError::InvalidInput(format!("{} has no blockhash", path)),
+                context.clone(),
+            )
+        });
+    if !blockhash_valid(&client, &blockhash, &context) {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "Blockhash {} of {} has expired, build the batch again with \
+                 --output-unsigned-batch",
+                blockhash, path
+            )),
+            context,
+        );
+    }
+
+    let mut summary = Summary::start();
+    for (index, entry) in entries.iter().enumerate() {
+        let label = entry["label"].as_str().unwrap_or_default();
+        let context = json!({ "index": index, "label": label });
+        let transaction = match signed_transaction(entry) {
+            Ok(transaction) => transaction,
+            Err(message) => {
+                report_item_error(
+                    &format!("Skipping {}.", label),
+                    &CliError::InvalidInput(message),
+                    context,
+                );
+                summary.skipped();
+                continue;
+            }
+        };
+        if !blockhash_valid(&client, &transaction.message.recent_blockhash, &context) {
+            report_item_error(
+                &format!("Skipping {}.", label),
+                &CliError::InvalidInput(format!(
+                    "Blockhash {} has expired",
+                    transaction.message.recent_blockhash
+                )),
+                context,
+            );
+            summary.skipped();
+            continue;
+        }
+        match broadcast(app_matches, &client, &transaction, retries) {
+            Ok(signature) => {
+                println!("{}: {}", label, signature);
+                summary.succeeded();
+            }
+            Err(err) => {
+                report_item_error(
+                    &format!("Transaction failed for {}.", label),
+                    &err.into(),
+                    context,
+                );
+                summary.failed();
+            }
+        }
+    }
+    summary.print();
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{signature::Keypair, system_instruction::transfer},
+    };
+
+    #[test]
+    fn batch_signatures_are_filled_in() {
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let transaction = Transaction::new_with_payer(
+            &[transfer(&payer.pubkey(), &recipient, 1)],
+            Some(&payer.pubkey()),
+        );
+        let serialized = bs58::encode(bincode::serialize(&transaction).unwrap()).into_string();
+        let mut entry = json!({ "label": "refund", "transaction": serialized, "signatures": [] });
+        assert!(signed_transaction(&entry)
+            .unwrap_err()
+            .starts_with("Missing the signature"));
+
+        let signature = payer.sign_message(&transaction.message_data());
+        entry["signatures"] = json!([format!("{}={}", payer.pubkey(), signature)]);
+        assert_eq!(
+            signed_transaction(&entry).unwrap().signatures,
+            vec![signature]
+        );
+
+        entry["signatures"] = json!([format!("{}={}", recipient, signature)]);
+        assert!(signed_transaction(&entry).is_err());
+    }
+}
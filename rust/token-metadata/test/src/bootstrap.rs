@@ -0,0 +1,179 @@
+use {
+    super::send_and_confirm,
+    crate::{
+        error::{exit_with_error, CliError},
+        rpc::FailoverClient,
+    },
+    clap::ArgMatches,
+    serde_json::json,
+    solana_clap_utils::input_parsers::value_of,
+    solana_client::client_error::reqwest::Url,
+    solana_program::{bpf_loader, loader_instruction},
+    solana_sdk::{
+        native_token::sol_to_lamports,
+        packet::PACKET_DATA_SIZE,
+        signature::{read_keypair_file, Keypair, Signer},
+        system_instruction::create_account,
+        transaction::Transaction,
+    },
+    std::{
+        fs,
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+/// Hosts `bootstrap` accepts as a local validator.
+const LOCAL_HOSTS: &[&str] = &["localhost", "127.0.0.1", "0.0.0.0", "[::1]"];
+
+/// How long the faucet's airdrop has to confirm.
+const AIRDROP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether `url` is served over plain HTTP by the local machine.
+fn is_local(url: &str) -> bool {
+    Url::parse(url).map_or(false, |url| {
+        url.scheme() == "http"
+            && url
+                .host_str()
+                .map_or(false, |host| LOCAL_HOSTS.contains(&host))
+    })
+}
+
+/// Program bytes that fit in one loader write alongside the transaction around them.
+fn write_chunk_size(payer: &dyn Signer, program: &Keypair) -> usize {
+    let instruction = loader_instruction::write(&program.pubkey(), &bpf_loader::id(), 0, vec![]);
+    let transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    // The length prefix of the bytes grows by up to two bytes once they are filled in.
+    PACKET_DATA_SIZE - bincode::serialized_size(&transaction).unwrap() as usize - 2
+}
+
+/// Requests `lamports` for `payer` from the validator's faucet and waits up to
+/// `AIRDROP_TIMEOUT` for them to land.
+fn airdrop(client: &FailoverClient, payer: &dyn Signer, lamports: u64) {
+    let context = json!({ "payer": payer.pubkey().to_string(), "lamports": lamports });
+    let signature = client
+        .request_airdrop(&payer.pubkey(), lamports)
+        .unwrap_or_else(|err| exit_with_error(err.into(), context.clone()));
+    let requested = Instant::now();
+    while !client
+        .confirm_transaction(&signature)
+        .unwrap_or_else(|err| exit_with_error(err.into(), context.clone()))
+    {
+        if requested.elapsed() >= AIRDROP_TIMEOUT {
+            exit_with_error(
+                CliError::InvalidInput(format!(
+                    "Airdrop {} did not confirm within {}s, is the validator running?",
+                    signature,
+                    AIRDROP_TIMEOUT.as_secs()
+                )),
+                context,
+            );
+        }
+        sleep(Duration::from_millis(500));
+    }
+    println!(
+        "Airdropped {} lamports to {} in {}",
+        lamports,
+        payer.pubkey(),
+        signature
+    );
+}
+
+/// Deploys the program at `path` with the non-upgradeable BPF loader: creates its account,
+/// writes the program into it chunk by chunk and finalizes it.
+fn deploy(app_matches: &ArgMatches, client: &FailoverClient, payer: &dyn Signer, path: &str) {
+    let context = json!({ "program": path });
+    let program_data =
+        fs::read(path).unwrap_or_else(|err| exit_with_error(err.into(), context.clone()));
+    let program = match app_matches.value_of("program_keypair") {
+        Some(keypair_path) => read_keypair_file(keypair_path).unwrap_or_else(|err| {
+            exit_with_error(
+                CliError::InvalidInput(format!("Cannot read {}: {}", keypair_path, err)),
+                context.clone(),
+            )
+        }),
+        None => Keypair::new(),
+    };
+    let signers: [&dyn Signer; 2] = [payer, &program];
+    let send = |instructions: &[_]| {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        let recent_blockhash = client.get_recent_blockhash().unwrap().0;
+        transaction.sign(&signers, recent_blockhash);
+        if let Err(err) = send_and_confirm(app_matches, client, &mut transaction, &signers) {
+            exit_with_error(err.into(), context.clone());
+        }
+    };
+
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(program_data.len())
+        .unwrap();
+    send(&[create_account(
+        &payer.pubkey(),
+        &program.pubkey(),
+        rent,
+        program_data.len() as u64,
+        &bpf_loader::id(),
+    )]);
+    let chunk_size = write_chunk_size(payer, &program);
+    let chunks = program_data.chunks(chunk_size);
+    let count = chunks.len();
+    for (index, chunk) in chunks.enumerate() {
+        println!("Writing chunk {} of {}", index + 1, count);
+        send(&[loader_instruction::write(
+            &program.pubkey(),
+            &bpf_loader::id(),
+            (index * chunk_size) as u32,
+            chunk.to_vec(),
+        )]);
+    }
+    send(&[loader_instruction::finalize(
+        &program.pubkey(),
+        &bpf_loader::id(),
+    )]);
+    println!("Deployed {} as {}", path, program.pubkey());
+    println!(
+        "Pass --metadata-program {} to run the other commands against it",
+        program.pubkey()
+    );
+}
+
+/// Readies a local validator, such as one started with `solana-test-validator`, for the other
+/// commands:
+///
+/// 1. Checks that every `--url` points at the local machine, so no real cluster is touched
+///    even after failing over.
+/// 2. Airdrops `--sol` to the payer from the validator's faucet.
+/// 3. With `--program`, deploys that build of the token metadata program to a new address, or
+///    to `--program-keypair`, and prints the `--metadata-program` to use it with.
+pub fn bootstrap(app_matches: &ArgMatches, payer: Box<dyn Signer>, client: FailoverClient) {
+    if let Some(url) = client.urls().iter().find(|url| !is_local(url)) {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "{} is not a local validator, pass only --url http://127.0.0.1:8899",
+                url
+            )),
+            json!({ "url": url }),
+        );
+    }
+    let sol = value_of::<f64>(app_matches, "sol").unwrap();
+    airdrop(&client, payer.as_ref(), sol_to_lamports(sol));
+    if let Some(path) = app_matches.value_of("program") {
+        deploy(app_matches, &client, payer.as_ref(), path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_local_hosts_are_local() {
+        assert!(is_local("http://localhost:8899"));
+        assert!(is_local("http://127.0.0.1:8899/"));
+        assert!(is_local("http://[::1]:8899"));
+        assert!(!is_local("http://localhost.evil.com:8899"));
+        assert!(!is_local("http://127.0.0.1.nip.io"));
+        assert!(!is_local("https://api.devnet.solana.com"));
+        assert!(!is_local("not a url"));
+    }
+}
@@ -0,0 +1,72 @@
+use {
+    crate::error::{exit_with_error, CliError},
+    clap::ArgMatches,
+    serde_json::json,
+    std::io::{self, BufRead, Write},
+};
+
+/// What the operator typed at a `--confirm-each` prompt.
+#[derive(Debug, PartialEq)]
+pub enum Answer {
+    Send,
+    Skip,
+    Abort,
+}
+
+impl Answer {
+    /// Reads one line of input: enter sends, and skip or abort may be shortened to their first
+    /// letter.
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "" | "y" | "yes" | "send" => Some(Answer::Send),
+            "s" | "skip" => Some(Answer::Skip),
+            "a" | "abort" => Some(Answer::Abort),
+            _ => None,
+        }
+    }
+}
+
+/// With `--confirm-each` and without `--yes`, prints `action` and waits for the operator to
+/// press enter to send it or type skip or abort. Returns whether to send; abort, or the end of
+/// the input, ends the run.
+pub fn confirm_step(app_matches: &ArgMatches, action: &str) -> bool {
+    if !app_matches.is_present("confirm_each") || app_matches.is_present("yes") {
+        return true;
+    }
+    let stdin = io::stdin();
+    loop {
+        print!("{}\nSend? [enter / skip / abort] ", action);
+        io::stdout().flush().unwrap();
+        let mut input = String::new();
+        let answer = match stdin.lock().read_line(&mut input) {
+            Ok(0) => Some(Answer::Abort),
+            Ok(_) => Answer::parse(&input),
+            Err(err) => exit_with_error(err.into(), json!({ "action": action })),
+        };
+        match answer {
+            Some(Answer::Send) => return true,
+            Some(Answer::Skip) => {
+                println!("Skipped");
+                return false;
+            }
+            Some(Answer::Abort) => exit_with_error(
+                CliError::InvalidInput("Aborted by the operator".to_owned()),
+                json!({ "action": action }),
+            ),
+            None => println!("Press enter to send, or type skip or abort"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirm_answers() {
+        assert_eq!(Answer::parse("\n"), Some(Answer::Send));
+        assert_eq!(Answer::parse("Skip\n"), Some(Answer::Skip));
+        assert_eq!(Answer::parse(" a "), Some(Answer::Abort));
+        assert_eq!(Answer::parse("maybe"), None);
+    }
+}
@@ -0,0 +1,53 @@
+use {
+    serde_json::Value,
+    std::io::{self, Write},
+};
+
+/// Quotes `field` when it holds a comma, quote or line break, doubling any quotes inside.
+pub fn escape_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Writes `rows`, JSON objects such as those the list commands print with `--output json`, as
+/// CSV with a header row of `columns`. Strings are written bare, missing fields and nulls as
+/// empty fields and anything else as its JSON text.
+pub fn write_csv(out: &mut impl Write, columns: &[&str], rows: &[Value]) -> io::Result<()> {
+    writeln!(out, "{}", columns.join(","))?;
+    for row in rows {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|column| match &row[*column] {
+                Value::Null => String::new(),
+                Value::String(value) => escape_field(value),
+                value => escape_field(&value.to_string()),
+            })
+            .collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    #[test]
+    fn csv_fields_are_escaped() {
+        let rows = [
+            json!({ "name": "Llama, \"Tuco\"", "count": 2 }),
+            json!({ "name": "Plain" }),
+        ];
+        let mut out = vec![];
+
+        write_csv(&mut out, &["name", "count"], &rows).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name,count\n\"Llama, \"\"Tuco\"\"\",2\nPlain,\n"
+        );
+    }
+}
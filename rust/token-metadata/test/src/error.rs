@@ -0,0 +1,339 @@
+use {
+    serde_json::{json, Value},
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        rpc_request::{RpcError, RpcResponseErrorData},
+        rpc_response::RpcSimulateTransactionResult,
+    },
+    solana_sdk::{
+        instruction::{Instruction, InstructionError},
+        pubkey::Pubkey,
+        signature::Signature,
+        system_program,
+        transaction::TransactionError,
+    },
+    std::sync::atomic::{AtomicBool, Ordering},
+    thiserror::Error,
+};
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+static STRICT: AtomicBool = AtomicBool::new(false);
+static DUMP_LOGS: AtomicBool = AtomicBool::new(false);
+
+/// Errors surfaced by the test client.
+#[derive(Debug, Error)]
+pub enum CliError {
+    /// An argument or input file entry was invalid
+    #[error("{0}")]
+    InvalidInput(String),
+
+    /// An account the command reads does not exist
+    #[error("{0}")]
+    AccountNotFound(String),
+
+    /// The RPC request or the transaction it submitted failed
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    /// Reading or writing a local file failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// A JSON file could not be parsed or written
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl CliError {
+    /// Stable name of the error variant, used as the `error` field of JSON output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CliError::InvalidInput(_) => "invalid_input",
+            CliError::AccountNotFound(_) => "account_not_found",
+            CliError::Client(_) => "client",
+            CliError::Io(_) => "io",
+            CliError::Json(_) => "json",
+        }
+    }
+}
+
+/// Switches error reporting to single-line JSON objects on stderr.
+pub fn set_json_errors(enabled: bool) {
+    JSON_ERRORS.store(enabled, Ordering::SeqCst);
+    if enabled {
+        // Everything that still unwraps ends up here, so report panics in the same shape.
+        std::panic::set_hook(Box::new(|info| {
+            let message = match info.payload().downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => match info.payload().downcast_ref::<String>() {
+                    Some(message) => message.clone(),
+                    None => "unknown panic".to_owned(),
+                },
+            };
+            let context = match info.location() {
+                Some(location) => json!({ "location": location.to_string() }),
+                None => Value::Null,
+            };
+            print_json_error("panic", &message, context, None);
+        }));
+    }
+}
+
+pub fn json_errors() -> bool {
+    JSON_ERRORS.load(Ordering::SeqCst)
+}
+
+fn print_json_error(kind: &str, message: &str, context: Value, logs: Option<&[String]>) {
+    let mut error = json!({ "error": kind, "message": message, "context": context });
+    if let Some(logs) = logs {
+        error["logs"] = json!(logs);
+    }
+    eprintln!("{}", error);
+}
+
+/// Makes reported transaction failures print the program logs of the failed transaction.
+pub fn set_dump_logs(enabled: bool) {
+    DUMP_LOGS.store(enabled, Ordering::SeqCst);
+}
+
+pub fn dump_logs() -> bool {
+    DUMP_LOGS.load(Ordering::SeqCst)
+}
+
+/// The program logs of a transaction that failed its preflight simulation, which the RPC node
+/// returns with the error but which the error only prints the count of.
+pub fn preflight_logs(err: &ClientError) -> Option<&[String]> {
+    match err.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data:
+                RpcResponseErrorData::SendTransactionPreflightFailure(RpcSimulateTransactionResult {
+                    logs: Some(logs),
+                    ..
+                }),
+            ..
+        }) => Some(logs),
+        _ => None,
+    }
+}
+
+/// The logs to report along with `err`, with `--dump-logs`.
+fn logs_of(err: &CliError) -> Option<&[String]> {
+    match err {
+        CliError::Client(err) if dump_logs() => preflight_logs(err),
+        _ => None,
+    }
+}
+
+fn print_logs(logs: &[String], print: impl Fn(&str)) {
+    print("Program logs:");
+    for log in logs {
+        print(&format!("  {}", log));
+    }
+}
+
+/// Reports the program logs of transaction `signature`, which landed but failed, for
+/// `--dump-logs`.
+pub fn report_logs(signature: &Signature, logs: &[String]) {
+    if json_errors() {
+        eprintln!(
+            "{}",
+            json!({ "signature": signature.to_string(), "logs": logs })
+        );
+    } else {
+        println!("Transaction {} failed.", signature);
+        print_logs(logs, |line| println!("{}", line));
+    }
+}
+
+/// Reports a failure for a single item of a bulk command and lets the command carry on.
+pub fn report_item_error(message: &str, err: &CliError, context: Value) {
+    if json_errors() {
+        print_json_error(
+            err.kind(),
+            &format!("{} {}", message, err),
+            context,
+            logs_of(err),
+        );
+    } else {
+        println!("{} {}", message, err);
+        if let Some(logs) = logs_of(err) {
+            print_logs(logs, |line| println!("{}", line));
+        }
+    }
+}
+
+/// Makes `report_undecodable` exit instead of letting the command skip the account.
+pub fn set_strict(enabled: bool) {
+    STRICT.store(enabled, Ordering::SeqCst);
+}
+
+/// Reports an account that does not decode, which the command then skips. With `--strict` the
+/// command exits instead, so malformed accounts are not passed over unnoticed.
+pub fn report_undecodable(key: &Pubkey, err: &str) {
+    let context = json!({ "account": key.to_string() });
+    let err = CliError::InvalidInput(format!("{} does not decode: {}", key, err));
+    if STRICT.load(Ordering::SeqCst) {
+        exit_with_error(err, context);
+    }
+    report_item_error("Skipping.", &err, context);
+}
+
+/// Reports a fatal error and exits nonzero.
+pub fn exit_with_error(err: CliError, context: Value) -> ! {
+    if json_errors() {
+        print_json_error(err.kind(), &err.to_string(), context, logs_of(&err));
+    } else {
+        eprintln!("Error: {}", err);
+        if let Some(logs) = logs_of(&err) {
+            print_logs(logs, |line| eprintln!("{}", line));
+        }
+    }
+    std::process::exit(1);
+}
+
+/// What a failed send means for the item a bulk command was sending.
+#[derive(Debug, PartialEq)]
+pub enum SendFailure {
+    /// The RPC node or the network failed, the same transaction may be sent again.
+    Retryable,
+    /// Instruction `index` of the transaction failed with `error`.
+    Program {
+        index: usize,
+        error: InstructionError,
+    },
+    /// Nothing else will land either, such as when the payer is out of funds.
+    Fatal,
+}
+
+/// Whether `err` shows that the transaction it was returned for did not take effect and never
+/// will: it failed its preflight simulation or on chain, or was never sent. Other errors, such
+/// as a timeout, leave open whether it landed, and so does a transaction found to be already
+/// processed.
+pub fn send_definitely_failed(err: &ClientError) -> bool {
+    match err.get_transaction_error() {
+        Some(TransactionError::AlreadyProcessed) => false,
+        Some(_) => true,
+        None => matches!(
+            err.kind(),
+            ClientErrorKind::SigningError(_)
+                | ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                    data: RpcResponseErrorData::SendTransactionPreflightFailure(_),
+                    ..
+                })
+        ),
+    }
+}
+
+/// System program error returned when an account cannot cover a transfer or account creation.
+const SYSTEM_INSUFFICIENT_FUNDS: u32 = 1;
+
+/// Sorts `err`, returned for a transaction made of `instructions`, into a `SendFailure`.
+pub fn classify_send_error(err: &ClientError, instructions: &[Instruction]) -> SendFailure {
+    match err.get_transaction_error() {
+        None => match err.kind() {
+            ClientErrorKind::SigningError(_) => SendFailure::Fatal,
+            _ => SendFailure::Retryable,
+        },
+        Some(TransactionError::BlockhashNotFound) => SendFailure::Retryable,
+        Some(TransactionError::InsufficientFundsForFee)
+        | Some(TransactionError::AccountNotFound) => SendFailure::Fatal,
+        Some(TransactionError::InstructionError(index, error)) => {
+            let index = index as usize;
+            let from_system_program = instructions.get(index).map_or(false, |instruction| {
+                instruction.program_id == system_program::id()
+            });
+            if from_system_program && error == InstructionError::Custom(SYSTEM_INSUFFICIENT_FUNDS) {
+                SendFailure::Fatal
+            } else {
+                SendFailure::Program { index, error }
+            }
+        }
+        Some(_) => SendFailure::Fatal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::pubkey::Pubkey};
+
+    #[test]
+    fn classify_program_and_funding_errors() {
+        let instructions = [
+            solana_sdk::system_instruction::create_account(
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                1,
+                0,
+                &Pubkey::new_unique(),
+            ),
+            Instruction::new_with_bytes(spl_token_metadata::id(), &[], vec![]),
+        ];
+        let failure = |index, code| {
+            let err = ClientError::from(TransactionError::InstructionError(
+                index,
+                InstructionError::Custom(code),
+            ));
+            classify_send_error(&err, &instructions)
+        };
+
+        assert_eq!(failure(0, SYSTEM_INSUFFICIENT_FUNDS), SendFailure::Fatal);
+        assert_eq!(
+            failure(1, SYSTEM_INSUFFICIENT_FUNDS),
+            SendFailure::Program {
+                index: 1,
+                error: InstructionError::Custom(SYSTEM_INSUFFICIENT_FUNDS)
+            }
+        );
+    }
+
+    #[test]
+    fn classify_network_errors_as_retryable() {
+        let err = ClientError::from(std::io::Error::new(std::io::ErrorKind::Other, "reset"));
+
+        assert_eq!(classify_send_error(&err, &[]), SendFailure::Retryable);
+        assert_eq!(
+            classify_send_error(&TransactionError::BlockhashNotFound.into(), &[]),
+            SendFailure::Retryable
+        );
+    }
+
+    #[test]
+    fn only_definite_send_failures_are_settled() {
+        let failed = ClientError::from(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(1),
+        ));
+        assert!(send_definitely_failed(&failed));
+        let processed = ClientError::from(TransactionError::AlreadyProcessed);
+        assert!(!send_definitely_failed(&processed));
+        let timeout = ClientError::from(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "timed out",
+        ));
+        assert!(!send_definitely_failed(&timeout));
+    }
+
+    #[test]
+    fn preflight_failures_carry_logs() {
+        let logs = vec![
+            "Program metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s invoke [1]".to_owned(),
+            "Program log: Error: DerivedKeyInvalid".to_owned(),
+        ];
+        let err = ClientError::from(RpcError::RpcResponseError {
+            code: -32002,
+            message: "Transaction simulation failed".to_owned(),
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(
+                RpcSimulateTransactionResult {
+                    err: None,
+                    logs: Some(logs.clone()),
+                    accounts: None,
+                },
+            ),
+        });
+        assert_eq!(preflight_logs(&err), Some(&logs[..]));
+        assert_eq!(
+            preflight_logs(&ClientError::from(TransactionError::AccountNotFound)),
+            None
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! The expressions `scan --filter` evaluates against each metadata account, such as
+//! `seller_fee > 500 && !mutable` or `symbol == "LLAMA" && name contains "Gold"`.
+//!
+//! Fields are read from the decoded `Metadata`, with the padding of its strings stripped:
+//!
+//! | field                   | type   |                                            |
+//! |-------------------------|--------|--------------------------------------------|
+//! | `name`, `symbol`, `uri` | text   |                                            |
+//! | `update_authority`      | text   | base58                                     |
+//! | `mint`                  | text   | base58                                     |
+//! | `seller_fee`            | number | basis points                               |
+//! | `creators`              | number | how many creators are listed               |
+//! | `verified`              | bool   | whether every listed creator has verified  |
+//! | `mutable`               | bool   |                                            |
+//! | `primary_sale`          | bool   | whether the primary sale has happened      |
+//!
+//! Numbers compare with `==`, `!=`, `<`, `<=`, `>` and `>=`, text with `==`, `!=` and
+//! `contains`, and booleans with `==` and `!=`. Conditions combine with `!`, `&&`, `||` and
+//! parentheses. Expressions are checked when parsed, so a filter that parses cannot fail on an
+//! account.
+
+use {
+    super::strip_padding,
+    spl_token_metadata::state::Metadata,
+    std::{iter::Peekable, str::CharIndices},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Type {
+    Bool,
+    Number,
+    Text,
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    Bool(bool),
+    Number(u64),
+    Text(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Name,
+    Symbol,
+    Uri,
+    UpdateAuthority,
+    Mint,
+    SellerFee,
+    Creators,
+    Verified,
+    Mutable,
+    PrimarySale,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "name" => Field::Name,
+            "symbol" => Field::Symbol,
+            "uri" => Field::Uri,
+            "update_authority" => Field::UpdateAuthority,
+            "mint" => Field::Mint,
+            "seller_fee" => Field::SellerFee,
+            "creators" => Field::Creators,
+            "verified" => Field::Verified,
+            "mutable" => Field::Mutable,
+            "primary_sale" => Field::PrimarySale,
+            _ => return None,
+        })
+    }
+
+    fn ty(self) -> Type {
+        match self {
+            Field::Name | Field::Symbol | Field::Uri | Field::UpdateAuthority | Field::Mint => {
+                Type::Text
+            }
+            Field::SellerFee | Field::Creators => Type::Number,
+            Field::Verified | Field::Mutable | Field::PrimarySale => Type::Bool,
+        }
+    }
+
+    fn read(self, metadata: &Metadata) -> Value {
+        let creators = metadata.data.creators.as_deref().unwrap_or_default();
+        match self {
+            Field::Name => Value::Text(strip_padding(&metadata.data.name).to_owned()),
+            Field::Symbol => Value::Text(strip_padding(&metadata.data.symbol).to_owned()),
+            Field::Uri => Value::Text(strip_padding(&metadata.data.uri).to_owned()),
+            Field::UpdateAuthority => Value::Text(metadata.update_authority.to_string()),
+            Field::Mint => Value::Text(metadata.mint.to_string()),
+            Field::SellerFee => Value::Number(metadata.data.seller_fee_basis_points as u64),
+            Field::Creators => Value::Number(creators.len() as u64),
+            Field::Verified => Value::Bool(creators.iter().all(|creator| creator.verified)),
+            Field::Mutable => Value::Bool(metadata.is_mutable),
+            Field::PrimarySale => Value::Bool(metadata.primary_sale_happened),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+impl Op {
+    fn applies_to(self, ty: Type) -> bool {
+        match self {
+            Op::Eq | Op::Ne => true,
+            Op::Lt | Op::Le | Op::Gt | Op::Ge => ty == Type::Number,
+            Op::Contains => ty == Type::Text,
+        }
+    }
+
+    fn apply(self, left: &Value, right: &Value) -> bool {
+        match (self, left, right) {
+            (Op::Eq, left, right) => left == right,
+            (Op::Ne, left, right) => left != right,
+            (Op::Lt, Value::Number(left), Value::Number(right)) => left < right,
+            (Op::Le, Value::Number(left), Value::Number(right)) => left <= right,
+            (Op::Gt, Value::Number(left), Value::Number(right)) => left > right,
+            (Op::Ge, Value::Number(left), Value::Number(right)) => left >= right,
+            (Op::Contains, Value::Text(left), Value::Text(right)) => left.contains(right.as_str()),
+            _ => unreachable!("operands are checked when parsed"),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    Field(Field),
+    Literal(Value),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, Op, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, metadata: &Metadata) -> Value {
+        match self {
+            Expr::Field(field) => field.read(metadata),
+            Expr::Literal(Value::Bool(value)) => Value::Bool(*value),
+            Expr::Literal(Value::Number(value)) => Value::Number(*value),
+            Expr::Literal(Value::Text(value)) => Value::Text(value.clone()),
+            Expr::Not(expr) => Value::Bool(!expr.test(metadata)),
+            Expr::And(left, right) => Value::Bool(left.test(metadata) && right.test(metadata)),
+            Expr::Or(left, right) => Value::Bool(left.test(metadata) || right.test(metadata)),
+            Expr::Compare(left, op, right) => {
+                Value::Bool(op.apply(&left.eval(metadata), &right.eval(metadata)))
+            }
+        }
+    }
+
+    fn test(&self, metadata: &Metadata) -> bool {
+        self.eval(metadata) == Value::Bool(true)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(u64),
+    Text(String),
+    Op(Op),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+/// Consumes the next character when it is `expected`.
+fn next_is(chars: &mut Peekable<CharIndices>, expected: char) -> bool {
+    chars.next_if(|(_, next)| *next == expected).is_some()
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if next_is(&mut chars, '&') => Token::And,
+            '|' if next_is(&mut chars, '|') => Token::Or,
+            '=' if next_is(&mut chars, '=') => Token::Op(Op::Eq),
+            '!' if next_is(&mut chars, '=') => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '<' if next_is(&mut chars, '=') => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if next_is(&mut chars, '=') => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => text.push(c),
+                        None => return Err(format!("Unterminated string at {}", start)),
+                    }
+                }
+                Token::Text(text)
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = start + 1;
+                while let Some((index, _)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    end = index + 1;
+                }
+                let number = &input[start..end];
+                Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| format!("{} is too large", number))?,
+                )
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = start + 1;
+                while let Some((index, _)) =
+                    chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    end = index + 1;
+                }
+                match &input[start..end] {
+                    "contains" => Token::Op(Op::Contains),
+                    ident => Token::Ident(ident.to_owned()),
+                }
+            }
+            c => return Err(format!("Unexpected {:?} at {}", c, start)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Unwraps a typed expression that has to be a condition.
+fn condition((expr, ty): (Expr, Type)) -> Result<Expr, String> {
+    match ty {
+        Type::Bool => Ok(expr),
+        ty => Err(format!("{:?} is a {:?}, not a condition", expr, ty)),
+    }
+}
+
+/// Recursive descent over the tokens, from the loosest binding `||` down to single values,
+/// typing each expression as it is built.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Consumes `token` when it is next.
+    fn take(&mut self, token: &Token) -> bool {
+        let taken = self.tokens.get(self.position) == Some(token);
+        if taken {
+            self.position += 1;
+        }
+        taken
+    }
+
+    fn or(&mut self) -> Result<(Expr, Type), String> {
+        let (mut left, ty) = self.and()?;
+        if !self.take(&Token::Or) {
+            return Ok((left, ty));
+        }
+        loop {
+            left = Expr::Or(
+                Box::new(condition((left, ty))?),
+                Box::new(condition(self.and()?)?),
+            );
+            if !self.take(&Token::Or) {
+                return Ok((left, Type::Bool));
+            }
+        }
+    }
+
+    fn and(&mut self) -> Result<(Expr, Type), String> {
+        let (mut left, ty) = self.not()?;
+        if !self.take(&Token::And) {
+            return Ok((left, ty));
+        }
+        loop {
+            left = Expr::And(
+                Box::new(condition((left, ty))?),
+                Box::new(condition(self.not()?)?),
+            );
+            if !self.take(&Token::And) {
+                return Ok((left, Type::Bool));
+            }
+        }
+    }
+
+    fn not(&mut self) -> Result<(Expr, Type), String> {
+        if self.take(&Token::Not) {
+            let expr = condition(self.not()?)?;
+            return Ok((Expr::Not(Box::new(expr)), Type::Bool));
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<(Expr, Type), String> {
+        let (left, left_type) = self.value()?;
+        let op = match self.tokens.get(self.position) {
+            Some(Token::Op(op)) => *op,
+            _ => return Ok((left, left_type)),
+        };
+        self.position += 1;
+        let (right, right_type) = self.value()?;
+        if left_type != right_type {
+            return Err(format!(
+                "Cannot compare {:?} ({:?}) with {:?} ({:?})",
+                left, left_type, right, right_type
+            ));
+        }
+        if !op.applies_to(left_type) {
+            return Err(format!("{:?} does not apply to {:?}", op, left_type));
+        }
+        Ok((
+            Expr::Compare(Box::new(left), op, Box::new(right)),
+            Type::Bool,
+        ))
+    }
+
+    fn value(&mut self) -> Result<(Expr, Type), String> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                if !self.take(&Token::Close) {
+                    return Err("Missing )".to_owned());
+                }
+                Ok(expr)
+            }
+            Some(Token::Number(number)) => Ok((Expr::Literal(Value::Number(number)), Type::Number)),
+            Some(Token::Text(text)) => Ok((Expr::Literal(Value::Text(text)), Type::Text)),
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Ok((Expr::Literal(Value::Bool(true)), Type::Bool)),
+                "false" => Ok((Expr::Literal(Value::Bool(false)), Type::Bool)),
+                _ => Field::parse(&ident)
+                    .map(|field| (Expr::Field(field), field.ty()))
+                    .ok_or_else(|| format!("Unknown field {}", ident)),
+            },
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Unexpected end of the filter".to_owned()),
+        }
+    }
+}
+
+/// A parsed `--filter` expression.
+#[derive(Debug)]
+pub struct Filter(Expr);
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            position: 0,
+        };
+        let expr = condition(parser.or()?)?;
+        match parser.next() {
+            None => Ok(Filter(expr)),
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+        }
+    }
+
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        self.0.test(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_program::pubkey::Pubkey,
+        spl_token_metadata::state::{Creator, Data, Key},
+    };
+
+    #[test]
+    fn filters_match_metadata() {
+        let metadata = Metadata {
+            key: Key::MetadataV1,
+            update_authority: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            data: Data {
+                name: format!("{:\0<32}", "Gold Llama"),
+                symbol: "LLAMA".to_owned(),
+                uri: "https://arweave.net/llama".to_owned(),
+                seller_fee_basis_points: 750,
+                creators: Some(vec![Creator {
+                    address: Pubkey::new_unique(),
+                    verified: false,
+                    share: 100,
+                }]),
+            },
+            primary_sale_happened: true,
+            is_mutable: false,
+            edition_nonce: Some(255),
+        };
+        let matches = |input| Filter::parse(input).unwrap().matches(&metadata);
+        assert!(matches("seller_fee > 500 && !mutable"));
+        assert!(matches("name == \"Gold Llama\""));
+        assert!(matches(
+            "symbol == \"LLAMA\" && (name contains \"Silver\" || creators >= 1)"
+        ));
+        assert!(matches("!verified && primary_sale == true"));
+        assert!(!matches("seller_fee <= 500 || mutable"));
+        assert!(matches(&format!("mint == \"{}\"", metadata.mint)));
+
+        for invalid in [
+            "seller_fee",
+            "seller_fee > \"500\"",
+            "name > \"a\"",
+            "royalty > 5",
+            "mutable &&",
+            "(mutable",
+            "name == \"Llama",
+            "mutable mutable",
+            "!name",
+        ] {
+            assert!(Filter::parse(invalid).is_err(), "{} parsed", invalid);
+        }
+    }
+}
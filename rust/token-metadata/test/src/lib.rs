@@ -0,0 +1,545 @@
+//! Builders for the token metadata program's instructions, shared by the test client CLI and
+//! usable by anything else that needs to create or update metadata and editions.
+
+use {
+    solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey},
+    solana_sdk::system_instruction::create_account,
+    spl_token::{
+        instruction::{initialize_account, initialize_mint, mint_to},
+        state::{Account, Mint},
+    },
+    spl_token_metadata::{
+        instruction::{
+            create_master_edition, create_metadata_accounts,
+            mint_new_edition_from_master_edition_via_token, update_metadata_accounts,
+        },
+        state::{Data, EDITION, EDITION_MARKER_BIT_SIZE, PREFIX},
+    },
+};
+
+/// The deployment of the token metadata program that PDAs and instructions are built for, the
+/// deployed program by default or another one such as a local fork.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MetadataProgram {
+    pub id: Pubkey,
+    /// Checks every PDA derived with `compute_and_print_pda_bump`.
+    pub debug_pda: bool,
+}
+
+impl Default for MetadataProgram {
+    fn default() -> Self {
+        MetadataProgram::new(spl_token_metadata::id())
+    }
+}
+
+/// Derives `address` again from `seeds` and `bump` the way the program signs for it, with
+/// `create_program_address`, and prints the bump. Panics when the two disagree, which means the
+/// seeds the client derived from are not the ones the program will use.
+pub fn compute_and_print_pda_bump(
+    label: &str,
+    seeds: &[&[u8]],
+    program_key: &Pubkey,
+    address: &Pubkey,
+    bump: u8,
+) {
+    let bump_seed = [bump];
+    let mut signer_seeds = seeds.to_vec();
+    signer_seeds.push(&bump_seed);
+    let derived = Pubkey::create_program_address(&signer_seeds, program_key);
+    assert_eq!(
+        derived.as_ref().ok(),
+        Some(address),
+        "{} PDA {} does not derive from its seeds with bump {}",
+        label,
+        address,
+        bump
+    );
+    eprintln!("{} PDA {} bump {}", label, address, bump);
+}
+
+impl MetadataProgram {
+    pub fn new(id: Pubkey) -> Self {
+        MetadataProgram {
+            id,
+            debug_pda: false,
+        }
+    }
+
+    fn find_pda(&self, label: &str, seeds: &[&[u8]]) -> Pubkey {
+        let (address, bump) = Pubkey::find_program_address(seeds, &self.id);
+        if self.debug_pda {
+            compute_and_print_pda_bump(label, seeds, &self.id, &address, bump);
+        }
+        address
+    }
+
+    /// Derives the metadata PDA for `mint`.
+    pub fn metadata_pda(&self, mint: &Pubkey) -> Pubkey {
+        let metadata_seeds = &[PREFIX.as_bytes(), self.id.as_ref(), mint.as_ref()];
+        self.find_pda("Metadata", metadata_seeds)
+    }
+
+    /// Derives the edition marker PDA recording which editions of the master edition of `mint`
+    /// around `edition` have been printed.
+    pub fn edition_marker_pda(&self, mint: &Pubkey, edition: u64) -> Pubkey {
+        let marker_number = (edition / EDITION_MARKER_BIT_SIZE).to_string();
+        let marker_seeds = &[
+            PREFIX.as_bytes(),
+            self.id.as_ref(),
+            mint.as_ref(),
+            EDITION.as_bytes(),
+            marker_number.as_bytes(),
+        ];
+        self.find_pda("Edition marker", marker_seeds)
+    }
+
+    /// Derives the edition PDA (master or limited) for `mint`.
+    pub fn edition_pda(&self, mint: &Pubkey) -> Pubkey {
+        let edition_seeds = &[
+            PREFIX.as_bytes(),
+            self.id.as_ref(),
+            mint.as_ref(),
+            EDITION.as_bytes(),
+        ];
+        self.find_pda("Edition", edition_seeds)
+    }
+}
+
+/// Strips the null padding the program appends to puffed string fields.
+pub fn strip_padding(value: &str) -> &str {
+    value.trim_end_matches('\u{0000}')
+}
+
+/// Builds the instructions creating the metadata of `mint`. With `new_mint_rent`, the mint is
+/// first created with that many lamports and initialized with `payer` as its authority.
+#[allow(clippy::too_many_arguments)]
+pub fn build_create_metadata_instructions(
+    program: &MetadataProgram,
+    payer: &Pubkey,
+    update_authority: &Pubkey,
+    mint: &Pubkey,
+    new_mint_rent: Option<u64>,
+    name: String,
+    symbol: String,
+    uri: String,
+    mutable: bool,
+) -> Vec<Instruction> {
+    let token_key = spl_token::id();
+    let mut instructions = vec![];
+
+    if let Some(rent) = new_mint_rent {
+        instructions.push(create_account(
+            payer,
+            mint,
+            rent,
+            Mint::LEN as u64,
+            &token_key,
+        ));
+        instructions.push(initialize_mint(&token_key, mint, payer, Some(payer), 0).unwrap());
+    }
+
+    instructions.push(create_metadata_accounts(
+        program.id,
+        program.metadata_pda(mint),
+        *mint,
+        *payer,
+        *payer,
+        *update_authority,
+        name,
+        symbol,
+        uri,
+        None,
+        0,
+        update_authority != payer,
+        mutable,
+    ));
+    instructions
+}
+
+/// Builds the instruction replacing the data of the metadata of `mint`, optionally handing it
+/// to `new_update_authority`.
+pub fn build_update_metadata_instructions(
+    program: &MetadataProgram,
+    update_authority: &Pubkey,
+    mint: &Pubkey,
+    new_update_authority: Option<Pubkey>,
+    data: Data,
+) -> Vec<Instruction> {
+    vec![update_metadata_accounts(
+        program.id,
+        program.metadata_pda(mint),
+        *update_authority,
+        new_update_authority,
+        Some(data),
+        None,
+    )]
+}
+
+/// Builds the instructions turning the metadata of `mint` into a master edition. With
+/// `added_token_account`, a token account holding one token of `mint` is first created for the
+/// given owner with the given rent, for mints that have no supply yet.
+pub fn build_master_edition_instructions(
+    program: &MetadataProgram,
+    payer: &Pubkey,
+    update_authority: &Pubkey,
+    mint_authority: &Pubkey,
+    mint: &Pubkey,
+    max_supply: Option<u64>,
+    added_token_account: Option<(&Pubkey, &Pubkey, u64)>,
+) -> Vec<Instruction> {
+    let token_key = spl_token::id();
+    let mut instructions = vec![];
+
+    if let Some((token_account, owner, rent)) = added_token_account {
+        instructions.push(create_account(
+            payer,
+            token_account,
+            rent,
+            Account::LEN as u64,
+            &token_key,
+        ));
+        instructions.push(initialize_account(&token_key, token_account, mint, owner).unwrap());
+        instructions.push(mint_to(&token_key, mint, token_account, payer, &[payer], 1).unwrap());
+    }
+
+    instructions.push(create_master_edition(
+        program.id,
+        program.edition_pda(mint),
+        *mint,
+        *update_authority,
+        *mint_authority,
+        program.metadata_pda(mint),
+        *payer,
+        max_supply,
+    ));
+    instructions
+}
+
+/// Builds the instructions printing edition `edition` of the master edition of `master_mint`
+/// into `new_mint`. The new mint and `new_token_account` holding its one token are created
+/// with `mint_rent` and `token_account_rent` lamports, with `payer` as their authority.
+#[allow(clippy::too_many_arguments)]
+pub fn build_mint_edition_instructions(
+    program: &MetadataProgram,
+    payer: &Pubkey,
+    account_authority: &Pubkey,
+    master_mint: &Pubkey,
+    master_token_account: &Pubkey,
+    new_mint: &Pubkey,
+    new_token_account: &Pubkey,
+    edition: u64,
+    mint_rent: u64,
+    token_account_rent: u64,
+) -> Vec<Instruction> {
+    let token_key = spl_token::id();
+    vec![
+        create_account(payer, new_mint, mint_rent, Mint::LEN as u64, &token_key),
+        initialize_mint(&token_key, new_mint, payer, Some(payer), 0).unwrap(),
+        create_account(
+            payer,
+            new_token_account,
+            token_account_rent,
+            Account::LEN as u64,
+            &token_key,
+        ),
+        initialize_account(&token_key, new_token_account, new_mint, payer).unwrap(),
+        mint_to(&token_key, new_mint, new_token_account, payer, &[payer], 1).unwrap(),
+        mint_new_edition_from_master_edition_via_token(
+            program.id,
+            program.metadata_pda(new_mint),
+            program.edition_pda(new_mint),
+            program.edition_pda(master_mint),
+            *new_mint,
+            *account_authority,
+            *payer,
+            *account_authority,
+            *master_token_account,
+            *account_authority,
+            program.metadata_pda(master_mint),
+            *master_mint,
+            edition,
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {
+        solana_program::{
+            account_info::AccountInfo,
+            borsh::try_from_slice_unchecked,
+            entrypoint::ProgramResult,
+            instruction::Instruction,
+            program_stubs::{set_syscall_stubs, SyscallStubs},
+            system_instruction::SystemInstruction,
+            system_program,
+        },
+        solana_program_test::{
+            builtin_process_instruction, tokio, ProgramTest, ProgramTestContext,
+        },
+        solana_sdk::{
+            account::{Account, WritableAccount},
+            instruction::InstructionError,
+            process_instruction::InvokeContext,
+            rent::Rent,
+            signature::{Keypair, Signer},
+            transaction::{Transaction, TransactionError},
+        },
+        spl_token_metadata::{
+            error::MetadataError,
+            state::{Key, Metadata, MAX_METADATA_LEN},
+        },
+        std::{cell::RefCell, sync::Once},
+    };
+
+    thread_local! {
+        /// The owner of each account after the last instruction `process_instruction` ran.
+        static OWNERS: RefCell<Vec<(Pubkey, Pubkey)>> = RefCell::new(vec![]);
+    }
+
+    // The processor ties the program id and accounts to the lifetime of the account data, which
+    // `processor!` does not give it, so they are leaked to live as long.
+    //
+    // It also borsh serializes into `&mut *data.borrow_mut()`, which advances the account's data
+    // slice past what was written. The BPF loader copies back the whole account regardless, so
+    // the full slices are put back here the same way.
+    fn process_instruction(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: &[u8],
+    ) -> ProgramResult {
+        let data = accounts
+            .iter()
+            .map(|account| {
+                let mut data = account.data.borrow_mut();
+                (data.as_mut_ptr(), data.len())
+            })
+            .collect::<Vec<_>>();
+        let result = spl_token_metadata::processor::process_instruction(
+            Box::leak(Box::new(*program_id)),
+            Box::leak(accounts.to_vec().into_boxed_slice()),
+            input,
+        );
+        for (account, (ptr, len)) in accounts.iter().zip(data) {
+            let mut data = account.data.borrow_mut();
+            // The slice only moved forward within the same account data, if at all.
+            if data.len() < len && data.as_ptr() == unsafe { ptr.add(len - data.len()) } {
+                *data = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+            }
+        }
+        OWNERS.with(|owners| {
+            *owners.borrow_mut() = accounts
+                .iter()
+                .map(|account| (*account.key, *account.owner))
+                .collect()
+        });
+        result
+    }
+
+    // `processor!`, except that owners the program assigned are committed too, as the BPF
+    // loader does.
+    fn process(
+        program_id: &Pubkey,
+        input: &[u8],
+        invoke_context: &mut dyn InvokeContext,
+    ) -> Result<(), InstructionError> {
+        builtin_process_instruction(process_instruction, program_id, input, invoke_context)?;
+        let owners = OWNERS.with(|owners| owners.take());
+        for keyed_account in invoke_context.get_keyed_accounts()? {
+            if let Some((_, owner)) = owners
+                .iter()
+                .find(|(key, _)| key == keyed_account.unsigned_key())
+            {
+                keyed_account.account.borrow_mut().set_owner(*owner);
+            }
+        }
+        Ok(())
+    }
+
+    /// The syscalls `ProgramTest` gives native programs, except that a system `allocate` of an
+    /// account that already has the space is skipped. Native cross-program invocations cannot
+    /// resize account data, so tests that create a PDA add it pre-sized with `add_presized`.
+    struct PresizedStubs(Box<dyn SyscallStubs>);
+
+    /// Stands in for the stubs while they are swapped.
+    struct NoStubs;
+
+    impl SyscallStubs for NoStubs {}
+
+    impl SyscallStubs for PresizedStubs {
+        fn sol_log(&self, message: &str) {
+            self.0.sol_log(message)
+        }
+
+        fn sol_log_compute_units(&self) {
+            self.0.sol_log_compute_units()
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            if instruction.program_id == system_program::id() {
+                if let Ok(SystemInstruction::Allocate { space }) =
+                    bincode::deserialize(&instruction.data)
+                {
+                    let account = account_infos
+                        .iter()
+                        .find(|account| *account.key == instruction.accounts[0].pubkey)
+                        .unwrap();
+                    if account.data_len() as u64 == space && *account.owner == system_program::id()
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+            self.0
+                .sol_invoke_signed(instruction, account_infos, signers_seeds)
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            self.0.sol_get_clock_sysvar(var_addr)
+        }
+
+        fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+            self.0.sol_get_epoch_schedule_sysvar(var_addr)
+        }
+
+        fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+            self.0.sol_get_fees_sysvar(var_addr)
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            self.0.sol_get_rent_sysvar(var_addr)
+        }
+    }
+
+    fn program_test() -> ProgramTest {
+        ProgramTest::new(
+            "spl_token_metadata",
+            spl_token_metadata::id(),
+            Some(process),
+        )
+    }
+
+    /// Adds a rent exempt, zeroed system account of `len` bytes for the program to allocate.
+    fn add_presized(program_test: &mut ProgramTest, address: Pubkey, len: usize) {
+        program_test.add_account(
+            address,
+            Account {
+                lamports: Rent::default().minimum_balance(len),
+                data: vec![0; len],
+                owner: system_program::id(),
+                ..Account::default()
+            },
+        );
+    }
+
+    /// Starts the program, wrapping the stubs `ProgramTest` installs the first time in
+    /// `PresizedStubs`.
+    async fn start(program_test: ProgramTest) -> ProgramTestContext {
+        static WRAP_STUBS: Once = Once::new();
+        let context = program_test.start_with_context().await;
+        WRAP_STUBS.call_once(|| {
+            let stubs = set_syscall_stubs(Box::new(NoStubs));
+            set_syscall_stubs(Box::new(PresizedStubs(stubs)));
+        });
+        context
+    }
+
+    #[tokio::test]
+    async fn create_metadata_instructions_pass_the_program_checks() {
+        let mut context = start(program_test()).await;
+        let mint = Keypair::new();
+        let rent = context.banks_client.get_rent().await.unwrap();
+
+        let mut instructions = build_create_metadata_instructions(
+            &MetadataProgram::default(),
+            &context.payer.pubkey(),
+            &context.payer.pubkey(),
+            &mint.pubkey(),
+            Some(rent.minimum_balance(Mint::LEN)),
+            "Test".to_owned(),
+            "TST".to_owned(),
+            "uri".to_owned(),
+            true,
+        );
+        // The mint and its authority pass, so only the metadata account is left to reject.
+        instructions[2].accounts[0].pubkey = Pubkey::new_unique();
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&context.payer.pubkey()),
+            &[&context.payer, &mint],
+            context.last_blockhash,
+        );
+        let err = context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap();
+
+        assert_eq!(
+            err,
+            TransactionError::InstructionError(
+                2,
+                InstructionError::Custom(MetadataError::InvalidMetadataKey as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn create_metadata_instructions_success() {
+        let mint = Keypair::new();
+        let metadata_address = MetadataProgram::default().metadata_pda(&mint.pubkey());
+        let mut program_test = program_test();
+        add_presized(&mut program_test, metadata_address, MAX_METADATA_LEN);
+        let mut context = start(program_test).await;
+        let rent = context.banks_client.get_rent().await.unwrap();
+
+        let instructions = build_create_metadata_instructions(
+            &MetadataProgram::default(),
+            &context.payer.pubkey(),
+            &context.payer.pubkey(),
+            &mint.pubkey(),
+            Some(rent.minimum_balance(Mint::LEN)),
+            "Test".to_owned(),
+            "TST".to_owned(),
+            "uri".to_owned(),
+            true,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&context.payer.pubkey()),
+            &[&context.payer, &mint],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let account = context
+            .banks_client
+            .get_account(metadata_address)
+            .await
+            .unwrap()
+            .unwrap();
+        let metadata: Metadata = try_from_slice_unchecked(&account.data).unwrap();
+
+        assert_eq!(metadata.key, Key::MetadataV1);
+        assert_eq!(strip_padding(&metadata.data.name), "Test");
+        assert_eq!(strip_padding(&metadata.data.symbol), "TST");
+        assert_eq!(strip_padding(&metadata.data.uri), "uri");
+        assert_eq!(metadata.data.seller_fee_basis_points, 0);
+        assert_eq!(metadata.data.creators, None);
+        assert_eq!(metadata.mint, mint.pubkey());
+        assert_eq!(metadata.update_authority, context.payer.pubkey());
+        assert!(metadata.is_mutable);
+        assert!(!metadata.primary_sale_happened);
+    }
+}
@@ -1,11 +1,33 @@
+mod batch;
+mod bootstrap;
+mod confirm;
+mod csv;
+mod error;
+mod filter;
+mod priority;
+mod query;
+mod repair;
+mod retry;
+mod rpc;
+mod scan;
+mod schema;
+mod selftest;
+mod summary;
+mod timing;
+mod token;
+mod validate;
+mod version;
+
 use std::{
-    fs::{self, File},
-    io::{Read, Write},
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
 };
 
-use serde_json::Value;
+use serde_json::{json, Value};
 use solana_client::{
-    client_error::reqwest,
+    client_error::{reqwest, ClientError},
     rpc_config::{
         RpcAccountInfoConfig, RpcLargestAccountsConfig, RpcLargestAccountsFilter,
         RpcProgramAccountsConfig,
@@ -15,92 +37,662 @@ use solana_client::{
 use solana_program::system_instruction;
 use solana_sdk::{
     account::ReadableAccount,
+    bs58,
     commitment_config::{CommitmentConfig, CommitmentLevel},
 };
 use spl_token_metadata::state::MAX_METADATA_LEN;
 use std::convert::TryFrom;
 use {
     arrayref::array_ref,
+    batch::broadcast_batch,
+    bootstrap::bootstrap,
     clap::{crate_description, crate_name, crate_version, App, Arg, ArgMatches, SubCommand},
+    confirm::confirm_step,
+    csv::write_csv,
+    error::{
+        classify_send_error, dump_logs, exit_with_error, report_item_error, report_logs,
+        report_undecodable, send_definitely_failed, set_dump_logs, set_json_errors, set_strict,
+        CliError, SendFailure,
+    },
+    priority::priority_fee_instruction,
+    query::{
+        audit_supply, authority_scan, check_images, check_metadata_exists, check_url, checksum,
+        find_by_symbol, holder, inventory, marker_utilization, royalties, scan, wallet_editions,
+    },
+    rand::Rng,
+    repair::{
+        can_update, check_can_update, creators_from_shares, dedup_creators, fix_nulls,
+        migrate_uris, parse_creator_share, remove_creator, strip_quotes, validate_creator_update,
+        validate_creators,
+    },
+    retry::{record_attempt, record_retry, write_retry_metrics, RetryReason},
+    rpc::FailoverClient,
+    scan::{get_program_account_keys, memcmp_filter, page_size_of, scan_program_accounts},
+    schema::{
+        print_schema, AirdropGrant, LlamaKey, ManifestEntry, ManifestJson, PubkeyString, Refund,
+        UriString, UriUpdate, SCHEMA_COMMANDS,
+    },
+    selftest::selftest,
     solana_clap_utils::{
-        input_parsers::pubkey_of,
-        input_validators::{is_url, is_valid_pubkey, is_valid_signer},
+        input_parsers::{pubkey_of, value_of},
+        input_validators::{is_parsable, is_url, is_valid_pubkey, is_valid_signer},
+        keypair::signer_from_path,
+        offline::{blockhash_arg, sign_only_arg, BLOCKHASH_ARG, SIGN_ONLY_ARG},
     },
-    solana_client::rpc_client::RpcClient,
-    solana_client::rpc_request::TokenAccountsFilter,
+    solana_client::nonce_utils,
+    solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config,
+    solana_client::rpc_request::{RpcError, TokenAccountsFilter},
     solana_program::{
-        account_info::AccountInfo, borsh::try_from_slice_unchecked, program_pack::Pack,
+        account_info::AccountInfo, borsh::try_from_slice_unchecked, instruction::Instruction,
+        program_error::ProgramError, program_pack::Pack,
     },
+    solana_remote_wallet::remote_wallet::RemoteWalletManager,
     solana_sdk::{
+        hash::Hash,
+        instruction::InstructionError,
+        native_token::{lamports_to_sol, sol_to_lamports},
+        packet::PACKET_DATA_SIZE,
         pubkey::Pubkey,
-        signature::{read_keypair_file, Keypair, Signer},
+        signature::{write_keypair_file, Keypair, Signature, Signer},
+        signer::unique_signers,
+        signers::Signers,
         system_instruction::create_account,
-        transaction::Transaction,
+        transaction::{uses_durable_nonce, Transaction},
     },
+    solana_transaction_status::{TransactionStatus, UiTransactionEncoding},
+    spl_associated_token_account::{create_associated_token_account, get_associated_token_address},
     spl_token::{
-        instruction::{initialize_account, initialize_mint, mint_to},
+        instruction::{initialize_account, initialize_mint, mint_to, set_authority, AuthorityType},
         state::{Account, Mint},
     },
     spl_token_metadata::{
+        deprecated_instruction::deprecated_mint_new_edition_from_master_edition_via_printing_token,
+        error::MetadataError,
         instruction::{
             create_master_edition, create_metadata_accounts,
             mint_new_edition_from_master_edition_via_token, puff_metadata_account,
             update_metadata_accounts,
         },
         state::{
-            get_reservation_list, Creator, Data, Edition, Key, MasterEditionV1, MasterEditionV2,
-            Metadata, EDITION, MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH, PREFIX,
+            get_reservation_list, Creator, Data, Edition, EditionMarker, Key, MasterEditionV1,
+            MasterEditionV2, Metadata, ReservationList, MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH,
+            MAX_URI_LENGTH,
         },
     },
-    std::str::FromStr,
+    spl_token_metadata_test_client::{
+        build_create_metadata_instructions, build_master_edition_instructions,
+        build_mint_edition_instructions, build_update_metadata_instructions, strip_padding,
+        MetadataProgram,
+    },
+    std::{
+        cell::Cell,
+        str::FromStr,
+        sync::Arc,
+        time::{Duration, Instant},
+    },
+    summary::Summary,
+    timing::Timings,
+    token::{approve, bulk_burn, freeze, revoke, thaw},
+    validate::{validate_file, VALIDATED_COMMANDS},
+    version::{decode_metadata, read_metadata},
 };
 
 const TOKEN_PROGRAM_PUBKEY: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
-fn puff_unpuffed_metadata(_app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
-    let metadata_accounts = client
-        .get_program_accounts(&spl_token_metadata::id())
+
+/// Resolves the signer given by `name` (a keypair file or a remote wallet URL such as
+/// `usb://ledger`), falling back to the `--keypair` signer when the argument is absent.
+pub fn signer_of_or_keypair(
+    app_matches: &ArgMatches,
+    name: &str,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) -> Box<dyn Signer> {
+    let path = app_matches
+        .value_of(name)
+        .unwrap_or_else(|| app_matches.value_of("keypair").unwrap());
+    signer_from_path(app_matches, path, name, wallet_manager).unwrap()
+}
+
+/// Fetches the metadata account at `metadata_key` of `mint`, exiting with a clear message
+/// rather than a panic when the mint has no metadata.
+pub fn get_metadata_account_or_exit(
+    client: &FailoverClient,
+    mint: &Pubkey,
+    metadata_key: &Pubkey,
+) -> solana_sdk::account::Account {
+    let context = json!({ "mint": mint.to_string(), "metadata": metadata_key.to_string() });
+    match client.get_account_with_commitment(metadata_key, client.commitment()) {
+        Ok(response) => match response.value {
+            Some(account) => account,
+            None => exit_with_error(
+                CliError::AccountNotFound(format!("No metadata exists for mint {}", mint)),
+                context,
+            ),
+        },
+        Err(err) => exit_with_error(err.into(), context),
+    }
+}
+
+/// Reads a JSON array of mint pubkeys, such as the failed mints `bulk_master_edition` writes to
+/// `--failed-file`. The metadata keys `dump_metadata_keys` writes are no mints.
+pub fn read_mints_file(path: &str) -> Vec<Pubkey> {
+    let mut file = File::open(path).unwrap();
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    let keys: Vec<String> = serde_json::from_str(&contents).unwrap();
+    keys.iter()
+        .map(|key| Pubkey::from_str(key).unwrap())
+        .collect()
+}
+
+/// Reads the mint and wallet of each llama in a record `create_new_llamas --record` wrote, one
+/// JSON object per line.
+pub fn read_record_file(path: &str) -> Vec<(Pubkey, Pubkey)> {
+    let contents = fs::read_to_string(path).unwrap();
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let entry: Value = serde_json::from_str(line).unwrap();
+            (
+                Pubkey::from_str(entry["mint"].as_str().unwrap()).unwrap(),
+                Pubkey::from_str(entry["wallet"].as_str().unwrap()).unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// Whether writing `new` over the on-chain `current` would leave it as it is. The program pads
+/// the strings it stores with nulls, so they are compared without the padding.
+pub fn data_unchanged(current: &Data, new: &Data) -> bool {
+    strip_padding(&current.name) == strip_padding(&new.name)
+        && strip_padding(&current.symbol) == strip_padding(&new.symbol)
+        && strip_padding(&current.uri) == strip_padding(&new.uri)
+        && current.seller_fee_basis_points == new.seller_fee_basis_points
+        && current.creators == new.creators
+}
+
+/// Each field of `actual`, read back after a send, that differs from the `requested` data, as
+/// `field: requested -> actual`. The strings are compared without their padding.
+pub fn data_differences(requested: &Data, actual: &Data) -> Vec<String> {
+    let mut differences = vec![];
+    for (field, requested, actual) in [
+        ("name", &requested.name, &actual.name),
+        ("symbol", &requested.symbol, &actual.symbol),
+        ("uri", &requested.uri, &actual.uri),
+    ] {
+        if strip_padding(requested) != strip_padding(actual) {
+            differences.push(format!(
+                "{}: {:?} -> {:?}",
+                field,
+                strip_padding(requested),
+                strip_padding(actual)
+            ));
+        }
+    }
+    if requested.seller_fee_basis_points != actual.seller_fee_basis_points {
+        differences.push(format!(
+            "seller_fee_basis_points: {} -> {}",
+            requested.seller_fee_basis_points, actual.seller_fee_basis_points
+        ));
+    }
+    if requested.creators != actual.creators {
+        differences.push(format!(
+            "creators: {:?} -> {:?}",
+            requested.creators, actual.creators
+        ));
+    }
+    differences
+}
+
+/// With `--verify-after-send`, exits with an error listing `differences` between what was sent
+/// for the account at `key` and what it holds now, when there are any.
+fn verify_after_send(app_matches: &ArgMatches, key: &Pubkey, differences: Vec<String>) {
+    if !app_matches.is_present("verify_after_send") {
+        return;
+    }
+    if differences.is_empty() {
+        println!("Verified {} matches what was sent", key);
+        return;
+    }
+    exit_with_error(
+        CliError::InvalidInput(format!(
+            "{} does not match what was sent:\n  {}",
+            key,
+            differences.join("\n  ")
+        )),
+        json!({ "account": key.to_string(), "differences": differences }),
+    );
+}
+
+fn verify_after_send_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("verify_after_send")
+        .long("verify-after-send")
+        .takes_value(false)
+        .help(
+            "Read the account back after the send and fail if any field differs from what was sent",
+        )
+}
+
+/// Checks `name`, `symbol` and `uri` against the program's length limits, so an oversized field
+/// fails here instead of on chain after the fee is paid.
+pub fn check_field_lengths(name: &str, symbol: &str, uri: &str) -> Result<(), CliError> {
+    let fields = [
+        ("name", name, MAX_NAME_LENGTH),
+        ("symbol", symbol, MAX_SYMBOL_LENGTH),
+        ("uri", uri, MAX_URI_LENGTH),
+    ];
+    for &(field, value, limit) in fields.iter() {
+        if value.len() > limit {
+            return Err(CliError::InvalidInput(format!(
+                "The {} {:?} is {} bytes long, the limit is {}",
+                field,
+                value,
+                value.len(),
+                limit
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Signs `transaction` against `--blockhash` when given, or the cluster's latest blockhash
+/// otherwise, and sends it. With `--sign-only` the signed transaction is printed instead and
+/// the process exits, so nothing after this call runs.
+pub fn sign_and_send<T: Signers>(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    transaction: &mut Transaction,
+    signers: &T,
+) {
+    let recent_blockhash = match value_of::<Hash>(app_matches, BLOCKHASH_ARG.name) {
+        Some(hash) => hash,
+        None => client.get_recent_blockhash().unwrap().0,
+    };
+    transaction.sign(signers, recent_blockhash);
+
+    if app_matches.is_present(SIGN_ONLY_ARG.name) {
+        println!("Blockhash: {}", recent_blockhash);
+        println!("Signers (Pubkey=Signature):");
+        for (pubkey, signature) in transaction
+            .message
+            .account_keys
+            .iter()
+            .zip(transaction.signatures.iter())
+        {
+            println!("  {}={}", pubkey, signature);
+        }
+        let serialized = bincode::serialize(&transaction).unwrap();
+        println!("Transaction: {}", bs58::encode(serialized).into_string());
+        std::process::exit(0);
+    }
+
+    if let Err(err) = send_and_confirm(app_matches, client, transaction, signers) {
+        exit_with_error(
+            err.into(),
+            json!({ "signature": transaction.signatures[0].to_string() }),
+        );
+    }
+}
+
+thread_local! {
+    /// When the previous transaction went out, for spacing sends by `--min-interval`.
+    static LAST_SEND: Cell<Option<Instant>> = Cell::new(None);
+    /// Sends since the payer balance was last checked against `--min-balance`.
+    static SENDS_SINCE_BALANCE_CHECK: Cell<Option<u32>> = Cell::new(None);
+}
+
+/// Sends between two checks of the payer balance.
+const BALANCE_CHECK_SENDS: u32 = 10;
+
+/// How long a run paused for a low payer balance waits between checks.
+const LOW_BALANCE_POLL: Duration = Duration::from_secs(30);
+
+/// With `--min-balance`, checks the balance of `payer` before the first send and every
+/// `BALANCE_CHECK_SENDS` sends after. Below the threshold the run pauses until the payer is
+/// topped up, or ends with `--abort-on-low-balance`, rather than failing every send that
+/// follows. Commands that record their progress as they go resume from it.
+pub fn check_payer_balance(app_matches: &ArgMatches, client: &FailoverClient, payer: &Pubkey) {
+    let min_balance = match value_of::<f64>(app_matches, "min_balance") {
+        Some(sol) => sol_to_lamports(sol),
+        None => return,
+    };
+    let due = SENDS_SINCE_BALANCE_CHECK.with(|sends| {
+        let due = sends
+            .get()
+            .map_or(true, |sends| sends >= BALANCE_CHECK_SENDS);
+        sends.set(Some(if due { 1 } else { sends.get().unwrap() + 1 }));
+        due
+    });
+    if !due {
+        return;
+    }
+    loop {
+        let balance = client.get_balance(payer).unwrap_or_else(|err| {
+            exit_with_error(err.into(), json!({ "payer": payer.to_string() }))
+        });
+        if balance >= min_balance {
+            return;
+        }
+        let message = format!(
+            "Payer {} holds {} SOL, below --min-balance {} SOL",
+            payer,
+            lamports_to_sol(balance),
+            lamports_to_sol(min_balance)
+        );
+        if app_matches.is_present("abort_on_low_balance") {
+            exit_with_error(
+                CliError::InvalidInput(message),
+                json!({ "payer": payer.to_string(), "balance": balance }),
+            );
+        }
+        eprintln!(
+            "{}, pausing until it is topped up, checking every {}s",
+            message,
+            LOW_BALANCE_POLL.as_secs()
+        );
+        std::thread::sleep(LOW_BALANCE_POLL);
+    }
+}
+
+/// Sends the signed `transaction` without waiting for it to confirm, first sleeping as long as
+/// needed to keep `--min-interval` milliseconds since the previous send.
+pub fn send_transaction(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    transaction: &Transaction,
+) -> Result<Signature, ClientError> {
+    check_payer_balance(app_matches, client, &transaction.message.account_keys[0]);
+    throttle(app_matches);
+    record_attempt();
+    client.send_transaction(transaction)
+}
+
+pub fn throttle(app_matches: &ArgMatches) {
+    let min_interval =
+        Duration::from_millis(value_of::<u64>(app_matches, "min_interval").unwrap_or(0));
+    LAST_SEND.with(|last_send| {
+        if let Some(wait) = last_send
+            .get()
+            .and_then(|sent| min_interval.checked_sub(sent.elapsed()))
+        {
+            std::thread::sleep(wait);
+        }
+        last_send.set(Some(Instant::now()));
+    });
+}
+
+/// Sleeps for `delay` scaled by a random factor within `--retry-jitter` of one, so instances
+/// sharing an RPC endpoint do not all retry at the same moment.
+pub fn backoff(app_matches: &ArgMatches, delay: Duration) {
+    let jitter = value_of::<f64>(app_matches, "retry_jitter").unwrap_or(0.0);
+    let factor = if jitter > 0.0 {
+        rand::thread_rng().gen_range(1.0 - jitter, 1.0 + jitter)
+    } else {
+        1.0
+    };
+    std::thread::sleep(delay.mul_f64(factor));
+}
+
+/// Times `send_and_confirm` sends a transaction again before giving up on it.
+const MAX_RESUBMISSIONS: usize = 10;
+
+/// The first of `sent` the cluster has seen, at any commitment, with its status.
+fn landed_status(
+    client: &FailoverClient,
+    sent: &[Signature],
+) -> Result<Option<(Signature, TransactionStatus)>, ClientError> {
+    let statuses = client.get_signature_statuses(sent)?.value;
+    Ok(sent
+        .iter()
+        .zip(statuses)
+        .find_map(|(signature, status)| status.map(|status| (*signature, status))))
+}
+
+/// Sends the signed `transaction` and waits for it to confirm. With `--max-txn-age`, one still
+/// unconfirmed after that many seconds is sent again, at most `MAX_RESUBMISSIONS` times. While
+/// its blockhash is valid the same transaction is rebroadcast, as a re-signed copy could land
+/// alongside it. Only once the blockhash has expired, and none of the submissions landed, is it
+/// re-signed with a fresh one. A transaction built against a durable nonce never expires, so it
+/// is always rebroadcast unchanged. Earlier submissions keep being watched in case one lands
+/// after all.
+pub fn send_and_confirm<T: Signers>(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    transaction: &mut Transaction,
+    signers: &T,
+) -> Result<Signature, ClientError> {
+    let max_age = match app_matches.value_of("max_txn_age") {
+        Some(seconds) => Duration::from_secs(seconds.parse().unwrap()),
+        None => {
+            check_payer_balance(app_matches, client, &transaction.message.account_keys[0]);
+            throttle(app_matches);
+            record_attempt();
+            return client.send_and_confirm_transaction(transaction);
+        }
+    };
+    let mut sent = vec![];
+    let mut resubmissions = 0;
+    let mut send = true;
+    loop {
+        let signature = transaction.signatures[0];
+        if send {
+            send_transaction(app_matches, client, transaction)?;
+            if !sent.contains(&signature) {
+                sent.push(signature);
+            }
+        }
+        send = true;
+        let submitted = Instant::now();
+        loop {
+            if let Some((signature, status)) = landed_status(client, &sent)? {
+                if status.satisfies_commitment(client.commitment()) {
+                    return match status.err {
+                        None => Ok(signature),
+                        Some(err) => {
+                            dump_transaction_logs(client, &signature);
+                            Err(err.into())
+                        }
+                    };
+                }
+                // Landed but not yet at the commitment asked for, so only waiting is left.
+            } else if submitted.elapsed() >= max_age {
+                break;
+            }
+            backoff(app_matches, Duration::from_millis(500));
+        }
+        if resubmissions == MAX_RESUBMISSIONS {
+            return Err(RpcError::ForUser(format!(
+                "Transaction {} unconfirmed after {} resubmissions",
+                signature, MAX_RESUBMISSIONS
+            ))
+            .into());
+        }
+        resubmissions += 1;
+        let expired = uses_durable_nonce(transaction).is_none()
+            && !client.is_blockhash_valid(&transaction.message.recent_blockhash)?;
+        if !expired {
+            println!(
+                "Transaction {} unconfirmed after {}s, sending it again",
+                signature,
+                max_age.as_secs()
+            );
+            record_retry(RetryReason::Other);
+            continue;
+        }
+        // None of the submissions can land any more, so one last look tells whether one did.
+        if landed_status(client, &sent)?.is_some() {
+            send = false;
+            continue;
+        }
+        println!(
+            "Transaction {} expired unconfirmed, resubmitting with a new blockhash",
+            signature
+        );
+        record_retry(RetryReason::BlockhashExpired);
+        let recent_blockhash = client
+            .get_new_blockhash(&transaction.message.recent_blockhash)?
+            .0;
+        transaction.sign(signers, recent_blockhash);
+    }
+}
+
+/// With `--dump-logs`, fetches and reports the program logs of `signature`, a transaction that
+/// landed but failed. Transactions failing their preflight simulation carry their logs in the
+/// error instead.
+fn dump_transaction_logs(client: &FailoverClient, signature: &Signature) {
+    if !dump_logs() {
+        return;
+    }
+    match client.get_transaction(signature, UiTransactionEncoding::Json) {
+        Ok(transaction) => {
+            let logs = transaction
+                .transaction
+                .meta
+                .and_then(|meta| meta.log_messages)
+                .unwrap_or_default();
+            report_logs(signature, &logs);
+        }
+        Err(err) => eprintln!("Cannot fetch the logs of {}: {}", signature, err),
+    }
+}
+
+fn dump_metadata_keys(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let output = app_matches.value_of("output").unwrap();
+    let keys: Vec<String> = get_program_account_keys(
+        &client,
+        &program.id,
+        vec![memcmp_filter(0, &[Key::MetadataV1 as u8])],
+    )
+    .iter()
+    .map(|key| key.to_string())
+    .collect();
+    println!("Found {} metadata accounts", keys.len());
+
+    let mut file = File::create(output).unwrap();
+    file.write_all(serde_json::to_string(&keys).unwrap().as_bytes())
         .unwrap();
+    println!("Wrote keys to {}", output);
+}
+
+/// Halves `batch_size` until a transaction puffing the first `batch_size` of `metadata_keys`
+/// fits in a packet and simulates cleanly, bottoming out at a single instruction.
+fn fit_puff_batch_size(
+    client: &FailoverClient,
+    program: &MetadataProgram,
+    payer: &dyn Signer,
+    metadata_keys: &[Pubkey],
+    mut batch_size: usize,
+) -> usize {
+    while batch_size > 1 {
+        let instructions: Vec<Instruction> = metadata_keys
+            .iter()
+            .take(batch_size)
+            .map(|key| puff_metadata_account(program.id, *key))
+            .collect();
+        let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+        let recent_blockhash = client.get_recent_blockhash().unwrap().0;
+        transaction.sign(&[payer], recent_blockhash);
+
+        let size = bincode::serialized_size(&transaction).unwrap();
+        if size > PACKET_DATA_SIZE as u64 {
+            println!("A batch of {} is too large ({} bytes)", batch_size, size);
+        } else {
+            match client.simulate_transaction(&transaction) {
+                Ok(response) => match response.value.err {
+                    None => return batch_size,
+                    Some(err) => println!("A batch of {} fails simulation: {}", batch_size, err),
+                },
+                Err(err) => println!("A batch of {} fails simulation: {}", batch_size, err),
+            }
+        }
+        batch_size /= 2;
+    }
+    1
+}
+
+fn puff_unpuffed_metadata(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let metadata_accounts = scan_program_accounts(
+        &client,
+        &program.id,
+        vec![memcmp_filter(0, &[Key::MetadataV1 as u8])],
+        page_size_of(app_matches),
+    );
     let mut needing_puffing = vec![];
     for acct in metadata_accounts {
-        if acct.1.data[0] == Key::MetadataV1 as u8 {
-            match try_from_slice_unchecked(&acct.1.data) {
-                Ok(val) => {
-                    let account: Metadata = val;
-                    if account.data.name.len() < MAX_NAME_LENGTH
-                        || account.data.uri.len() < MAX_URI_LENGTH
-                        || account.data.symbol.len() < MAX_SYMBOL_LENGTH
-                        || account.edition_nonce.is_none()
-                    {
-                        needing_puffing.push(acct.0);
-                    }
-                }
-                Err(_) => {
-                    println!("Skipping {}", acct.0)
+        match decode_metadata(&acct.1.data) {
+            Ok(account) => {
+                if account.data.name.len() < MAX_NAME_LENGTH
+                    || account.data.uri.len() < MAX_URI_LENGTH
+                    || account.data.symbol.len() < MAX_SYMBOL_LENGTH
+                    || account.edition_nonce.is_none()
+                {
+                    needing_puffing.push(acct.0);
                 }
-            };
-        }
+            }
+            Err(err) => report_undecodable(&acct.0, &err),
+        };
     }
     println!("Found {} accounts needing puffing", needing_puffing.len());
 
+    let requested = app_matches
+        .value_of("batch_size")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    let batch_size = fit_puff_batch_size(
+        &client,
+        program,
+        payer.as_ref(),
+        &needing_puffing,
+        requested,
+    );
+    if batch_size < requested {
+        println!("Reduced the batch size to {}", batch_size);
+    }
+
+    let mut timings = Timings::start(app_matches, &client);
+    timings.estimate((needing_puffing.len() + batch_size - 1) / batch_size, 1);
     let mut instructions = vec![];
     let mut i = 0;
     while i < needing_puffing.len() {
         let pubkey = needing_puffing[i];
-        instructions.push(puff_metadata_account(spl_token_metadata::id(), pubkey));
-        if instructions.len() >= 20 {
+        instructions.push(puff_metadata_account(program.id, pubkey));
+        if instructions.len() >= batch_size {
             let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
             let recent_blockhash = client.get_recent_blockhash().unwrap().0;
 
-            transaction.sign(&[&payer], recent_blockhash);
-            match client.send_and_confirm_transaction(&transaction) {
+            transaction.sign(&[payer.as_ref()], recent_blockhash);
+            match timings.time(i, &pubkey.to_string(), || {
+                send_and_confirm(app_matches, &client, &mut transaction, &[payer.as_ref()])
+            }) {
                 Ok(_) => {
-                    println!("Another 20 down. At {} / {}", i, needing_puffing.len());
+                    println!(
+                        "Another {} down. At {} / {}",
+                        batch_size,
+                        i,
+                        needing_puffing.len()
+                    );
                     instructions = vec![];
                     i += 1;
                 }
-                Err(_) => {
-                    println!("Txn failed. Retry.");
-                    std::thread::sleep(std::time::Duration::from_millis(1000));
+                Err(err) => {
+                    record_retry(RetryReason::of(&err));
+                    report_item_error(
+                        "Txn failed. Retry.",
+                        &err.into(),
+                        json!({ "index": i, "batch_size": instructions.len() }),
+                    );
+                    backoff(app_matches, Duration::from_millis(1000));
                 }
             }
         } else {
@@ -111,26 +703,327 @@ fn puff_unpuffed_metadata(_app_matches: &ArgMatches, payer: Keypair, client: Rpc
     if instructions.len() > 0 {
         let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
         let recent_blockhash = client.get_recent_blockhash().unwrap().0;
-        transaction.sign(&[&payer], recent_blockhash);
-        client.send_and_confirm_transaction(&transaction).unwrap();
+        transaction.sign(&[payer.as_ref()], recent_blockhash);
+        timings
+            .time(needing_puffing.len(), "final batch", || {
+                send_and_confirm(app_matches, &client, &mut transaction, &[payer.as_ref()])
+            })
+            .unwrap();
+    }
+    timings.finish(app_matches, &client);
+}
+
+/// Converts a UI amount such as `1.5` into base units of a mint with the given decimals.
+fn ui_amount_to_base_units(amount: &str, decimals: u8) -> Result<u64, String> {
+    let (whole, fraction) = match amount.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (amount, ""),
+    };
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(format!("{} is not a valid amount", amount));
+    }
+    if !whole
+        .chars()
+        .chain(fraction.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("{} is not a valid amount", amount));
+    }
+    if fraction.len() > decimals as usize {
+        return Err(format!(
+            "{} has more than the {} decimals supported by this mint",
+            amount, decimals
+        ));
+    }
+    let padded = format!(
+        "{}{}{}",
+        whole,
+        fraction,
+        "0".repeat(decimals as usize - fraction.len())
+    );
+    let digits = padded.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    digits
+        .parse::<u64>()
+        .map_err(|_| format!("{} overflows a u64 at {} decimals", amount, decimals))
+}
+
+/// Derives the associated token account of `owner` for `mint`, along with the instruction
+/// to create it if it does not exist yet.
+fn associated_token_account_instructions(
+    client: &FailoverClient,
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> (Pubkey, Vec<Instruction>) {
+    let associated_token_key = get_associated_token_address(owner, mint);
+    let mut instructions = vec![];
+    if client.get_account(&associated_token_key).is_err() {
+        instructions.push(create_associated_token_account(payer, owner, mint));
+    }
+    (associated_token_key, instructions)
+}
+
+/// Parses `amount` as base units with `--raw`, or as a UI amount at `decimals` otherwise.
+fn amount_of(app_matches: &ArgMatches, amount: &str, decimals: u8) -> Result<u64, String> {
+    if app_matches.is_present("raw") {
+        amount.parse::<u64>().map_err(|err| err.to_string())
+    } else {
+        ui_amount_to_base_units(amount, decimals)
     }
 }
 
-fn mint_coins(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
+/// Reads the `owner,amount` rows of a recipients file, skipping an `owner` header line. Exits on
+/// the first row that is not a wallet and an amount.
+fn read_recipients(app_matches: &ArgMatches, path: &str, decimals: u8) -> Vec<(Pubkey, u64)> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => exit_with_error(err.into(), json!({ "file": path })),
+    };
+    let mut recipients = vec![];
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (line_number == 0 && line.starts_with("owner")) {
+            continue;
+        }
+        let recipient = line.split_once(',').and_then(|(owner, amount)| {
+            let owner = Pubkey::from_str(owner.trim()).ok()?;
+            let amount = amount_of(app_matches, amount.trim(), decimals).ok()?;
+            Some((owner, amount))
+        });
+        match recipient {
+            Some(recipient) => recipients.push(recipient),
+            None => exit_with_error(
+                CliError::InvalidInput(format!(
+                    "Line {} of {} is not an owner,amount pair: {:?}",
+                    line_number + 1,
+                    path,
+                    line
+                )),
+                json!({ "file": path, "line": line_number + 1 }),
+            ),
+        }
+    }
+    recipients
+}
+
+/// Sends the mints of `batch` as one transaction, counting its recipients as succeeded or
+/// failed together.
+fn send_mint_batch(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    payer: &dyn Signer,
+    batch: &mut Vec<(Pubkey, Vec<Instruction>)>,
+    timings: &mut Timings,
+    summary: &mut Summary,
+    sent: &mut usize,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let instructions: Vec<Instruction> = batch
+        .iter()
+        .flat_map(|(_, instructions)| instructions.iter().cloned())
+        .collect();
+    let owners: Vec<String> = batch.iter().map(|(owner, _)| owner.to_string()).collect();
+    if !confirm_step(app_matches, &format!("Mint to {}", owners.join(", "))) {
+        owners.iter().for_each(|_| summary.skipped());
+        batch.clear();
+        return;
+    }
+    let signers = [payer];
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    let result = client
+        .get_recent_blockhash()
+        .and_then(|(recent_blockhash, _)| {
+            transaction.sign(&signers, recent_blockhash);
+            timings.time(*sent, &owners.join(" "), || {
+                send_and_confirm(app_matches, client, &mut transaction, &signers)
+            })
+        });
+    match result {
+        Ok(_) => {
+            println!("Minted to {}", owners.join(", "));
+            owners.iter().for_each(|_| summary.succeeded());
+        }
+        Err(err) => {
+            report_item_error(
+                "Transaction failed.",
+                &err.into(),
+                json!({ "owners": owners }),
+            );
+            owners.iter().for_each(|_| summary.failed());
+        }
+    }
+    *sent += 1;
+    batch.clear();
+}
+
+/// Mints to the associated token account of every recipient of `--recipients-file`, creating
+/// the accounts that do not exist yet and packing as many recipients into each transaction as
+/// fit in a packet. Returns whether every recipient was minted to.
+fn mint_coins_to_recipients(
+    app_matches: &ArgMatches,
+    payer: &dyn Signer,
+    client: &FailoverClient,
+    mint_key: &Pubkey,
+    mint: &Mint,
+) -> bool {
     let token_key = Pubkey::from_str(TOKEN_PROGRAM_PUBKEY).unwrap();
-    let amount = match app_matches.value_of("amount") {
-        Some(val) => Some(val.parse::<u64>().unwrap()),
-        None => None,
+    let path = app_matches.value_of("recipients_file").unwrap();
+    let recipients = read_recipients(app_matches, path, mint.decimals);
+
+    let mut timings = Timings::start(app_matches, client);
+    timings.estimate(recipients.len(), 1);
+    let mut summary = Summary::start();
+    let mut batch: Vec<(Pubkey, Vec<Instruction>)> = vec![];
+    let mut created = HashSet::new();
+    let mut sent = 0;
+    for (owner, amount) in recipients {
+        let (associated_token_key, mut instructions) =
+            associated_token_account_instructions(client, &payer.pubkey(), &owner, mint_key);
+        // An owner listed twice has its account created by the first row only.
+        if !created.insert(associated_token_key) {
+            instructions.clear();
+        }
+        instructions.push(
+            mint_to(
+                &token_key,
+                mint_key,
+                &associated_token_key,
+                &payer.pubkey(),
+                &[&payer.pubkey()],
+                amount,
+            )
+            .unwrap(),
+        );
+
+        let combined: Vec<Instruction> = batch
+            .iter()
+            .flat_map(|(_, instructions)| instructions.iter().cloned())
+            .chain(instructions.iter().cloned())
+            .collect();
+        let combined_size = bincode::serialized_size(&Transaction::new_with_payer(
+            &combined,
+            Some(&payer.pubkey()),
+        ))
+        .unwrap() as usize;
+        if combined_size > PACKET_DATA_SIZE {
+            send_mint_batch(
+                app_matches,
+                client,
+                payer,
+                &mut batch,
+                &mut timings,
+                &mut summary,
+                &mut sent,
+            );
+        }
+        batch.push((owner, instructions));
     }
+    send_mint_batch(
+        app_matches,
+        client,
+        payer,
+        &mut batch,
+        &mut timings,
+        &mut summary,
+        &mut sent,
+    );
+    timings.finish(app_matches, client);
+    summary.print();
+    summary.all_succeeded()
+}
+
+/// Removes the mint authority of `mint_key`, held by the payer, so no more tokens can be
+/// minted.
+fn disable_mint_authority(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    payer: &dyn Signer,
+    mint_key: &Pubkey,
+) {
+    let context = json!({ "mint": mint_key.to_string() });
+    let instruction = set_authority(
+        &spl_token::id(),
+        mint_key,
+        None,
+        AuthorityType::MintTokens,
+        &payer.pubkey(),
+        &[],
+    )
     .unwrap();
+    let signers = [payer];
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    let signature = client
+        .get_recent_blockhash()
+        .and_then(|(recent_blockhash, _)| {
+            transaction.sign(&signers, recent_blockhash);
+            send_and_confirm(app_matches, client, &mut transaction, &signers)
+        })
+        .unwrap_or_else(|err| exit_with_error(err.into(), context.clone()));
+    let supply = client
+        .get_account(mint_key)
+        .ok()
+        .and_then(|account| Mint::unpack(&account.data).ok())
+        .map(|mint| mint.supply);
+    println!(
+        "Disabled the mint authority of {} in {}, its supply is fixed at {:?} base units",
+        mint_key, signature, supply
+    );
+}
+
+fn mint_coins(app_matches: &ArgMatches, payer: Box<dyn Signer>, client: FailoverClient) {
+    let token_key = Pubkey::from_str(TOKEN_PROGRAM_PUBKEY).unwrap();
     let mint_key = pubkey_of(app_matches, "mint").unwrap();
+    let disable = app_matches.is_present("disable_mint_authority");
+    if disable && !app_matches.is_present("yes") {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "--disable-mint-authority stops {} from ever minting again and cannot be undone, \
+                 pass --yes to go ahead",
+                mint_key
+            )),
+            json!({ "mint": mint_key.to_string() }),
+        );
+    }
+    let mint_account = client.get_account(&mint_key).unwrap();
+    let mint = Mint::unpack(&mint_account.data).unwrap();
+    if app_matches.is_present("recipients_file") {
+        let minted =
+            mint_coins_to_recipients(app_matches, payer.as_ref(), &client, &mint_key, &mint);
+        if disable && !minted {
+            println!(
+                "Leaving the mint authority of {} in place because not every recipient was minted to",
+                mint_key
+            );
+        } else if disable {
+            disable_mint_authority(app_matches, &client, payer.as_ref(), &mint_key);
+        }
+        return;
+    }
+    let amount_str = app_matches.value_of("amount").unwrap();
+    let amount = amount_of(app_matches, amount_str, mint.decimals).unwrap_or_else(|err| {
+        exit_with_error(
+            CliError::InvalidInput(format!("Invalid amount: {}", err)),
+            json!({ "mint": mint_key.to_string(), "amount": amount_str }),
+        )
+    });
     let mut instructions = vec![];
 
-    let mut signers = vec![&payer];
+    let mut signers: Vec<&dyn Signer> = vec![payer.as_ref()];
     let destination_key: Pubkey;
     let destination = Keypair::new();
     if app_matches.is_present("destination") {
         destination_key = pubkey_of(app_matches, "destination").unwrap();
+    } else if app_matches.is_present("ata") || app_matches.is_present("owner") {
+        let owner = pubkey_of(app_matches, "owner").unwrap_or_else(|| payer.pubkey());
+        let (associated_token_key, mut ata_instructions) =
+            associated_token_account_instructions(&client, &payer.pubkey(), &owner, &mint_key);
+        destination_key = associated_token_key;
+        instructions.append(&mut ata_instructions);
     } else {
         destination_key = destination.pubkey();
         signers.push(&destination);
@@ -159,29 +1052,93 @@ fn mint_coins(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
         .unwrap(),
     );
     let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
-    let recent_blockhash = client.get_recent_blockhash().unwrap().0;
+    let signature = client
+        .get_recent_blockhash()
+        .and_then(|(recent_blockhash, _)| {
+            transaction.sign(&signers, recent_blockhash);
+            send_and_confirm(app_matches, &client, &mut transaction, &signers)
+        })
+        .unwrap_or_else(|err| {
+            exit_with_error(
+                err.into(),
+                json!({
+                    "mint": mint_key.to_string(),
+                    "destination": destination_key.to_string(),
+                    "amount": amount,
+                }),
+            )
+        });
 
-    transaction.sign(&signers, recent_blockhash);
-    client.send_and_confirm_transaction(&transaction).unwrap();
+    println!(
+        "Minted {:?} tokens ({} base units at {} decimals) to {:?} in {}.",
+        amount_str, amount, mint.decimals, destination_key, signature
+    );
+    if disable {
+        disable_mint_authority(app_matches, &client, payer.as_ref(), &mint_key);
+    }
+}
+/// Commands that never touch the token metadata program: they work offline, deploy it, or use
+/// only the system and token programs.
+const WITHOUT_METADATA_PROGRAM: &[&str] = &[
+    "approve",
+    "bootstrap",
+    "broadcast_batch",
+    "bulk_burn",
+    "file_refunds",
+    "freeze",
+    "holder",
+    "mint_coins",
+    "print_schema",
+    "revoke",
+    "thaw",
+    "validate_file",
+];
 
-    println!("Minted {:?} tokens to {:?}.", amount, destination_key);
+/// Checks that `program` is deployed and executable on the cluster, so pointing at a cluster
+/// without it, such as a fresh local validator, fails up front with a clear message rather
+/// than on the first account the command looks for.
+pub fn check_program_deployed(client: &FailoverClient, program: &Pubkey) -> Result<(), CliError> {
+    let account = client
+        .get_account_with_commitment(program, client.commitment())?
+        .value;
+    match account {
+        Some(account) if account.executable => Ok(()),
+        Some(_) => Err(CliError::InvalidInput(format!(
+            "{} on {} is not an executable program",
+            program,
+            client.url()
+        ))),
+        None => Err(CliError::InvalidInput(format!(
+            "The token metadata program {} is not deployed on {}. Deploy it, e.g. with \
+             bootstrap --program, or pass the address it is deployed at with --metadata-program",
+            program,
+            client.url()
+        ))),
+    }
 }
-fn show_reservation_list(app_matches: &ArgMatches, _payer: Keypair, client: RpcClient) {
-    let key = pubkey_of(app_matches, "key").unwrap();
-    let mut res_data = client.get_account(&key).unwrap();
+
+/// Decodes the reservation list in `account` with the program's own `get_reservation_list`.
+fn decode_reservation_list(
+    key: &Pubkey,
+    mut account: solana_sdk::account::Account,
+) -> Result<Box<dyn ReservationList>, ProgramError> {
     let mut lamports = 0;
     let account_info = AccountInfo::new(
-        &key,
+        key,
         false,
         false,
         &mut lamports,
-        &mut res_data.data,
-        &res_data.owner,
+        &mut account.data,
+        &account.owner,
         false,
         0,
     );
+    get_reservation_list(&account_info)
+}
 
-    let res_list = get_reservation_list(&account_info).unwrap();
+/// Prints the reservations and spot counts of the reservation list in `account`.
+fn print_reservation_list(key: &Pubkey, account: solana_sdk::account::Account) {
+    let res_list = decode_reservation_list(key, account).unwrap();
     println!("Res list {:?}", res_list.reservations());
     println!(
         "current res spots: {:?}",
@@ -191,42 +1148,412 @@ fn show_reservation_list(app_matches: &ArgMatches, _payer: Keypair, client: RpcC
     println!("supply snapshot: {:?}", res_list.supply_snapshot());
 }
 
-fn show(app_matches: &ArgMatches, _payer: Keypair, client: RpcClient) {
-    let program_key = spl_token_metadata::id();
-
-    let printing_mint_key = pubkey_of(app_matches, "mint").unwrap();
-    let master_metadata_seeds = &[
-        PREFIX.as_bytes(),
-        &program_key.as_ref(),
-        printing_mint_key.as_ref(),
-    ];
-    let (master_metadata_key, _) =
-        Pubkey::find_program_address(master_metadata_seeds, &program_key);
-
-    let master_metadata_account = client.get_account(&master_metadata_key).unwrap();
-    let master_metadata: Metadata =
-        try_from_slice_unchecked(&master_metadata_account.data).unwrap();
+fn show_reservation_list(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+) {
+    let key = pubkey_of(app_matches, "key").unwrap();
+    let res_data = client.get_account(&key).unwrap();
+    print_reservation_list(&key, res_data);
+}
 
-    let update_authority = master_metadata.update_authority;
+/// Returns every inconsistency in `res_list` that would leave a drop stuck: addresses
+/// reserved twice, more spots reserved than the list was set up for, and more spots used than
+/// were reserved.
+pub fn reservation_list_problems(res_list: &dyn ReservationList) -> Vec<String> {
+    let mut problems = vec![];
+    let reservations = res_list.reservations();
+    // Slots not handed out yet hold the default address.
+    let assigned: Vec<_> = reservations
+        .iter()
+        .filter(|r| r.address != Pubkey::default())
+        .collect();
+    for (index, reservation) in assigned.iter().enumerate() {
+        let earlier = assigned[..index]
+            .iter()
+            .any(|r| r.address == reservation.address);
+        let later = assigned[index + 1..]
+            .iter()
+            .filter(|r| r.address == reservation.address)
+            .count();
+        if !earlier && later > 0 {
+            problems.push(format!(
+                "{} is reserved {} times",
+                reservation.address,
+                later + 1
+            ));
+        }
+        if reservation.spots_remaining > reservation.total_spots {
+            problems.push(format!(
+                "{} has {} spots remaining of the {} reserved for it",
+                reservation.address, reservation.spots_remaining, reservation.total_spots
+            ));
+        }
+    }
 
-    let master_edition_seeds = &[
-        PREFIX.as_bytes(),
-        &program_key.as_ref(),
-        &master_metadata.mint.as_ref(),
-        EDITION.as_bytes(),
-    ];
-    let (master_edition_key, _) = Pubkey::find_program_address(master_edition_seeds, &program_key);
-    let master_edition_account_res = client.get_account(&master_edition_key);
+    let reserved: u64 = assigned.iter().map(|r| r.total_spots).sum();
+    let consumed: u64 = assigned
+        .iter()
+        .map(|r| r.total_spots.saturating_sub(r.spots_remaining))
+        .sum();
+    let total = res_list.total_reservation_spots();
+    let current = res_list.current_reservation_spots();
+    if reserved > total {
+        problems.push(format!(
+            "Reservations hold {} spots, the list has {}",
+            reserved, total
+        ));
+    }
+    if current > total {
+        problems.push(format!(
+            "{} spots are counted as reserved, the list has {}",
+            current, total
+        ));
+    }
+    if consumed > current {
+        problems.push(format!(
+            "{} spots are used, only {} are counted as reserved",
+            consumed, current
+        ));
+    }
+    problems
+}
 
-    println!("Metadata key: {:?}", master_metadata_key);
-    println!("Metadata: {:#?}", master_metadata);
-    println!("Update authority: {:?}", update_authority);
+fn validate_reservation_list(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let key = pubkey_of(app_matches, "key").unwrap();
+    let context = json!({ "key": key.to_string() });
+    let account = match client
+        .get_account_with_commitment(&key, client.commitment())
+        .unwrap()
+        .value
+    {
+        Some(account) => account,
+        None => exit_with_error(
+            CliError::AccountNotFound(format!("{} does not exist", key)),
+            context,
+        ),
+    };
+    let decoded = if account.owner == program.id && !account.data.is_empty() {
+        decode_reservation_list(&key, account).ok()
+    } else {
+        None
+    };
+    let res_list = match decoded {
+        Some(res_list) => res_list,
+        None => exit_with_error(
+            CliError::InvalidInput(format!("{} is not a reservation list", key)),
+            context,
+        ),
+    };
+
+    let problems = reservation_list_problems(res_list.as_ref());
+    if problems.is_empty() {
+        println!("Reservation list {} is consistent", key);
+        return;
+    }
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    exit_with_error(
+        CliError::InvalidInput(format!(
+            "Reservation list {} has {} problems",
+            key,
+            problems.len()
+        )),
+        context,
+    );
+}
+
+/// Prints one summary line per reservation list of `--keys-file`, followed by the totals across
+/// all of them. Lists are fetched a page at a time.
+fn bulk_reservation_lists(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let keys = read_mints_file(app_matches.value_of("keys_file").unwrap());
+    let output = app_matches.value_of("output").unwrap();
+
+    let mut lists = vec![];
+    let mut missing = vec![];
+    let mut undecodable = vec![];
+    let (mut current_spots, mut total_spots, mut reservations) = (0, 0, 0);
+    for page in keys.chunks(page_size_of(app_matches)) {
+        let accounts = client.get_multiple_accounts(page).unwrap();
+        for (key, account) in page.iter().zip(accounts) {
+            let account = match account {
+                Some(account) => account,
+                None => {
+                    missing.push(key.to_string());
+                    continue;
+                }
+            };
+            // Anything the program does not own, such as an empty account, is not a list.
+            let decoded = if account.owner == program.id && !account.data.is_empty() {
+                decode_reservation_list(key, account).ok()
+            } else {
+                None
+            };
+            let res_list = match decoded {
+                Some(res_list) => res_list,
+                None => {
+                    undecodable.push(key.to_string());
+                    continue;
+                }
+            };
+            current_spots += res_list.current_reservation_spots();
+            total_spots += res_list.total_reservation_spots();
+            reservations += res_list.reservations().len();
+            lists.push(json!({
+                "key": key.to_string(),
+                "master_edition": res_list.master_edition().to_string(),
+                "current_reservation_spots": res_list.current_reservation_spots(),
+                "total_reservation_spots": res_list.total_reservation_spots(),
+                "supply_snapshot": res_list.supply_snapshot(),
+                "reservations": res_list.reservations().len(),
+            }));
+        }
+    }
+
+    if output == "json" {
+        let report = json!({
+            "lists": lists,
+            "missing": missing,
+            "undecodable": undecodable,
+            "totals": {
+                "lists": lists.len(),
+                "current_reservation_spots": current_spots,
+                "total_reservation_spots": total_spots,
+                "reservations": reservations,
+            },
+        });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return;
+    }
+    for list in &lists {
+        println!(
+            "{} spots {}/{} supply snapshot {} reservations {}",
+            list["key"].as_str().unwrap(),
+            list["current_reservation_spots"],
+            list["total_reservation_spots"],
+            list["supply_snapshot"],
+            list["reservations"]
+        );
+    }
+    for key in &missing {
+        println!("{} does not exist", key);
+    }
+    for key in &undecodable {
+        println!("{} is not a reservation list", key);
+    }
+    println!(
+        "Totals: {} lists, spots {}/{}, {} reservations",
+        lists.len(),
+        current_spots,
+        total_spots,
+        reservations
+    );
+}
+
+/// Prints every reservation list of the deprecated master edition at `master_edition_key`. The
+/// list PDAs are seeded with a resource key chosen by whoever created them, so rather than
+/// being derived they are found by the master edition they point back to.
+fn show_reservation_lists_of(
+    client: &FailoverClient,
+    program: &MetadataProgram,
+    master_edition_key: &Pubkey,
+) {
+    let program_key = program.id;
+    let mut found = 0;
+    for key in [Key::ReservationListV1, Key::ReservationListV2] {
+        let filters = vec![
+            memcmp_filter(0, &[key as u8]),
+            memcmp_filter(1, master_edition_key.as_ref()),
+        ];
+        for list_key in get_program_account_keys(client, &program_key, filters) {
+            println!("Reservation list {}", list_key);
+            print_reservation_list(&list_key, client.get_account(&list_key).unwrap());
+            found += 1;
+        }
+    }
+    if found == 0 {
+        println!("No reservation lists found for this master edition");
+    }
+}
+
+/// The reservations, spot counts and supply snapshot of `res_list` at `key` as JSON.
+pub fn reservation_list_json(key: &Pubkey, version: Key, res_list: &dyn ReservationList) -> Value {
+    let reservations: Vec<Value> = res_list
+        .reservations()
+        .iter()
+        .map(|reservation| {
+            json!({
+                "address": reservation.address.to_string(),
+                "spots_remaining": reservation.spots_remaining,
+                "total_spots": reservation.total_spots,
+            })
+        })
+        .collect();
+    json!({
+        "key": key.to_string(),
+        "version": format!("{:?}", version),
+        "master_edition": res_list.master_edition().to_string(),
+        "supply_snapshot": res_list.supply_snapshot(),
+        "total_reservation_spots": res_list.total_reservation_spots(),
+        "current_reservation_spots": res_list.current_reservation_spots(),
+        "reservations": reservations,
+    })
+}
+
+/// Writes every reservation list of the program to `--output`, found by their key byte so only
+/// reservation lists are fetched.
+fn dump_reservation_lists(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let output = app_matches.value_of("output").unwrap();
+    let mut lists = vec![];
+    for version in [Key::ReservationListV1, Key::ReservationListV2] {
+        let accounts = scan_program_accounts(
+            &client,
+            &program.id,
+            vec![memcmp_filter(0, &[version as u8])],
+            page_size_of(app_matches),
+        );
+        for (key, account) in accounts {
+            match decode_reservation_list(&key, account) {
+                Ok(res_list) => lists.push(reservation_list_json(&key, version, res_list.as_ref())),
+                Err(err) => report_undecodable(&key, &err.to_string()),
+            }
+        }
+    }
+    if let Err(err) = fs::write(output, serde_json::to_string_pretty(&lists).unwrap()) {
+        exit_with_error(err.into(), json!({ "output": output }));
+    }
+    println!("Wrote {} reservation lists to {}", lists.len(), output);
+}
+
+/// Prints the length of `data` and a hex dump of it, 16 bytes per row, marking where each of
+/// the `fields` begins.
+fn print_hex_dump(data: &[u8], fields: &[(usize, &str)]) {
+    println!("Data length: {}", data.len());
+    for (row, chunk) in data.chunks(16).enumerate() {
+        let start = row * 16;
+        for (offset, field) in fields {
+            if *offset >= start && *offset < start + chunk.len() {
+                println!("-- {} at {:#06x}", field, offset);
+            }
+        }
+        let bytes: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        println!("{:#06x}  {}", start, bytes.join(" "));
+    }
+}
+
+/// What kind of account an update authority is: a wallet, a program or a PDA, and who owns it.
+pub fn describe_authority(key: &Pubkey, account: Option<&solana_sdk::account::Account>) -> String {
+    let address = if key.is_on_curve() {
+        "wallet address"
+    } else {
+        "PDA, no private key can sign for it"
+    };
+    match account {
+        None => format!("{} with no account", address),
+        Some(account) if account.executable => "program".to_owned(),
+        Some(account) if account.owner == solana_program::system_program::id() => {
+            format!("system account, {}", address)
+        }
+        Some(account) => format!("account owned by {}, {}", account.owner, address),
+    }
+}
+
+fn show(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let printing_mint_key = pubkey_of(app_matches, "mint").unwrap();
+    let master_metadata_key = program.metadata_pda(&printing_mint_key);
+
+    let master_metadata_account =
+        get_metadata_account_or_exit(&client, &printing_mint_key, &master_metadata_key);
+    let raw = app_matches.is_present("raw");
+    if raw {
+        // Dumped before decoding so the bytes are shown even when deserialization fails.
+        print_hex_dump(
+            &master_metadata_account.data,
+            &[
+                (0, "key"),
+                (1, "update authority"),
+                (33, "mint"),
+                (65, "data (name, symbol, uri, ...)"),
+            ],
+        );
+    }
+    let (master_metadata, version) =
+        read_metadata(&master_metadata_account.data).unwrap_or_else(|err| {
+            exit_with_error(
+                CliError::InvalidInput(format!(
+                    "Metadata {} does not decode as any known version: {}",
+                    master_metadata_key, err
+                )),
+                json!({ "mint": printing_mint_key.to_string() }),
+            )
+        });
+
+    let update_authority = master_metadata.update_authority;
+
+    let master_edition_key = program.edition_pda(&master_metadata.mint);
+    let master_edition_account_res = client.get_account(&master_edition_key);
+
+    println!("Metadata key: {:?}", master_metadata_key);
+    println!("Metadata version: {}", version);
+    println!("Metadata: {:#?}", master_metadata);
+    println!("Update authority: {:?}", update_authority);
+    if app_matches.is_present("show_authority_balance") {
+        let account = client
+            .get_account_with_commitment(&update_authority, client.commitment())
+            .unwrap_or_else(|err| {
+                exit_with_error(
+                    err.into(),
+                    json!({ "update_authority": update_authority.to_string() }),
+                )
+            })
+            .value;
+        let lamports = account.as_ref().map_or(0, |account| account.lamports);
+        println!(
+            "Update authority balance: {} SOL",
+            lamports_to_sol(lamports)
+        );
+        println!(
+            "Update authority kind: {}",
+            describe_authority(&update_authority, account.as_ref())
+        );
+    }
     match master_edition_account_res {
         Ok(master_edition_account) => {
+            if raw {
+                print_hex_dump(
+                    &master_edition_account.data,
+                    &[(0, "key"), (1, "supply or parent")],
+                );
+            }
             if master_edition_account.data[0] == Key::MasterEditionV1 as u8 {
                 let master_edition: MasterEditionV1 =
                     try_from_slice_unchecked(&master_edition_account.data).unwrap();
                 println!("Deprecated Master edition {:#?}", master_edition);
+                if app_matches.is_present("reservation_lists") {
+                    show_reservation_lists_of(&client, program, &master_edition_key);
+                }
             } else if master_edition_account.data[0] == Key::MasterEditionV2 as u8 {
                 let master_edition: MasterEditionV2 =
                     try_from_slice_unchecked(&master_edition_account.data).unwrap();
@@ -245,18 +1572,12 @@ fn show(app_matches: &ArgMatches, _payer: Keypair, client: RpcClient) {
 
 fn mint_edition_via_token_call(
     app_matches: &ArgMatches,
-    payer: Keypair,
-    client: RpcClient,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
 ) -> (Edition, Pubkey, Pubkey) {
-    let account_authority = read_keypair_file(
-        app_matches
-            .value_of("account_authority")
-            .unwrap_or_else(|| app_matches.value_of("keypair").unwrap()),
-    )
-    .unwrap();
-
-    let program_key = spl_token_metadata::id();
-    let token_key = Pubkey::from_str(TOKEN_PROGRAM_PUBKEY).unwrap();
+    let account_authority = signer_of_or_keypair(app_matches, "account_authority", wallet_manager);
 
     let mint_key = pubkey_of(app_matches, "mint").unwrap();
     let existing_token_account = Pubkey::from_str(
@@ -281,107 +1602,68 @@ fn mint_edition_via_token_call(
 
     let new_mint_key = Keypair::new();
     let added_token_account = Keypair::new();
-    let new_mint_pub = new_mint_key.pubkey();
-    let metadata_seeds = &[
-        PREFIX.as_bytes(),
-        &program_key.as_ref(),
-        &new_mint_pub.as_ref(),
-    ];
-    let (metadata_key, _) = Pubkey::find_program_address(metadata_seeds, &program_key);
-
-    let edition_seeds = &[
-        PREFIX.as_bytes(),
-        &program_key.as_ref(),
-        &new_mint_pub.as_ref(),
-        EDITION.as_bytes(),
-    ];
-    let (edition_key, _) = Pubkey::find_program_address(edition_seeds, &program_key);
-
-    let master_metadata_seeds = &[PREFIX.as_bytes(), &program_key.as_ref(), mint_key.as_ref()];
-    let (master_metadata_key, _) =
-        Pubkey::find_program_address(master_metadata_seeds, &program_key);
+    let edition_key = program.edition_pda(&new_mint_key.pubkey());
 
-    let master_metadata_account = client.get_account(&master_metadata_key).unwrap();
-    let master_metadata: Metadata =
-        try_from_slice_unchecked(&master_metadata_account.data).unwrap();
-
-    let master_edition_seeds = &[
-        PREFIX.as_bytes(),
-        &program_key.as_ref(),
-        &master_metadata.mint.as_ref(),
-        EDITION.as_bytes(),
-    ];
-    let (master_edition_key, _) = Pubkey::find_program_address(master_edition_seeds, &program_key);
-    let master_edition_account = client.get_account(&master_edition_key).unwrap();
+    let master_metadata_account =
+        get_metadata_account_or_exit(&client, &mint_key, &program.metadata_pda(&mint_key));
+    let master_metadata = decode_metadata(&master_metadata_account.data).unwrap();
+    let master_edition_account = client
+        .get_account(&program.edition_pda(&master_metadata.mint))
+        .unwrap();
     let master_edition: MasterEditionV2 =
         try_from_slice_unchecked(&master_edition_account.data).unwrap();
-    let signers = vec![&account_authority, &new_mint_key, &added_token_account];
-    let mut instructions = vec![
-        create_account(
-            &payer.pubkey(),
-            &new_mint_key.pubkey(),
-            client
-                .get_minimum_balance_for_rent_exemption(Mint::LEN)
-                .unwrap(),
-            Mint::LEN as u64,
-            &token_key,
-        ),
-        initialize_mint(
-            &token_key,
-            &new_mint_key.pubkey(),
-            &payer.pubkey(),
-            Some(&payer.pubkey()),
-            0,
-        )
-        .unwrap(),
-        create_account(
-            &payer.pubkey(),
-            &added_token_account.pubkey(),
-            client
-                .get_minimum_balance_for_rent_exemption(Account::LEN)
-                .unwrap(),
-            Account::LEN as u64,
-            &token_key,
-        ),
-        initialize_account(
-            &token_key,
-            &added_token_account.pubkey(),
-            &new_mint_key.pubkey(),
-            &payer.pubkey(),
-        )
-        .unwrap(),
-        mint_to(
-            &token_key,
-            &new_mint_key.pubkey(),
-            &added_token_account.pubkey(),
-            &payer.pubkey(),
-            &[&payer.pubkey()],
-            1,
-        )
-        .unwrap(),
-    ];
+    let edition = match value_of::<u64>(app_matches, "edition") {
+        Some(edition) => {
+            let context = json!({ "mint": mint_key.to_string(), "edition": edition });
+            if let Some(max_supply) = master_edition.max_supply.filter(|max| edition > *max) {
+                exit_with_error(
+                    CliError::InvalidInput(format!(
+                        "Edition {} is past the max supply of {}",
+                        edition, max_supply
+                    )),
+                    context,
+                );
+            }
+            if edition_taken(&client, program, &master_metadata.mint, edition) {
+                exit_with_error(
+                    CliError::InvalidInput(format!("Edition {} is already printed", edition)),
+                    context,
+                );
+            }
+            println!(
+                "Warning: printing edition {} instead of {}, the transaction fails if another print takes it first",
+                edition,
+                master_edition.supply + 1
+            );
+            edition
+        }
+        None => master_edition.supply + 1,
+    };
 
-    instructions.push(mint_new_edition_from_master_edition_via_token(
-        program_key,
-        metadata_key,
-        edition_key,
-        master_edition_key,
-        new_mint_key.pubkey(),
-        account_authority.pubkey(),
-        payer.pubkey(),
-        account_authority.pubkey(),
-        existing_token_account,
-        account_authority.pubkey(),
-        master_metadata_key,
-        master_metadata.mint,
-        master_edition.supply + 1,
-    ));
+    let instructions = build_mint_edition_instructions(
+        program,
+        &payer.pubkey(),
+        &account_authority.pubkey(),
+        &master_metadata.mint,
+        &existing_token_account,
+        &new_mint_key.pubkey(),
+        &added_token_account.pubkey(),
+        edition,
+        client
+            .get_minimum_balance_for_rent_exemption(Mint::LEN)
+            .unwrap(),
+        client
+            .get_minimum_balance_for_rent_exemption(Account::LEN)
+            .unwrap(),
+    );
+    let signers: Vec<&dyn Signer> = vec![
+        account_authority.as_ref(),
+        &new_mint_key,
+        &added_token_account,
+    ];
 
     let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
-    let recent_blockhash = client.get_recent_blockhash().unwrap().0;
-
-    transaction.sign(&signers, recent_blockhash);
-    client.send_and_confirm_transaction(&transaction).unwrap();
+    sign_and_send(app_matches, &client, &mut transaction, &signers);
     let account = client.get_account(&edition_key).unwrap();
     let edition: Edition = try_from_slice_unchecked(&account.data).unwrap();
     (edition, edition_key, new_mint_key.pubkey())
@@ -389,39 +1671,19 @@ fn mint_edition_via_token_call(
 
 fn master_edition_call(
     app_matches: &ArgMatches,
-    payer: Keypair,
-    client: RpcClient,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
 ) -> (MasterEditionV2, Pubkey) {
-    let update_authority = read_keypair_file(
-        app_matches
-            .value_of("update_authority")
-            .unwrap_or_else(|| app_matches.value_of("keypair").unwrap()),
-    )
-    .unwrap();
-    let mint_authority = read_keypair_file(
-        app_matches
-            .value_of("mint_authority")
-            .unwrap_or_else(|| app_matches.value_of("keypair").unwrap()),
-    )
-    .unwrap();
-
-    let program_key = spl_token_metadata::id();
-    let token_key = Pubkey::from_str(TOKEN_PROGRAM_PUBKEY).unwrap();
+    let update_authority = signer_of_or_keypair(app_matches, "update_authority", wallet_manager);
+    let mint_authority = signer_of_or_keypair(app_matches, "mint_authority", wallet_manager);
 
     let mint_key = pubkey_of(app_matches, "mint").unwrap();
-    let metadata_seeds = &[PREFIX.as_bytes(), &program_key.as_ref(), mint_key.as_ref()];
-    let (metadata_key, _) = Pubkey::find_program_address(metadata_seeds, &program_key);
-
-    let metadata_account = client.get_account(&metadata_key).unwrap();
-    let metadata: Metadata = try_from_slice_unchecked(&metadata_account.data).unwrap();
-
-    let master_edition_seeds = &[
-        PREFIX.as_bytes(),
-        &program_key.as_ref(),
-        &metadata.mint.as_ref(),
-        EDITION.as_bytes(),
-    ];
-    let (master_edition_key, _) = Pubkey::find_program_address(master_edition_seeds, &program_key);
+    let metadata_account =
+        get_metadata_account_or_exit(&client, &mint_key, &program.metadata_pda(&mint_key));
+    let metadata = decode_metadata(&metadata_account.data).unwrap();
+    let master_edition_key = program.edition_pda(&metadata.mint);
 
     let max_supply = match app_matches.value_of("max_supply") {
         Some(val) => Some(val.parse::<u64>().unwrap()),
@@ -431,78 +1693,333 @@ fn master_edition_call(
     let added_token_account = Keypair::new();
 
     let needs_a_token = app_matches.is_present("add_one_token");
-    let mut signers = vec![&update_authority, &mint_authority];
-    let mut instructions = vec![];
-
-    if needs_a_token {
+    let mut signers = vec![update_authority.as_ref(), mint_authority.as_ref()];
+    let added_token_account_rent = if needs_a_token {
         signers.push(&added_token_account);
-        instructions.push(create_account(
-            &payer.pubkey(),
-            &added_token_account.pubkey(),
+        Some(
             client
                 .get_minimum_balance_for_rent_exemption(Account::LEN)
                 .unwrap(),
-            Account::LEN as u64,
-            &token_key,
-        ));
-        instructions.push(
-            initialize_account(
-                &token_key,
-                &added_token_account.pubkey(),
-                &metadata.mint,
-                &payer.pubkey(),
-            )
-            .unwrap(),
-        );
-        instructions.push(
-            mint_to(
-                &token_key,
-                &metadata.mint,
-                &added_token_account.pubkey(),
-                &payer.pubkey(),
-                &[&payer.pubkey()],
-                1,
-            )
-            .unwrap(),
         )
-    }
+    } else {
+        None
+    };
 
-    instructions.push(create_master_edition(
-        program_key,
-        master_edition_key,
-        mint_key,
-        update_authority.pubkey(),
-        mint_authority.pubkey(),
-        metadata_key,
-        payer.pubkey(),
+    let added_token_account_key = added_token_account.pubkey();
+    let payer_key = payer.pubkey();
+    let instructions = build_master_edition_instructions(
+        program,
+        &payer.pubkey(),
+        &update_authority.pubkey(),
+        &mint_authority.pubkey(),
+        &metadata.mint,
         max_supply,
-    ));
+        added_token_account_rent.map(|rent| (&added_token_account_key, &payer_key, rent)),
+    );
 
     let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
-    let recent_blockhash = client.get_recent_blockhash().unwrap().0;
-
-    transaction.sign(&signers, recent_blockhash);
-    client.send_and_confirm_transaction(&transaction).unwrap();
+    sign_and_send(app_matches, &client, &mut transaction, &signers);
     let account = client.get_account(&master_edition_key).unwrap();
     let master_edition: MasterEditionV2 = try_from_slice_unchecked(&account.data).unwrap();
+    let mut differences = vec![];
+    if master_edition.max_supply != max_supply {
+        differences.push(format!(
+            "max_supply: {:?} -> {:?}",
+            max_supply, master_edition.max_supply
+        ));
+    }
+    if master_edition.supply != 0 {
+        differences.push(format!("supply: 0 -> {}", master_edition.supply));
+    }
+    verify_after_send(app_matches, &master_edition_key, differences);
     (master_edition, master_edition_key)
 }
 
+/// Parses `--max-supply`, where `unlimited` means no maximum.
+fn max_supply_of(app_matches: &ArgMatches) -> Option<u64> {
+    match app_matches.value_of("max_supply").unwrap() {
+        "unlimited" => None,
+        val => Some(val.parse::<u64>().unwrap()),
+    }
+}
+
+fn is_jitter(val: String) -> Result<(), String> {
+    match val.parse::<f64>() {
+        Ok(jitter) if (0.0..=1.0).contains(&jitter) => Ok(()),
+        _ => Err(format!("{} is not a fraction between 0 and 1", val)),
+    }
+}
+
+fn is_edition_number(val: String) -> Result<(), String> {
+    match val.parse::<u64>() {
+        Ok(edition) if edition > 0 => Ok(()),
+        _ => Err(format!(
+            "{} is not an edition number, editions start at 1",
+            val
+        )),
+    }
+}
+
+/// The name `template` gives item `n`, with every `{n}` in it replaced by the number.
+pub fn numbered_name(template: &str, n: u64) -> String {
+    template.replace("{n}", &n.to_string())
+}
+
+fn is_name_template(val: String) -> Result<(), String> {
+    if val.contains("{n}") {
+        Ok(())
+    } else {
+        Err(format!("{:?} has no {{n}} to number the items with", val))
+    }
+}
+
+fn is_seller_fee(val: String) -> Result<(), String> {
+    match val.parse::<u16>() {
+        Ok(basis_points) if basis_points <= 10000 => Ok(()),
+        _ => Err(format!(
+            "{} is not a number of basis points up to 10000",
+            val
+        )),
+    }
+}
+
+fn is_max_supply(val: String) -> Result<(), String> {
+    if val == "unlimited" {
+        Ok(())
+    } else {
+        is_parsable::<u64>(val)
+    }
+}
+
+/// Sends the master edition instructions of the mints in `batch` as one transaction, recording
+/// the mints as created or failed together.
+#[allow(clippy::too_many_arguments)]
+fn send_master_edition_batch(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    payer: &dyn Signer,
+    authorities: &[&dyn Signer],
+    batch: &mut Vec<(Pubkey, Vec<Instruction>, Option<Keypair>)>,
+    timings: &mut Timings,
+    created: &mut Vec<String>,
+    failed: &mut Vec<String>,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let instructions: Vec<Instruction> = batch
+        .iter()
+        .flat_map(|(_, instructions, _)| instructions.iter().cloned())
+        .collect();
+    let mut signers: Vec<&dyn Signer> = authorities.to_vec();
+    signers.extend(
+        batch
+            .iter()
+            .filter_map(|(_, _, token_account)| token_account.as_ref())
+            .map(|token_account| token_account as &dyn Signer),
+    );
+    let mints: Vec<String> = batch.iter().map(|(mint, _, _)| mint.to_string()).collect();
+    if !confirm_step(
+        app_matches,
+        &format!("Create master editions for {}", mints.join(", ")),
+    ) {
+        batch.clear();
+        return;
+    }
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    let result = client
+        .get_recent_blockhash()
+        .and_then(|(recent_blockhash, _)| {
+            transaction.sign(&signers, recent_blockhash);
+            timings.time(created.len() + failed.len(), &mints.join(" "), || {
+                send_and_confirm(app_matches, client, &mut transaction, &signers)
+            })
+        });
+    match result {
+        Ok(_) => {
+            println!("Created master editions for {}", mints.join(", "));
+            created.extend(mints);
+        }
+        Err(err) => {
+            report_item_error(
+                "Transaction failed.",
+                &err.into(),
+                json!({ "mints": mints }),
+            );
+            failed.extend(mints);
+        }
+    }
+    batch.clear();
+}
+
+fn bulk_master_edition(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    let update_authority = signer_of_or_keypair(app_matches, "update_authority", wallet_manager);
+    let mint_authority = signer_of_or_keypair(app_matches, "mint_authority", wallet_manager);
+    let authorities = [update_authority.as_ref(), mint_authority.as_ref()];
+    // The wallets of a record get the tokens minted here, other mints' go to the payer.
+    let mints: Vec<(Pubkey, Option<Pubkey>)> = match app_matches.value_of("record") {
+        Some(path) => read_record_file(path)
+            .into_iter()
+            .map(|(mint, wallet)| (mint, Some(wallet)))
+            .collect(),
+        None => read_mints_file(app_matches.value_of("mints_file").unwrap())
+            .into_iter()
+            .map(|mint| (mint, None))
+            .collect(),
+    };
+    let max_supply = max_supply_of(app_matches);
+    let batch_size = app_matches
+        .value_of("batch_size")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    let token_account_rent = client
+        .get_minimum_balance_for_rent_exemption(Account::LEN)
+        .unwrap();
+
+    let mut timings = Timings::start(app_matches, &client);
+    // Each mint may add a token account, signing for itself.
+    timings.estimate(
+        mints.len(),
+        unique_signers(vec![
+            payer.as_ref(),
+            update_authority.as_ref(),
+            mint_authority.as_ref(),
+        ])
+        .len()
+            + 1,
+    );
+    let mut batch: Vec<(Pubkey, Vec<Instruction>, Option<Keypair>)> = vec![];
+    let mut created = vec![];
+    let mut failed = vec![];
+    for &(mint, wallet) in &mints {
+        if client
+            .get_account_with_commitment(&program.edition_pda(&mint), client.commitment())
+            .unwrap()
+            .value
+            .is_some()
+        {
+            println!("Skipping {} because it already has an edition", mint);
+            continue;
+        }
+        if client
+            .get_account_with_commitment(&program.metadata_pda(&mint), client.commitment())
+            .unwrap()
+            .value
+            .is_none()
+        {
+            println!("Skipping {} because it has no metadata", mint);
+            continue;
+        }
+
+        // A master edition needs the one token of a 1/1 to exist, so mint it when nobody has.
+        let added_token_account = match client.get_token_supply(&mint) {
+            Ok(supply) if supply.amount == "0" => Some(Keypair::new()),
+            Ok(_) => None,
+            Err(err) => {
+                report_item_error(
+                    "Could not read the mint supply.",
+                    &err.into(),
+                    json!({ "mint": mint.to_string() }),
+                );
+                failed.push(mint.to_string());
+                continue;
+            }
+        };
+        let added_token_account_key = added_token_account.as_ref().map(|keypair| keypair.pubkey());
+        let owner = wallet.unwrap_or_else(|| payer.pubkey());
+        let instructions = build_master_edition_instructions(
+            program,
+            &payer.pubkey(),
+            &update_authority.pubkey(),
+            &mint_authority.pubkey(),
+            &mint,
+            max_supply,
+            added_token_account_key
+                .as_ref()
+                .map(|key| (key, &owner, token_account_rent)),
+        );
+
+        // Start a new transaction when this mint would push the current one past the batch
+        // size or out of a packet.
+        let combined: Vec<Instruction> = batch
+            .iter()
+            .flat_map(|(_, instructions, _)| instructions.iter().cloned())
+            .chain(instructions.iter().cloned())
+            .collect();
+        let combined_size = bincode::serialized_size(&Transaction::new_with_payer(
+            &combined,
+            Some(&payer.pubkey()),
+        ))
+        .unwrap() as usize;
+        if batch.len() >= batch_size || combined_size > PACKET_DATA_SIZE {
+            send_master_edition_batch(
+                app_matches,
+                &client,
+                payer.as_ref(),
+                &authorities,
+                &mut batch,
+                &mut timings,
+                &mut created,
+                &mut failed,
+            );
+        }
+        batch.push((mint, instructions, added_token_account));
+    }
+    send_master_edition_batch(
+        app_matches,
+        &client,
+        payer.as_ref(),
+        &authorities,
+        &mut batch,
+        &mut timings,
+        &mut created,
+        &mut failed,
+    );
+
+    println!(
+        "Created {} master editions, {} failed",
+        created.len(),
+        failed.len()
+    );
+    let failed_file = app_matches.value_of("failed_file").unwrap();
+    // Failed llamas of a record are written as record lines, to retry with their wallets.
+    let contents = if app_matches.is_present("record") {
+        mints
+            .iter()
+            .filter(|(mint, _)| failed.contains(&mint.to_string()))
+            .map(|(mint, wallet)| {
+                format!(
+                    "{}\n",
+                    json!({ "wallet": wallet.unwrap().to_string(), "mint": mint.to_string() })
+                )
+            })
+            .collect()
+    } else {
+        serde_json::to_string(&failed).unwrap()
+    };
+    fs::write(failed_file, contents).unwrap();
+    println!("Wrote the failed mints to {}", failed_file);
+    timings.finish(app_matches, &client);
+}
+
 fn update_metadata_account_call(
     app_matches: &ArgMatches,
-    payer: Keypair,
-    client: RpcClient,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
 ) -> (Metadata, Pubkey) {
-    let update_authority = read_keypair_file(
-        app_matches
-            .value_of("update_authority")
-            .unwrap_or_else(|| app_matches.value_of("keypair").unwrap()),
-    )
-    .unwrap();
-    let program_key = spl_token_metadata::id();
+    let update_authority = signer_of_or_keypair(app_matches, "update_authority", wallet_manager);
     let mint_key = pubkey_of(app_matches, "mint").unwrap();
-    let metadata_seeds = &[PREFIX.as_bytes(), &program_key.as_ref(), mint_key.as_ref()];
-    let (metadata_key, _) = Pubkey::find_program_address(metadata_seeds, &program_key);
+    let metadata_key = program.metadata_pda(&mint_key);
 
     let uri = match app_matches.value_of("uri") {
         Some(val) => Some(val.to_owned()),
@@ -514,157 +2031,626 @@ fn update_metadata_account_call(
         None => None,
     };
 
-    let new_update_authority = pubkey_of(app_matches, "new_update_authority");
+    let symbol = app_matches.value_of("symbol").map(|val| val.to_owned());
+
+    let new_update_authority = pubkey_of(app_matches, "new_update_authority");
+
+    let metadata_account = client.get_account(&metadata_key).unwrap();
+    let metadata = decode_metadata(&metadata_account.data).unwrap();
+    check_can_update(
+        &mint_key,
+        &metadata_key,
+        &metadata,
+        update_authority.as_ref(),
+    );
+
+    let seller_fee_basis_points =
+        value_of::<u16>(app_matches, "seller_fee").unwrap_or(metadata.data.seller_fee_basis_points);
+    let creators = match app_matches.values_of("creators") {
+        Some(values) => {
+            let shares: Vec<(Pubkey, u8)> = values
+                .map(|value| parse_creator_share(value).unwrap())
+                .collect();
+            let existing = metadata.data.creators.clone().unwrap_or_default();
+            let creators = creators_from_shares(&shares, &existing);
+            if let Err(err) =
+                validate_creator_update(&creators, &existing, &update_authority.pubkey())
+            {
+                exit_with_error(
+                    CliError::InvalidInput(err),
+                    json!({ "mint": mint_key.to_string() }),
+                );
+            }
+            Some(creators)
+        }
+        None => metadata.data.creators,
+    };
+
+    let new_data = Data {
+        name: name.unwrap_or(metadata.data.name),
+        symbol: symbol.unwrap_or(metadata.data.symbol),
+        uri: uri.unwrap_or(metadata.data.uri),
+        seller_fee_basis_points,
+        creators,
+    };
+    if let Err(err) = check_field_lengths(&new_data.name, &new_data.symbol, &new_data.uri) {
+        exit_with_error(err, json!({ "mint": mint_key.to_string() }));
+    }
+
+    let instructions = build_update_metadata_instructions(
+        program,
+        &update_authority.pubkey(),
+        &mint_key,
+        new_update_authority,
+        new_data.clone(),
+    );
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    let signers = vec![update_authority.as_ref()];
+    sign_and_send(app_matches, &client, &mut transaction, &signers);
+    let metadata_account = client.get_account(&metadata_key).unwrap();
+    let metadata = decode_metadata(&metadata_account.data).unwrap();
+    let mut differences = data_differences(&new_data, &metadata.data);
+    let expected_authority = new_update_authority.unwrap_or_else(|| update_authority.pubkey());
+    if metadata.update_authority != expected_authority {
+        differences.push(format!(
+            "update_authority: {} -> {}",
+            expected_authority, metadata.update_authority
+        ));
+    }
+    verify_after_send(app_matches, &metadata_key, differences);
+    (metadata, metadata_key)
+}
+
+/// Reads the entries `pull_llama_arweave_uris` appended to its progress file, one JSON object
+/// per line. A run interrupted while writing can leave the last line cut off, which is dropped
+/// so its item is pulled again.
+pub fn read_uri_progress(contents: &str) -> Result<Vec<Value>, String> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut entries = vec![];
+    for (number, line) in lines.iter().enumerate() {
+        match serde_json::from_str::<Value>(line) {
+            Ok(entry) if entry["index"].is_u64() => entries.push(entry),
+            _ if number + 1 == lines.len() && !contents.ends_with('\n') => break,
+            _ => return Err(format!("Line {} is not a pulled uri", number + 1)),
+        }
+    }
+    Ok(entries)
+}
+
+/// How long a request for an off-chain file may take before it counts as failed.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An HTTP client for off-chain files, giving up on a request after `HTTP_TIMEOUT`.
+pub fn http_client() -> reqwest::blocking::Client {
+    reqwest::blocking::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .unwrap()
+}
+
+/// Fetches the body at `uri`, trying again up to `retries` times after a failed request or a
+/// server error. Client errors such as a missing file are not retried, except rate limiting.
+fn fetch_uri_body(
+    app_matches: &ArgMatches,
+    http: &reqwest::blocking::Client,
+    uri: &str,
+    retries: usize,
+) -> Option<String> {
+    let mut attempt = 0;
+    loop {
+        throttle(app_matches);
+        let err = match http
+            .get(uri)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+        {
+            Ok(body) => return Some(body),
+            Err(err) => err,
+        };
+        let final_status = err.status().map_or(false, |status| {
+            status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+        });
+        if final_status || attempt >= retries {
+            println!("Arweave URL {} cannot be fetched: {}", uri, err);
+            return None;
+        }
+        attempt += 1;
+        eprintln!(
+            "Fetching {} failed: {}. Retry {} of {}.",
+            uri, err, attempt, retries
+        );
+        backoff(app_matches, Duration::from_millis(1000));
+    }
+}
+
+/// Pulls the uri and manifest of the metadata listed in `all_metadata.json` over `start..end`.
+/// Each item is appended to a JSON lines progress file as soon as it is pulled, so an
+/// interrupted run continues after the last item written with `--resume`. The whole range is
+/// then written to `metadata_uris_<start>_<end>.json` as `[uri, manifest, metadata]` triples.
+fn pull_llama_arweave_uris(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+) {
+    let mut file = File::open("all_metadata.json").unwrap();
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    let keys: Vec<String> = serde_json::from_str(&contents).unwrap();
+    let len = keys.len();
+    let start = app_matches
+        .value_of("start")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    let end = app_matches
+        .value_of("end")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    let retries = value_of::<usize>(app_matches, "retries").unwrap();
+
+    let progress_path = match app_matches.value_of("progress") {
+        Some(path) => path.to_owned(),
+        None => format!("metadata_uris_{}_{}.jsonl", start, end),
+    };
+    let context = json!({ "progress": progress_path });
+    let mut entries = match fs::read_to_string(&progress_path) {
+        Ok(contents) if !contents.is_empty() => {
+            if !app_matches.is_present("resume") {
+                exit_with_error(
+                    CliError::InvalidInput(format!(
+                        "{} holds an earlier pull, pass --resume to continue it",
+                        progress_path
+                    )),
+                    context,
+                );
+            }
+            let entries = read_uri_progress(&contents).unwrap_or_else(|err| {
+                exit_with_error(
+                    CliError::InvalidInput(format!("{}: {}", progress_path, err)),
+                    context.clone(),
+                )
+            });
+            if !contents.ends_with('\n') {
+                // Drops the line cut off by the interruption before appending after it.
+                let lines: String = entries.iter().map(|entry| format!("{}\n", entry)).collect();
+                if let Err(err) = fs::write(&progress_path, lines) {
+                    exit_with_error(err.into(), context);
+                }
+            }
+            entries
+        }
+        Ok(_) => vec![],
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(err) => exit_with_error(err.into(), context),
+    };
+    let next = entries
+        .last()
+        .map_or(start, |entry| entry["index"].as_u64().unwrap() as usize + 1);
+    if next < start || next > end {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "{} ends at item {}, outside {}..{}",
+                progress_path,
+                next - 1,
+                start,
+                end
+            )),
+            context,
+        );
+    }
+    if next > start {
+        println!("Resuming at {} with {} uris pulled", next, entries.len());
+    }
+    let mut progress = match OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&progress_path)
+    {
+        Ok(progress) => progress,
+        Err(err) => exit_with_error(err.into(), context),
+    };
+
+    let http = http_client();
+    for (i, key) in keys.iter().enumerate().take(end).skip(next) {
+        println!("Doing {} out of {}", i, len);
+        let metadata_account = client.get_account(&Pubkey::from_str(key).unwrap()).unwrap();
+        let metadata = decode_metadata(&metadata_account.data).unwrap();
+        let uri = strip_padding(&metadata.data.uri);
+        let body = fetch_uri_body(app_matches, &http, uri, retries);
+        let entry = json!({ "index": i, "uri": uri, "body": body, "metadata": key });
+        if let Err(err) = progress.write_all(format!("{}\n", entry).as_bytes()) {
+            exit_with_error(err.into(), context);
+        }
+        entries.push(entry);
+    }
+
+    let uris: Vec<Value> = entries
+        .iter()
+        .map(|entry| json!([entry["uri"], entry["body"], entry["metadata"]]))
+        .collect();
+    let mut file = File::create(format!("metadata_uris_{}_{}.json", start, end)).unwrap();
+    file.write_all(serde_json::to_string(&uris).unwrap().as_bytes())
+        .unwrap();
+}
+
+/// Reads the `(wallet, count)` grants of an airdrop, from a `wallet,count` CSV file when `path`
+/// ends in `.csv` and from a JSON array of pairs otherwise. Exits on the first invalid wallet.
+fn read_airdrop_grants(path: &str) -> Vec<(String, u32)> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => exit_with_error(err.into(), json!({ "file": path })),
+    };
+    let grants: Vec<(String, u32)> = if path.ends_with(".csv") {
+        let mut grants = vec![];
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (line_number == 0 && line.starts_with("wallet")) {
+                continue;
+            }
+            let (wallet, count) = match line.split_once(',') {
+                Some((wallet, count)) => (wallet.trim(), count.trim()),
+                None => ("", ""),
+            };
+            match count.parse::<u32>() {
+                Ok(count) => grants.push((wallet.to_owned(), count)),
+                Err(_) => exit_with_error(
+                    CliError::InvalidInput(format!(
+                        "Line {} of {} is not a wallet,count pair: {:?}",
+                        line_number + 1,
+                        path,
+                        line
+                    )),
+                    json!({ "file": path, "line": line_number + 1 }),
+                ),
+            }
+        }
+        grants
+    } else {
+        match serde_json::from_str::<Vec<AirdropGrant>>(&contents) {
+            Ok(grants) => grants
+                .into_iter()
+                .map(|AirdropGrant(wallet, count)| (wallet.0, count))
+                .collect(),
+            Err(err) => exit_with_error(CliError::from(err), json!({ "file": path })),
+        }
+    };
+
+    for (index, (wallet, _)) in grants.iter().enumerate() {
+        if Pubkey::from_str(wallet).is_err() {
+            exit_with_error(
+                CliError::InvalidInput(format!(
+                    "Entry {} has an invalid wallet {:?}",
+                    index, wallet
+                )),
+                json!({ "file": path, "index": index }),
+            );
+        }
+    }
+    grants
+}
+
+/// Writes `grants` the way `read_airdrop_grants` reads them: wallet,count lines when `path` ends
+/// in .csv, a JSON array of pairs otherwise.
+fn write_airdrop_grants(path: &str, grants: &[(String, u32)]) -> std::io::Result<()> {
+    let contents = if path.ends_with(".csv") {
+        let mut contents = "wallet,count\n".to_owned();
+        for (wallet, count) in grants {
+            contents.push_str(&format!("{},{}\n", wallet, count));
+        }
+        contents
+    } else {
+        serde_json::to_string(grants).unwrap()
+    };
+    fs::write(path, contents)
+}
+
+/// Writes the input entries a bulk command gave up on to `--failures`, when given, in the schema
+/// of its input file so that passing the file back as the input re-runs just those.
+fn write_failures(app_matches: &ArgMatches, summary: &mut Summary, failures: &[Value]) {
+    if let Some(path) = app_matches.value_of("failures") {
+        if let Err(err) = fs::write(path, serde_json::to_string(failures).unwrap()) {
+            exit_with_error(err.into(), json!({ "failures": path }));
+        }
+        summary.output(path);
+    }
+}
+
+/// Picks the edition number of the next print: the lowest number past both the master
+/// edition's `supply` and `next`, the lowest number not yet sent by this run, that `is_taken`
+/// does not report as printed already.
+pub fn next_edition_number(supply: u64, next: u64, is_taken: impl Fn(u64) -> bool) -> u64 {
+    let mut edition = next.max(supply + 1);
+    while is_taken(edition) {
+        edition += 1;
+    }
+    edition
+}
 
-    let metadata_account = client.get_account(&metadata_key).unwrap();
-    let metadata: Metadata = try_from_slice_unchecked(&metadata_account.data).unwrap();
+fn master_edition_supply(client: &FailoverClient, master_edition_key: &Pubkey) -> u64 {
+    let account = client.get_account(master_edition_key).unwrap();
+    let master_edition: MasterEditionV2 = try_from_slice_unchecked(&account.data).unwrap();
+    master_edition.supply
+}
 
-    let new_data = Data {
-        name: name.unwrap_or(metadata.data.name),
-        symbol: metadata.data.symbol,
-        uri: uri.unwrap_or(metadata.data.uri),
-        seller_fee_basis_points: 0,
-        creators: metadata.data.creators,
+/// Checks that a master edition at `supply` with `max_supply` has room for `planned` more prints,
+/// which take the numbers right after the supply.
+pub fn check_print_room(supply: u64, max_supply: Option<u64>, planned: u64) -> Result<(), String> {
+    match max_supply {
+        Some(max_supply) if supply.saturating_add(planned) > max_supply => Err(format!(
+            "Drop of {} prints exceeds max supply {}, only {} left after supply {}",
+            planned,
+            max_supply,
+            max_supply.saturating_sub(supply),
+            supply
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Aborts the airdrop before the first send unless `master_edition_key` is a `MasterEditionV2`
+/// that can print `planned` more editions.
+fn verify_master_edition_before_airdrop(
+    client: &FailoverClient,
+    master_mint: &Pubkey,
+    master_edition_key: &Pubkey,
+    planned: u64,
+) {
+    let context = json!({
+        "mint": master_mint.to_string(),
+        "master_edition": master_edition_key.to_string(),
+        "planned": planned,
+    });
+    let account = match client
+        .get_account_with_commitment(master_edition_key, client.commitment())
+        .unwrap()
+        .value
+    {
+        Some(account) => account,
+        None => exit_with_error(
+            CliError::AccountNotFound(format!("{} has no master edition", master_mint)),
+            context,
+        ),
     };
+    if account.data[0] == Key::MasterEditionV1 as u8 {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "Master edition {} is V1, convert it to V2 before airdropping",
+                master_edition_key
+            )),
+            context,
+        );
+    }
+    if account.data[0] != Key::MasterEditionV2 as u8 {
+        exit_with_error(
+            CliError::InvalidInput(format!("{} is not a master edition mint", master_mint)),
+            context,
+        );
+    }
+    let master_edition: MasterEditionV2 = try_from_slice_unchecked(&account.data).unwrap();
+    if let Err(message) =
+        check_print_room(master_edition.supply, master_edition.max_supply, planned)
+    {
+        exit_with_error(CliError::InvalidInput(message), context);
+    }
+}
 
-    let instructions = [update_metadata_accounts(
-        program_key,
-        metadata_key,
-        update_authority.pubkey(),
-        new_update_authority,
-        Some(new_data),
-        None,
-    )];
+/// The edition the next print `address` claims through `res_list` gets, and the spots it has
+/// left, read the way the program reads the list: the supply snapshot plus the spots reserved
+/// before `address` since the last anchor plus its remaining spots, handed out from the last.
+/// `None` when the list has no snapshot yet or `address` has no spot left on it.
+pub fn reserved_edition(res_list: &dyn ReservationList, address: &Pubkey) -> Option<(u64, u64)> {
+    let snapshot = res_list.supply_snapshot()?;
+    let mut offset: u64 = 0;
+    for reservation in res_list.reservations() {
+        if reservation.address == *address {
+            return match reservation.spots_remaining {
+                0 => None,
+                spots => Some((snapshot + offset + spots, spots)),
+            };
+        }
+        if reservation.address == solana_program::system_program::id() {
+            // An anchor: the spots before it are not all listed, so counting restarts here.
+            offset = reservation.total_spots;
+        } else {
+            offset += reservation.total_spots;
+        }
+    }
+    None
+}
 
-    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
-    let recent_blockhash = client.get_recent_blockhash().unwrap().0;
-    let signers = vec![&update_authority];
+/// A V1 master edition printing through a reservation list: the list and the payer's account of
+/// printing tokens, one of which each print burns.
+struct ReservedPrinting {
+    list: Pubkey,
+    printing_mint: Pubkey,
+    printing_token_account: Pubkey,
+}
 
-    transaction.sign(&signers, recent_blockhash);
-    client.send_and_confirm_transaction(&transaction).unwrap();
-    let metadata_account = client.get_account(&metadata_key).unwrap();
-    let metadata: Metadata = try_from_slice_unchecked(&metadata_account.data).unwrap();
-    (metadata, metadata_key)
+/// Checks that `list` is a reservation list of the V1 master edition at `master_edition_key`
+/// holding `planned` spots for `payer`, and finds the payer's printing tokens to burn for them.
+fn reserved_printing(
+    client: &FailoverClient,
+    payer: &Pubkey,
+    master_edition_key: &Pubkey,
+    list: &Pubkey,
+    planned: u64,
+) -> ReservedPrinting {
+    let context = json!({
+        "master_edition": master_edition_key.to_string(),
+        "reservation_list": list.to_string(),
+        "planned": planned,
+    });
+    let fail = |message: String| -> ! {
+        exit_with_error(CliError::InvalidInput(message), context.clone())
+    };
+    let account = client
+        .get_account(master_edition_key)
+        .unwrap_or_else(|err| exit_with_error(err.into(), context.clone()));
+    if account.data[0] != Key::MasterEditionV1 as u8 {
+        fail(format!(
+            "Master edition {} is not V1, only V1 master editions print through reservation lists",
+            master_edition_key
+        ));
+    }
+    let master_edition: MasterEditionV1 = try_from_slice_unchecked(&account.data).unwrap();
+    let account = client
+        .get_account(list)
+        .unwrap_or_else(|err| exit_with_error(err.into(), context.clone()));
+    let res_list = decode_reservation_list(list, account)
+        .unwrap_or_else(|err| fail(format!("{} is not a reservation list: {}", list, err)));
+    if res_list.master_edition() != *master_edition_key {
+        fail(format!(
+            "Reservation list {} belongs to master edition {}, not {}",
+            list,
+            res_list.master_edition(),
+            master_edition_key
+        ));
+    }
+    match reserved_edition(res_list.as_ref(), payer) {
+        Some((_, spots)) if spots >= planned => {}
+        Some((_, spots)) => fail(format!(
+            "{} has {} reserved spots left on {}, the drop needs {}",
+            payer, spots, list, planned
+        )),
+        None => fail(format!(
+            "{} has no reserved spot left on {}, or the list has no supply snapshot yet",
+            payer, list
+        )),
+    }
+    let printing_token_account = client
+        .get_token_accounts_by_owner(
+            payer,
+            TokenAccountsFilter::Mint(master_edition.printing_mint),
+        )
+        .unwrap_or_else(|err| exit_with_error(err.into(), context.clone()))
+        .iter()
+        .map(|account| Pubkey::from_str(&account.pubkey).unwrap())
+        .find(|key| {
+            client
+                .get_token_account_balance(key)
+                .ok()
+                .and_then(|balance| balance.amount.parse::<u64>().ok())
+                .map_or(false, |amount| amount >= planned)
+        })
+        .unwrap_or_else(|| {
+            fail(format!(
+                "{} holds no account with {} printing tokens of {}",
+                payer, planned, master_edition.printing_mint
+            ))
+        });
+    ReservedPrinting {
+        list: *list,
+        printing_mint: master_edition.printing_mint,
+        printing_token_account,
+    }
 }
 
-fn pull_llama_arweave_uris(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
-    let mut file = File::open("all_metadata.json").unwrap();
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    let keys: Vec<String> = serde_json::from_str(&contents).unwrap();
-    let mut uris: Vec<(String, Option<String>, String)> = vec![];
-    let mut i = 0;
-    let len = keys.len();
-    let start = app_matches
-        .value_of("start")
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    let end = app_matches
-        .value_of("end")
+/// Whether the edition marker of `master_mint` records `edition` as printed.
+fn edition_taken(
+    client: &FailoverClient,
+    program: &MetadataProgram,
+    master_mint: &Pubkey,
+    edition: u64,
+) -> bool {
+    let marker_key = program.edition_marker_pda(master_mint, edition);
+    match client
+        .get_account_with_commitment(&marker_key, client.commitment())
         .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    for key in keys {
-        if i >= start && i < end {
-            println!("Doing {} out of {}", i, len);
-            let metadata_account = client
-                .get_account(&Pubkey::from_str(&key).unwrap())
-                .unwrap();
-            let metadata: Metadata = try_from_slice_unchecked(&metadata_account.data).unwrap();
-            match reqwest::blocking::get(&metadata.data.uri) {
-                Ok(mut res) => {
-                    let mut body = String::new();
-                    let mut uri_body = None;
-                    match res.read_to_string(&mut body) {
-                        Ok(_) => uri_body = Some(body),
-                        Err(_) => {
-                            println!("Arweave URL {} does not exist", &metadata.data.uri)
-                        }
-                    };
-                    uris.push((metadata.data.uri.replace("\u{0000}", ""), uri_body, key));
-                }
-                Err(_) => uris.push((metadata.data.uri.replace("\u{0000}", ""), None, key)),
-            }
+        .value
+    {
+        Some(account) => {
+            let marker: EditionMarker = try_from_slice_unchecked(&account.data).unwrap();
+            marker.edition_taken(edition).unwrap()
         }
-        i += 1;
+        None => false,
     }
-
-    let mut file = File::create(
-        "metadata_uris_".to_owned() + &start.to_string() + "_" + &end.to_string() + ".json",
-    )
-    .unwrap();
-
-    file.write_all(serde_json::to_string(&uris).unwrap().as_bytes())
-        .unwrap();
 }
 
-fn airdrop(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
-    let update_authority = read_keypair_file(
-        app_matches
-            .value_of("update_authority")
-            .unwrap_or_else(|| app_matches.value_of("keypair").unwrap()),
-    )
-    .unwrap();
+fn airdrop(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    let update_authority = signer_of_or_keypair(app_matches, "update_authority", wallet_manager);
 
-    let metadata_program = spl_token_metadata::id();
+    let metadata_program = program.id;
 
     let participation_trophy =
         Pubkey::from_str("Gt2VHnTpWhczM2EvYQSVAf3BHCVNyR1q5yUGibzb6sEX").unwrap();
 
-    let metadata_seeds = &[
-        PREFIX.as_bytes(),
-        &metadata_program.as_ref(),
-        &participation_trophy.as_ref(),
-    ];
-    let (master_metadata_key, _) = Pubkey::find_program_address(metadata_seeds, &metadata_program);
+    let master_metadata_key = program.metadata_pda(&participation_trophy);
     let master_metadata_account = client.get_account(&master_metadata_key).unwrap();
-    let master_metadata: Metadata =
-        try_from_slice_unchecked(&master_metadata_account.data).unwrap();
-
-    let master_edition_seeds = &[
-        PREFIX.as_bytes(),
-        &metadata_program.as_ref(),
-        &master_metadata.mint.as_ref(),
-        EDITION.as_bytes(),
-    ];
-    let (master_edition_key, _) =
-        Pubkey::find_program_address(master_edition_seeds, &metadata_program);
-    let master_edition_account = client.get_account(&master_edition_key).unwrap();
-    let master_edition: MasterEditionV2 =
-        try_from_slice_unchecked(&master_edition_account.data).unwrap();
-    let edition_offset = master_edition.supply;
-    let existing_token_account = Pubkey::from_str(
-        &client
-            .get_token_accounts_by_owner(
-                &payer.pubkey(),
-                TokenAccountsFilter::Mint(participation_trophy),
-            )
-            .unwrap()
-            .iter()
-            .find(|x| {
-                client
-                    .get_token_account_balance(&Pubkey::from_str(&x.pubkey).unwrap())
-                    .unwrap()
-                    .amount
-                    != "0"
-            })
-            .unwrap()
-            .pubkey,
-    )
-    .unwrap();
+    let master_metadata = decode_metadata(&master_metadata_account.data).unwrap();
 
-    let mut file = File::open(app_matches.value_of("file").unwrap()).unwrap();
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    let keys: Vec<(String, u8)> = serde_json::from_str(&contents).unwrap();
+    let master_edition_key = program.edition_pda(&master_metadata.mint);
+    // Lowest edition number this run has not sent yet, the supply only catches up on confirmation.
+    let mut next_edition = 1;
+    let keys = read_airdrop_grants(app_matches.value_of("file").unwrap());
+    let planned = keys.iter().map(|key| key.1 as u64).sum();
+    let reserved = pubkey_of(app_matches, "reservation_list").map(|list| {
+        reserved_printing(
+            &client,
+            &payer.pubkey(),
+            &master_edition_key,
+            &list,
+            planned,
+        )
+    });
+    if reserved.is_none() {
+        verify_master_edition_before_airdrop(
+            &client,
+            &master_metadata.mint,
+            &master_edition_key,
+            planned,
+        );
+    }
+    let existing_token_account = reserved.is_none().then(|| {
+        Pubkey::from_str(
+            &client
+                .get_token_accounts_by_owner(
+                    &payer.pubkey(),
+                    TokenAccountsFilter::Mint(participation_trophy),
+                )
+                .unwrap()
+                .iter()
+                .find(|x| {
+                    client
+                        .get_token_account_balance(&Pubkey::from_str(&x.pubkey).unwrap())
+                        .unwrap()
+                        .amount
+                        != "0"
+                })
+                .unwrap()
+                .pubkey,
+        )
+        .unwrap()
+    });
 
     /* let mut file = File::open(app_matches.value_of("cache").unwrap()).unwrap();
     let mut contents = String::new();
     file.read_to_string(&mut contents).unwrap();
     let cache_keys: Vec<(String, u8)> = serde_json::from_str(&contents).unwrap();*/
     let token_key = spl_token::id();
+    let use_ata = app_matches.is_present("use_ata");
     let len = keys.len();
+    let mut timings = Timings::start(app_matches, &client);
+    // Each print signs for its new mint, and without --use-ata for its token account.
+    timings.estimate(
+        keys.iter().map(|key| key.1 as usize).sum(),
+        unique_signers(vec![payer.as_ref(), update_authority.as_ref()]).len()
+            + 1
+            + !use_ata as usize,
+    );
+    let mut summary = Summary::start();
+    let mut failures: Vec<(String, u32)> = vec![];
+    let retries = value_of::<usize>(app_matches, "retries").unwrap();
+    // Retries of the grant being sent, reset for each grant.
+    let mut attempt = 0;
     let mut i = 0;
     while i < len {
         println!("At {} out of {}", i, len);
@@ -673,34 +2659,54 @@ fn airdrop(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
         /*if j < cache_keys.len() {
             j = cache_keys[i].1 as usize;
         }*/
-        while j < key.1.into() {
-            let mut signers = vec![&update_authority];
+        while j < key.1 as usize {
+            let mut signers = vec![update_authority.as_ref()];
             let mut instructions = vec![];
 
             let new_mint_key = Keypair::new();
             let added_token_account = Keypair::new();
             let new_mint_pub = new_mint_key.pubkey();
 
-            println!("Granting nft {} to key {}", j, key.0);
+            let edition = match &reserved {
+                // The program picks the number from the list, this is the one it will pick.
+                Some(reserved) => {
+                    let account = client.get_account(&reserved.list).unwrap();
+                    let res_list = decode_reservation_list(&reserved.list, account).unwrap();
+                    match reserved_edition(res_list.as_ref(), &payer.pubkey()) {
+                        Some((edition, _)) => edition,
+                        None => exit_with_error(
+                            CliError::InvalidInput(format!(
+                                "{} has no reserved spot left on {}",
+                                payer.pubkey(),
+                                reserved.list
+                            )),
+                            json!({ "index": i, "wallet": key.0, "grant": j }),
+                        ),
+                    }
+                }
+                None => {
+                    let supply = master_edition_supply(&client, &master_edition_key);
+                    next_edition_number(supply, next_edition, |edition| {
+                        edition_taken(&client, program, &master_metadata.mint, edition)
+                    })
+                }
+            };
+            println!("Granting nft {} to key {} as edition {}", j, key.0, edition);
+            if !confirm_step(
+                app_matches,
+                &format!("Print edition {} for {}", edition, key.0),
+            ) {
+                summary.skipped();
+                j += 1;
+                attempt = 0;
+                continue;
+            }
+
+            let new_metadata_key = program.metadata_pda(&new_mint_pub);
 
-            let metadata_seeds = &[
-                PREFIX.as_bytes(),
-                &metadata_program.as_ref(),
-                &new_mint_pub.as_ref(),
-            ];
-            let (new_metadata_key, _) =
-                Pubkey::find_program_address(metadata_seeds, &metadata_program);
-
-            let edition_seeds = &[
-                PREFIX.as_bytes(),
-                &metadata_program.as_ref(),
-                &new_mint_pub.as_ref(),
-                EDITION.as_bytes(),
-            ];
-            let (edition_key, _) = Pubkey::find_program_address(edition_seeds, &metadata_program);
+            let edition_key = program.edition_pda(&new_mint_pub);
 
             signers.push(&new_mint_key);
-            signers.push(&added_token_account);
             instructions.push(create_account(
                 &payer.pubkey(),
                 &new_mint_key.pubkey(),
@@ -720,30 +2726,42 @@ fn airdrop(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
                 )
                 .unwrap(),
             );
-            instructions.push(create_account(
-                &payer.pubkey(),
-                &added_token_account.pubkey(),
-                client
-                    .get_minimum_balance_for_rent_exemption(Account::LEN)
-                    .unwrap(),
-                Account::LEN as u64,
-                &token_key,
-            ));
-
-            instructions.push(
-                initialize_account(
-                    &token_key,
+            let recipient = Pubkey::from_str(&key.0).unwrap();
+            let recipient_token_key = if use_ata {
+                // The mint is new, so its associated token account never exists yet.
+                instructions.push(create_associated_token_account(
+                    &payer.pubkey(),
+                    &recipient,
+                    &new_mint_pub,
+                ));
+                get_associated_token_address(&recipient, &new_mint_pub)
+            } else {
+                signers.push(&added_token_account);
+                instructions.push(create_account(
+                    &payer.pubkey(),
                     &added_token_account.pubkey(),
-                    &new_mint_key.pubkey(),
-                    &Pubkey::from_str(&key.0).unwrap(),
-                )
-                .unwrap(),
-            );
+                    client
+                        .get_minimum_balance_for_rent_exemption(Account::LEN)
+                        .unwrap(),
+                    Account::LEN as u64,
+                    &token_key,
+                ));
+                instructions.push(
+                    initialize_account(
+                        &token_key,
+                        &added_token_account.pubkey(),
+                        &new_mint_pub,
+                        &recipient,
+                    )
+                    .unwrap(),
+                );
+                added_token_account.pubkey()
+            };
             instructions.push(
                 mint_to(
                     &token_key,
                     &new_mint_key.pubkey(),
-                    &added_token_account.pubkey(),
+                    &recipient_token_key,
                     &payer.pubkey(),
                     &[&payer.pubkey()],
                     1,
@@ -751,39 +2769,296 @@ fn airdrop(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
                 .unwrap(),
             );
 
-            instructions.push(mint_new_edition_from_master_edition_via_token(
-                metadata_program,
-                new_metadata_key,
-                edition_key,
-                master_edition_key,
-                new_mint_key.pubkey(),
-                payer.pubkey(),
-                payer.pubkey(),
-                payer.pubkey(),
-                existing_token_account,
-                Pubkey::from_str(&key.0).unwrap(),
-                master_metadata_key,
-                master_metadata.mint,
-                edition_offset + i as u64 + j as u64 + 1,
-            ));
+            instructions.push(match (&reserved, existing_token_account) {
+                (Some(reserved), _) => {
+                    deprecated_mint_new_edition_from_master_edition_via_printing_token(
+                        metadata_program,
+                        new_metadata_key,
+                        edition_key,
+                        master_edition_key,
+                        new_mint_key.pubkey(),
+                        payer.pubkey(),
+                        reserved.printing_mint,
+                        reserved.printing_token_account,
+                        payer.pubkey(),
+                        payer.pubkey(),
+                        update_authority.pubkey(),
+                        master_metadata_key,
+                        Some(reserved.list),
+                    )
+                }
+                (None, Some(existing_token_account)) => {
+                    mint_new_edition_from_master_edition_via_token(
+                        metadata_program,
+                        new_metadata_key,
+                        edition_key,
+                        master_edition_key,
+                        new_mint_key.pubkey(),
+                        payer.pubkey(),
+                        payer.pubkey(),
+                        payer.pubkey(),
+                        existing_token_account,
+                        recipient,
+                        master_metadata_key,
+                        master_metadata.mint,
+                        edition,
+                    )
+                }
+                (None, None) => unreachable!(),
+            });
 
+            if let Some(instruction) = priority_fee_instruction(app_matches, &client, &instructions)
+            {
+                instructions.insert(0, instruction);
+            }
             let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
             let recent_blockhash = client.get_recent_blockhash().unwrap().0;
 
             transaction.sign(&signers, recent_blockhash);
-            match client.send_transaction(&transaction) {
-                Ok(_) => j += 1,
-                Err(err) => {
-                    println!("Transaction failed. No retry! {:?}", err);
-                    j += 1
+            let result = timings.time(i, &key.0, || {
+                send_transaction(app_matches, &client, &transaction)
+            });
+            let err = match result {
+                Ok(_) => {
+                    next_edition = edition + 1;
+                    summary.succeeded();
+                    j += 1;
+                    attempt = 0;
+                    continue;
+                }
+                Err(err) => err,
+            };
+            let context = json!({ "index": i, "wallet": key.0, "grant": j, "edition": edition });
+            match classify_send_error(&err, &instructions) {
+                SendFailure::Retryable => {
+                    // Each retry prints with a new mint, so one that reached the leader before the
+                    // error must be known not to land before another is sent.
+                    let signature = transaction.signatures[0];
+                    match landed_after_error(
+                        app_matches,
+                        &client,
+                        payer.as_ref(),
+                        None,
+                        &transaction,
+                        &err,
+                    ) {
+                        Ok(true) => {
+                            println!("Transaction {} landed despite: {}", signature, err);
+                            next_edition = edition + 1;
+                            summary.succeeded();
+                            j += 1;
+                            attempt = 0;
+                        }
+                        Ok(false) if attempt < retries => {
+                            record_retry(RetryReason::of(&err));
+                            attempt += 1;
+                            report_item_error(
+                                &format!("Transaction failed. Retry {} of {}.", attempt, retries),
+                                &err.into(),
+                                context,
+                            );
+                            backoff(app_matches, Duration::from_millis(1000));
+                        }
+                        Ok(false) => {
+                            report_item_error(
+                                "Transaction failed. Out of retries!",
+                                &err.into(),
+                                context,
+                            );
+                            summary.failed();
+                            match failures.last_mut() {
+                                Some((wallet, count)) if *wallet == key.0 => *count += 1,
+                                _ => failures.push((key.0.clone(), 1)),
+                            }
+                            j += 1;
+                            attempt = 0;
+                        }
+                        Err(lookup) => {
+                            // Left out of --failures, as granting it again could print twice.
+                            report_item_error(
+                                &format!(
+                                    "Cannot tell whether transaction {} landed, check it before \
+                                     granting again.",
+                                    signature
+                                ),
+                                &lookup,
+                                context,
+                            );
+                            summary.failed();
+                            j += 1;
+                            attempt = 0;
+                        }
+                    }
+                }
+                SendFailure::Program {
+                    index,
+                    error: InstructionError::Custom(code),
+                } if index == instructions.len() - 1
+                    && code == MetadataError::AlreadyInitialized as u32 =>
+                {
+                    // Taken by a print that has not confirmed yet, so its marker did not show it.
+                    record_retry(RetryReason::of(&err));
+                    report_item_error(
+                        &format!("Edition {} is taken. Retry.", edition),
+                        &err.into(),
+                        context,
+                    );
+                    next_edition = edition + 1;
                 }
+                SendFailure::Program { .. } => {
+                    report_item_error("Transaction failed. No retry!", &err.into(), context);
+                    summary.failed();
+                    match failures.last_mut() {
+                        Some((wallet, count)) if *wallet == key.0 => *count += 1,
+                        _ => failures.push((key.0.clone(), 1)),
+                    }
+                    j += 1;
+                    attempt = 0;
+                }
+                SendFailure::Fatal => exit_with_error(err.into(), context),
             }
         }
         i += 1
     }
+    if let Some(path) = app_matches.value_of("failures") {
+        if let Err(err) = write_airdrop_grants(path, &failures) {
+            exit_with_error(err.into(), json!({ "failures": path }));
+        }
+        summary.output(path);
+    }
+    timings.finish(app_matches, &client);
+    summary.print();
+}
+
+/// Returns the slot of the newest transaction touching `key`, costing one
+/// `getSignaturesForAddress` request. `None` when no transaction is on record.
+fn last_modified_slot(client: &FailoverClient, key: &Pubkey) -> Option<u64> {
+    let config = GetConfirmedSignaturesForAddress2Config {
+        before: None,
+        until: None,
+        limit: Some(1),
+        commitment: None,
+    };
+    client
+        .get_signatures_for_address_with_config(key, config)
+        .unwrap()
+        .first()
+        .map(|status| status.slot)
+}
+
+/// Progress of a `find_all_llamas` scan over `start..end`: the index to continue from and the bad
+/// metadata found so far.
+#[derive(Debug, PartialEq)]
+pub struct ScanCheckpoint {
+    pub start: usize,
+    pub end: usize,
+    pub next: usize,
+    pub bad_metadata: Vec<(Value, String)>,
+}
+
+impl ScanCheckpoint {
+    /// The line recording that the scan continues from `next`, with the bad metadata found by
+    /// the item just scanned, if any.
+    pub fn record(next: usize, bad_metadata: Option<&(Value, String)>) -> String {
+        match bad_metadata {
+            Some(bad_metadata) => format!(
+                "{}\n",
+                json!({ "next": next, "bad_metadata": bad_metadata })
+            ),
+            None => format!("{}\n", json!({ "next": next })),
+        }
+    }
+
+    /// The checkpoint as JSON lines: its range, then a line per bad metadata and the position.
+    pub fn to_lines(&self) -> String {
+        let mut lines = format!("{}\n", json!({ "start": self.start, "end": self.end }));
+        for bad_metadata in &self.bad_metadata {
+            lines.push_str(&format!("{}\n", json!({ "bad_metadata": bad_metadata })));
+        }
+        lines.push_str(&Self::record(self.next, None));
+        lines
+    }
+
+    /// Reads a checkpoint from its lines, the last record of a position winning. A run
+    /// interrupted while writing can leave the last line cut off, which is dropped so its item
+    /// is scanned again.
+    pub fn from_lines(contents: &str) -> Option<Self> {
+        let lines: Vec<&str> = contents.lines().collect();
+        let header: Value = serde_json::from_str(lines.first()?).ok()?;
+        let index = |value: &Value, name: &str| value[name].as_u64().map(|index| index as usize);
+        let start = index(&header, "start")?;
+        let mut checkpoint = ScanCheckpoint {
+            start,
+            end: index(&header, "end")?,
+            next: start,
+            bad_metadata: vec![],
+        };
+        for (number, line) in lines.iter().enumerate().skip(1) {
+            let record: Value = match serde_json::from_str(line) {
+                Ok(record) => record,
+                Err(_) if number + 1 == lines.len() && !contents.ends_with('\n') => break,
+                Err(_) => return None,
+            };
+            if !record["bad_metadata"].is_null() {
+                checkpoint
+                    .bad_metadata
+                    .push(serde_json::from_value(record["bad_metadata"].clone()).ok()?);
+            }
+            if !record["next"].is_null() {
+                checkpoint.next = index(&record, "next")?;
+            }
+        }
+        Some(checkpoint)
+    }
+}
+
+/// Loads the checkpoint at `path` to continue a scan over `start..end`, or starts a new one
+/// when there is none or `reset` is set. A checkpoint left by a scan over another range is not
+/// mixed in.
+fn load_scan_checkpoint(path: &str, start: usize, end: usize, reset: bool) -> ScanCheckpoint {
+    let fresh = ScanCheckpoint {
+        start,
+        end,
+        next: start,
+        bad_metadata: vec![],
+    };
+    if reset {
+        return fresh;
+    }
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return fresh,
+        Err(err) => exit_with_error(err.into(), json!({ "checkpoint": path })),
+    };
+    let checkpoint = ScanCheckpoint::from_lines(&contents).unwrap_or_else(|| {
+        exit_with_error(
+            CliError::InvalidInput(format!("{} is not a scan checkpoint", path)),
+            json!({ "checkpoint": path }),
+        )
+    });
+    if (checkpoint.start, checkpoint.end) != (start, end) {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "{} is for a scan of {}..{}, pass --reset to scan {}..{} instead",
+                path, checkpoint.start, checkpoint.end, start, end
+            )),
+            json!({ "checkpoint": path }),
+        );
+    }
+    println!(
+        "Resuming at {} with {} bad metadata found so far",
+        checkpoint.next,
+        checkpoint.bad_metadata.len()
+    );
+    checkpoint
 }
 
-fn find_all_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
+fn find_all_llamas(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
     let llama_key = Pubkey::from_str("LLAmArGWBCspEarLTCBpKLdXxYS4EUuiQZQmy1RD8oc").unwrap();
     let start = app_matches
         .value_of("start")
@@ -795,30 +3070,74 @@ fn find_all_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient)
         .unwrap()
         .parse::<usize>()
         .unwrap();
+    let since_slot = value_of::<u64>(app_matches, "since_slot");
+    let before_slot = value_of::<u64>(app_matches, "before_slot");
     let token_accounts = client
         .get_token_accounts_by_owner(&llama_key, TokenAccountsFilter::ProgramId(spl_token::id()))
         .unwrap();
 
-    let mut bad_metadata: Vec<(Value, String)> = vec![];
-    let metadata_program = spl_token_metadata::id();
+    let checkpoint_path = app_matches.value_of("checkpoint").unwrap();
+    let mut checkpoint =
+        load_scan_checkpoint(checkpoint_path, start, end, app_matches.is_present("reset"));
+    // Rewritten whole once, through a file renamed over it so a crash leaves the old one intact,
+    // then appended to a line per item.
+    let context = json!({ "checkpoint": checkpoint_path });
+    let temp_path = format!("{}.tmp", checkpoint_path);
+    if let Err(err) = fs::write(&temp_path, checkpoint.to_lines())
+        .and_then(|_| fs::rename(&temp_path, checkpoint_path))
+    {
+        exit_with_error(err.into(), context);
+    }
+    let mut progress = match OpenOptions::new().append(true).open(checkpoint_path) {
+        Ok(progress) => progress,
+        Err(err) => exit_with_error(err.into(), context),
+    };
+    let mut save_checkpoint = |next: usize, bad_metadata: Option<&(Value, String)>| {
+        let record = ScanCheckpoint::record(next, bad_metadata);
+        if let Err(err) = progress.write_all(record.as_bytes()) {
+            exit_with_error(err.into(), json!({ "checkpoint": checkpoint_path }));
+        }
+    };
     let mut i = 0;
     let len = token_accounts.len();
+    let mut summary = Summary::start();
     for account in token_accounts {
-        if i >= start && i < end {
+        if i >= checkpoint.next && i < end {
             println!("At {} out of {}", i, len);
             let actual_data = client
                 .get_account(&Pubkey::from_str(&account.pubkey).unwrap())
                 .unwrap();
             let token_account = Account::unpack_unchecked(&actual_data.data).unwrap();
-            let metadata_seeds = &[
-                PREFIX.as_bytes(),
-                &metadata_program.as_ref(),
-                token_account.mint.as_ref(),
-            ];
-            let (metadata_key, _) = Pubkey::find_program_address(metadata_seeds, &metadata_program);
+            let metadata_key = program.metadata_pda(&token_account.mint);
+            if since_slot.is_some() || before_slot.is_some() {
+                let slot = last_modified_slot(&client, &metadata_key);
+                let in_window = slot.map_or(false, |slot| {
+                    since_slot.map_or(true, |since| slot >= since)
+                        && before_slot.map_or(true, |before| slot < before)
+                });
+                if !in_window {
+                    println!("Skipping {} last modified at slot {:?}", metadata_key, slot);
+                    summary.skipped();
+                    i += 1;
+                    checkpoint.next = i;
+                    save_checkpoint(checkpoint.next, None);
+                    continue;
+                }
+            }
+            let mut bad_metadata = None;
             match client.get_account(&metadata_key) {
                 Ok(val) => {
-                    let md: Metadata = try_from_slice_unchecked(val.data()).unwrap();
+                    let md = match decode_metadata(val.data()) {
+                        Ok(md) => md,
+                        Err(err) => {
+                            report_undecodable(&metadata_key, &err);
+                            summary.skipped();
+                            i += 1;
+                            checkpoint.next = i;
+                            save_checkpoint(checkpoint.next, None);
+                            continue;
+                        }
+                    };
                     let mut res = reqwest::blocking::get(md.data.uri).unwrap();
                     let mut body = String::new();
                     res.read_to_string(&mut body).unwrap();
@@ -834,27 +3153,171 @@ fn find_all_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient)
                     }
                     if !found {
                         println!("Found a bad one! {}", metadata_key);
-                        bad_metadata.push((parsed, metadata_key.to_string()))
+                        bad_metadata = Some((parsed, metadata_key.to_string()));
                     }
+                    summary.succeeded();
                 }
                 Err(_) => {
-                    println!("token account {} does not have a metadata", account.pubkey)
+                    println!("token account {} does not have a metadata", account.pubkey);
+                    summary.failed();
                 }
             }
+            checkpoint.next = i + 1;
+            save_checkpoint(checkpoint.next, bad_metadata.as_ref());
+            checkpoint.bad_metadata.extend(bad_metadata);
         }
         i += 1;
     }
+    let bad_metadata = checkpoint.bad_metadata;
 
-    let mut file = File::create(
-        "bad_metadata_".to_owned() + &start.to_string() + "_" + &end.to_string() + ".json",
-    )
-    .unwrap();
+    let format = app_matches.value_of("output").unwrap();
+    let output = format!("bad_metadata_{}_{}.{}", start, end, format);
+    let mut file = File::create(&output).unwrap();
 
-    file.write_all(serde_json::to_string(&bad_metadata).unwrap().as_bytes())
-        .unwrap();
+    if format == "csv" {
+        let rows: Vec<Value> = bad_metadata
+            .iter()
+            .map(|(manifest, metadata)| {
+                json!({ "metadata": metadata, "name": manifest["name"], "image": manifest["image"] })
+            })
+            .collect();
+        write_csv(&mut file, &["metadata", "name", "image"], &rows).unwrap();
+    } else {
+        file.write_all(serde_json::to_string(&bad_metadata).unwrap().as_bytes())
+            .unwrap();
+    }
+    summary.output(&output);
+    summary.output(checkpoint_path);
+    summary.print();
+}
+
+/// Reads the `name` and `symbol` strings out of the arweave manifest stored at `uri`, checking
+/// that both are present and fit on chain.
+pub fn manifest_name_and_symbol(uri: &str, arweave: &Value) -> Result<(String, String), CliError> {
+    let field = |name: &str| match arweave[name].as_str() {
+        Some(value) => Ok(value.to_owned()),
+        None => Err(CliError::InvalidInput(format!(
+            "Manifest has no string {} field",
+            name
+        ))),
+    };
+    let (name, symbol) = (field("name")?, field("symbol")?);
+    check_field_lengths(&name, &symbol, uri)?;
+    Ok((name, symbol))
+}
+
+const ARWEAVE_GATEWAY: &str = "https://arweave.net/";
+
+/// The gateway URL of the Arweave transaction `id`, which is 43 base64url characters.
+pub fn arweave_manifest_url(id: &str) -> Result<String, CliError> {
+    let valid = id.len() == 43
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(format!("{}{}", ARWEAVE_GATEWAY, id))
+    } else {
+        Err(CliError::InvalidInput(format!(
+            "{:?} is not an Arweave transaction id",
+            id
+        )))
+    }
+}
+
+/// The llamas `create_new_llamas` works through, each with the `n` `--name-template` names it
+/// with. From `--manifests`, only the uri of each manifest is known and it is fetched when its
+/// llama is created, unless the entry embeds it. Otherwise the manifests embedded in
+/// llamas_new.json are paired with the wallets of prints.json.
+fn read_llama_entries(app_matches: &ArgMatches) -> Vec<ManifestEntry> {
+    let read = |path: &str| {
+        fs::read_to_string(path)
+            .unwrap_or_else(|err| exit_with_error(err.into(), json!({ "path": path })))
+    };
+    let name_offset = value_of::<u64>(app_matches, "name_offset").unwrap();
+    let path = match app_matches.value_of("manifests") {
+        Some(path) => path,
+        None => {
+            let keys: Vec<LlamaKey> = serde_json::from_str(&read("llamas_new.json")).unwrap();
+            let wallets: Vec<PubkeyString> = serde_json::from_str(&read("prints.json")).unwrap();
+            if wallets.len() != keys.len() {
+                exit_with_error(
+                    CliError::InvalidInput(format!(
+                        "prints.json holds {} wallets for the {} llamas of llamas_new.json",
+                        wallets.len(),
+                        keys.len()
+                    )),
+                    json!({ "wallets": wallets.len(), "llamas": keys.len() }),
+                );
+            }
+            if let Some(index) = wallets
+                .iter()
+                .position(|wallet| Pubkey::from_str(&wallet.0).is_err())
+            {
+                exit_with_error(
+                    CliError::InvalidInput(format!(
+                        "Entry {} of prints.json is not a pubkey",
+                        index
+                    )),
+                    json!({ "path": "prints.json", "index": index }),
+                );
+            }
+            return wallets
+                .into_iter()
+                .zip(keys)
+                .enumerate()
+                .map(|(index, (wallet, LlamaKey(uri, manifest)))| ManifestEntry {
+                    wallet,
+                    uri,
+                    manifest: Some(manifest),
+                    n: Some(index as u64 + name_offset),
+                })
+                .collect();
+        }
+    };
+    read_manifest_entries(path, &read(path), name_offset)
 }
 
-fn create_new_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
+/// Parses the `--manifests` entries in `contents`, read from `path`. The `n` of an entry that
+/// has none is its index plus `name_offset`.
+fn read_manifest_entries(path: &str, contents: &str, name_offset: u64) -> Vec<ManifestEntry> {
+    let entries: Vec<Value> = serde_json::from_str(contents)
+        .unwrap_or_else(|err| exit_with_error(err.into(), json!({ "path": path })));
+    entries
+        .into_iter()
+        .enumerate()
+        .map(
+            |(index, entry)| match serde_json::from_value::<ManifestEntry>(entry) {
+                Ok(entry) if Pubkey::from_str(&entry.wallet.0).is_ok() => ManifestEntry {
+                    n: entry.n.or(Some(index as u64 + name_offset)),
+                    ..entry
+                },
+                _ => exit_with_error(
+                    CliError::InvalidInput(format!(
+                        "Entry {} of {} is not {{ \"wallet\": <pubkey>, \"uri\": <uri> }}",
+                        index, path
+                    )),
+                    json!({ "path": path, "index": index }),
+                ),
+            },
+        )
+        .collect()
+}
+
+/// Fetches the manifest JSON at `uri`.
+fn fetch_manifest(http: &reqwest::blocking::Client, uri: &str) -> Result<Value, CliError> {
+    http.get(uri)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json())
+        .map_err(|err| CliError::InvalidInput(format!("Cannot fetch manifest {}: {}", uri, err)))
+}
+
+fn create_new_llamas(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
     let start = app_matches
         .value_of("start")
         .unwrap()
@@ -865,43 +3328,131 @@ fn create_new_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient
         .unwrap()
         .parse::<usize>()
         .unwrap();
-    let mut file = File::open("llamas_new.json").unwrap();
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    let keys: Vec<(String, Value)> = serde_json::from_str(&contents).unwrap();
-    let mut file = File::open("prints.json").unwrap();
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    let wallets: Vec<String> = serde_json::from_str(&contents).unwrap();
+    let entries = read_llama_entries(app_matches);
     let token_key = spl_token::id();
-    let len = wallets.len();
+    let len = entries.len();
+    let resolve_manifests = app_matches.is_present("arweave_manifest_resolve");
+    let name_template = app_matches.value_of("name_template");
+    let mint_token = !app_matches.is_present("no_mint_token");
+    let http = reqwest::blocking::Client::new();
+    let creators = vec![Creator {
+        address: Pubkey::from_str("LamapQPXuMYEuvsyZqK2UPqn1XCT2sW1soURj7ZJkZF").unwrap(),
+        verified: true,
+        share: 100,
+    }];
+    if let Err(err) = validate_creators(&creators) {
+        exit_with_error(
+            CliError::InvalidInput(err),
+            json!({ "creators": creators.iter().map(|c| c.address.to_string()).collect::<Vec<_>>() }),
+        );
+    }
+    let record_path = app_matches.value_of("record").unwrap();
+    let mut record = match OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(record_path)
+    {
+        Ok(record) => record,
+        Err(err) => exit_with_error(CliError::Io(err), json!({ "path": record_path })),
+    };
+    let mut timings = Timings::start(app_matches, &client);
+    timings.estimate(len, 2 + mint_token as usize);
+    let mut summary = Summary::start();
+    let mut failures = vec![];
     let mut i = 0;
     while i < len {
         if i >= start && i < end {
             println!("At {} out of {}", i, len);
-            let arweave_manifest = &keys[i].0;
-            let arweave: &Value = &keys[i].1;
-            let wallet = &Pubkey::from_str(&wallets[i]).unwrap();
+            // Failures keep the manifest read and the n named with, for --manifests to re-run.
+            let failure = serde_json::to_value(&entries[i]).unwrap();
+            let ManifestEntry {
+                wallet: PubkeyString(wallet),
+                uri: UriString(arweave_manifest),
+                manifest: embedded_manifest,
+                n,
+            } = &entries[i];
+            let resolved;
+            let arweave_manifest = if resolve_manifests {
+                let context = json!({ "index": i, "manifest": arweave_manifest });
+                let url = arweave_manifest_url(arweave_manifest).and_then(|url| {
+                    check_url(&http, &url)
+                        .map(|_| url)
+                        .map_err(|err| CliError::InvalidInput(format!("Manifest {}", err)))
+                });
+                match url {
+                    Ok(url) => {
+                        resolved = url;
+                        &resolved
+                    }
+                    Err(err) => {
+                        report_item_error("Skipping unreachable manifest.", &err, context);
+                        summary.skipped();
+                        failures.push(failure);
+                        i += 1;
+                        continue;
+                    }
+                }
+            } else {
+                arweave_manifest
+            };
+            let wallet = &Pubkey::from_str(wallet).unwrap();
+            let fetched;
+            let arweave = match embedded_manifest {
+                Some(ManifestJson(manifest)) => manifest,
+                None => match fetch_manifest(&http, arweave_manifest) {
+                    Ok(manifest) => {
+                        fetched = manifest;
+                        &fetched
+                    }
+                    Err(err) => {
+                        report_item_error(
+                            "Skipping unreadable manifest.",
+                            &err,
+                            json!({ "index": i, "manifest": arweave_manifest }),
+                        );
+                        summary.skipped();
+                        failures.push(failure);
+                        i += 1;
+                        continue;
+                    }
+                },
+            };
 
-            let program_key = spl_token_metadata::id();
+            let program_key = program.id;
             let token_key = Pubkey::from_str(TOKEN_PROGRAM_PUBKEY).unwrap();
-            let name = arweave["name"].to_owned();
-            let symbol = arweave["symbol"].to_owned();
+            let fields =
+                manifest_name_and_symbol(arweave_manifest, arweave).and_then(|(name, symbol)| {
+                    match name_template {
+                        Some(template) => {
+                            let name = numbered_name(template, n.unwrap());
+                            check_field_lengths(&name, &symbol, arweave_manifest)?;
+                            Ok((name, symbol))
+                        }
+                        None => Ok((name, symbol)),
+                    }
+                });
+            let (name, symbol) = match fields {
+                Ok(fields) => fields,
+                Err(err) => {
+                    report_item_error(
+                        "Skipping malformed manifest.",
+                        &err,
+                        json!({ "index": i, "manifest": arweave_manifest }),
+                    );
+                    summary.skipped();
+                    failures.push(failure);
+                    i += 1;
+                    continue;
+                }
+            };
             let uri = arweave_manifest;
             let mutable = true;
             let new_mint = Keypair::new();
             let mint_key = new_mint.pubkey();
-            let metadata_seeds = &[PREFIX.as_bytes(), &program_key.as_ref(), mint_key.as_ref()];
-            let (metadata_key, _) = Pubkey::find_program_address(metadata_seeds, &program_key);
-            let mut signers = vec![&payer];
-
-            let edition_seeds = &[
-                PREFIX.as_bytes(),
-                &program_key.as_ref(),
-                &mint_key.as_ref(),
-                EDITION.as_bytes(),
-            ];
-            let (edition_key, _) = Pubkey::find_program_address(edition_seeds, &program_key);
+            let metadata_key = program.metadata_pda(&mint_key);
+            let mut signers: Vec<&dyn Signer> = vec![payer.as_ref()];
+
+            let edition_key = program.edition_pda(&mint_key);
 
             let mut new_mint_instructions = vec![
                 create_account(
@@ -931,15 +3482,10 @@ fn create_new_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient
                 payer.pubkey(),
                 payer.pubkey(),
                 payer.pubkey(),
-                name.to_string(),
-                symbol.to_string(),
+                name,
+                symbol,
                 uri.to_string(),
-                Some(vec![Creator {
-                    address: Pubkey::from_str("LamapQPXuMYEuvsyZqK2UPqn1XCT2sW1soURj7ZJkZF")
-                        .unwrap(),
-                    verified: true,
-                    share: 100,
-                }]),
+                Some(creators.clone()),
                 500,
                 true,
                 mutable,
@@ -948,75 +3494,169 @@ fn create_new_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient
             instructions.append(&mut new_mint_instructions);
             instructions.push(new_metadata_instruction);
 
+            // A master edition needs the one token to exist, so without it only the mint and
+            // metadata are created, for bulk_master_edition to finish once it is minted.
             let added_token_account = Keypair::new();
-            signers.push(&added_token_account);
-            instructions.push(create_account(
-                &payer.pubkey(),
-                &added_token_account.pubkey(),
-                client
-                    .get_minimum_balance_for_rent_exemption(Account::LEN)
-                    .unwrap(),
-                Account::LEN as u64,
-                &token_key,
-            ));
-            instructions.push(
-                initialize_account(
-                    &token_key,
+            if mint_token {
+                signers.push(&added_token_account);
+                instructions.push(create_account(
+                    &payer.pubkey(),
                     &added_token_account.pubkey(),
-                    &mint_key,
-                    &wallet,
-                )
-                .unwrap(),
-            );
-            instructions.push(
-                mint_to(
+                    client
+                        .get_minimum_balance_for_rent_exemption(Account::LEN)
+                        .unwrap(),
+                    Account::LEN as u64,
                     &token_key,
-                    &mint_key,
-                    &added_token_account.pubkey(),
-                    &payer.pubkey(),
-                    &[&payer.pubkey()],
-                    1,
-                )
-                .unwrap(),
-            );
+                ));
+                instructions.push(
+                    initialize_account(
+                        &token_key,
+                        &added_token_account.pubkey(),
+                        &mint_key,
+                        &wallet,
+                    )
+                    .unwrap(),
+                );
+                instructions.push(
+                    mint_to(
+                        &token_key,
+                        &mint_key,
+                        &added_token_account.pubkey(),
+                        &payer.pubkey(),
+                        &[&payer.pubkey()],
+                        1,
+                    )
+                    .unwrap(),
+                );
 
-            instructions.push(create_master_edition(
-                program_key,
-                edition_key,
-                mint_key,
-                payer.pubkey(),
-                payer.pubkey(),
-                metadata_key,
-                payer.pubkey(),
-                Some(0u64),
-            ));
+                instructions.push(create_master_edition(
+                    program_key,
+                    edition_key,
+                    mint_key,
+                    payer.pubkey(),
+                    payer.pubkey(),
+                    metadata_key,
+                    payer.pubkey(),
+                    Some(0u64),
+                ));
+            }
 
+            if let Some(instruction) = priority_fee_instruction(app_matches, &client, &instructions)
+            {
+                instructions.insert(0, instruction);
+            }
+            if !confirm_step(
+                app_matches,
+                &format!("Create {} for {} from {}", mint_key, wallet, uri),
+            ) {
+                summary.skipped();
+                i += 1;
+                continue;
+            }
             let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
             let recent_blockhash = client.get_recent_blockhash().unwrap().0;
+            save_mint_keypair(app_matches, &new_mint);
             signers.push(&new_mint);
 
             transaction.sign(&signers, recent_blockhash);
-            match client.send_and_confirm_transaction(&transaction) {
-                Ok(_) => {
+            match timings.time(i, &entries[i].wallet.0, || {
+                send_and_confirm(app_matches, &client, &mut transaction, &signers)
+            }) {
+                Ok(signature) => {
+                    if mint_token {
+                        println!("Created {} for {} in {}", mint_key, wallet, signature);
+                    } else {
+                        println!(
+                            "Created {} for {} in {}, without its token",
+                            mint_key, wallet, signature
+                        );
+                    }
+                    // One line per llama, written as it confirms so an interrupted run keeps
+                    // the record of what it created.
+                    let line = json!({
+                        "wallet": wallet.to_string(),
+                        "mint": mint_key.to_string(),
+                        "metadata": metadata_key.to_string(),
+                        "edition": if mint_token { Some(edition_key.to_string()) } else { None },
+                        "signature": signature.to_string(),
+                    });
+                    if let Err(err) = writeln!(record, "{}", line) {
+                        exit_with_error(CliError::Io(err), line);
+                    }
+                    summary.succeeded();
                     i += 1;
                 }
                 Err(err) => {
-                    println!("Transaction failed. Retry {:?}", err);
+                    record_retry(RetryReason::of(&err));
+                    report_item_error(
+                        "Transaction failed. Retry",
+                        &err.into(),
+                        json!({ "index": i, "wallet": entries[i].wallet.0, "mint": mint_key.to_string() }),
+                    );
                 }
             }
         } else {
             i += 1;
         }
     }
+    summary.output(record_path);
+    write_failures(app_matches, &mut summary, &failures);
+    timings.finish(app_matches, &client);
+    summary.print();
 }
 
-fn update_new_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
-    let update_authority = read_keypair_file(
-        app_matches
-            .value_of("update_authority")
-            .unwrap_or_else(|| app_matches.value_of("keypair").unwrap()),
-    )
-    .unwrap();
+/// Where `update_new_llamas` records the signature sent for each metadata it updated.
+const UPDATE_CHECKPOINT: &str = "saved_update_signatures.json";
+
+/// Checks the `[metadata, signature]` pairs of a checkpoint written by `update_new_llamas`,
+/// returning the entries whose update landed and the metadata whose transaction was dropped or
+/// failed.
+fn verify_checkpoint(client: &FailoverClient, path: &str) -> (Vec<(String, String)>, Vec<String>) {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => exit_with_error(err.into(), json!({ "checkpoint": path })),
+    };
+    let entries: Vec<(String, String)> = match serde_json::from_str(&contents) {
+        Ok(entries) => entries,
+        Err(err) => exit_with_error(err.into(), json!({ "checkpoint": path })),
+    };
+
+    let mut landed = vec![];
+    let mut dropped = vec![];
+    // getSignatureStatuses takes at most 256 signatures per request.
+    for page in entries.chunks(256) {
+        let signatures: Vec<Signature> = page
+            .iter()
+            .map(|(_, signature)| Signature::from_str(signature).unwrap())
+            .collect();
+        // Checkpoints may be older than the status cache, so search the ledger history too.
+        let statuses = match client.get_signature_statuses_with_history(&signatures) {
+            Ok(statuses) => statuses.value,
+            Err(err) => exit_with_error(err.into(), json!({ "checkpoint": path })),
+        };
+        for ((key, signature), status) in page.iter().zip(statuses) {
+            match status {
+                Some(status) if status.err.is_none() => {
+                    landed.push((key.clone(), signature.clone()))
+                }
+                _ => {
+                    println!("Update of {} in {} did not land", key, signature);
+                    dropped.push(key.clone());
+                }
+            }
+        }
+    }
+    (landed, dropped)
+}
+
+fn update_new_llamas(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    let update_authority = signer_of_or_keypair(app_matches, "update_authority", wallet_manager);
     let start = app_matches
         .value_of("start")
         .unwrap()
@@ -1027,22 +3667,53 @@ fn update_new_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient
         .unwrap()
         .parse::<usize>()
         .unwrap();
-    let metadata_program = spl_token_metadata::id();
+    let metadata_program = program.id;
+    let only_if_changed = app_matches.is_present("only_if_changed");
 
     let mut file = File::open(app_matches.value_of("file").unwrap()).unwrap();
     let mut contents = String::new();
     file.read_to_string(&mut contents).unwrap();
-    let keys: Vec<(String, String)> = serde_json::from_str(&contents).unwrap();
+    let keys: Vec<(String, String)> = serde_json::from_str::<Vec<UriUpdate>>(&contents)
+        .unwrap()
+        .into_iter()
+        .map(|UriUpdate(metadata, uri)| (metadata.0, uri.0))
+        .collect();
 
     let mut old_file = File::open(app_matches.value_of("old_file").unwrap()).unwrap();
     let mut old_contents = String::new();
     old_file.read_to_string(&mut old_contents).unwrap();
-    let old_keys: Vec<(String, String)> = serde_json::from_str(&old_contents).unwrap();
+    let old_keys: Vec<UriUpdate> = serde_json::from_str(&old_contents).unwrap();
+    let mut processed: HashSet<String> = old_keys
+        .into_iter()
+        .map(|UriUpdate(metadata, _)| metadata.0)
+        .collect();
+    // Updates that landed stay in the checkpoint this run writes.
+    let mut signatures = vec![];
+    if let Some(path) = app_matches.value_of("verify_checkpoint") {
+        let (landed, dropped) = verify_checkpoint(&client, path);
+        println!(
+            "{} checkpointed updates landed, re-queuing {} that did not",
+            landed.len(),
+            dropped.len()
+        );
+        processed.extend(landed.iter().map(|(key, _)| key.clone()));
+        signatures.extend(landed);
+        for key in &dropped {
+            processed.remove(key);
+        }
+    }
 
     let len = keys.len();
     let mut i = 0;
 
+    let mut timings = Timings::start(app_matches, &client);
+    timings.estimate(
+        len.saturating_sub(processed.len()),
+        unique_signers(vec![payer.as_ref(), update_authority.as_ref()]).len(),
+    );
+    let mut summary = Summary::start();
     let mut saved = vec![];
+    let mut failures = vec![];
     while i < len {
         if i >= start && i < end {
             println!("At {} out of {}", i, len);
@@ -1050,25 +3721,57 @@ fn update_new_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient
 
             let arweave_uri = &key.1;
             let metadata_key = Pubkey::from_str(&key.0).unwrap();
-            for n in &old_keys {
-                if n.0 == key.0 {
-                    i += 1;
-                    println!("Skipping {} because already processed", key.0);
-                    continue;
-                }
+            if processed.contains(&key.0) {
+                summary.skipped();
+                i += 1;
+                println!("Skipping {} because already processed", key.0);
+                continue;
             }
             let metadata_account = client.get_account(&metadata_key).unwrap();
-            let metadata: Metadata = try_from_slice_unchecked(&metadata_account.data).unwrap();
+            let metadata = decode_metadata(&metadata_account.data).unwrap();
+            if let Err(err) = can_update(&metadata_key, &metadata, &update_authority.pubkey()) {
+                report_item_error(
+                    &format!("Skipping {}.", key.0),
+                    &err,
+                    json!({ "index": i, "metadata": key.0 }),
+                );
+                summary.skipped();
+                failures.push(json!(key));
+                i += 1;
+                continue;
+            }
 
             let new_data = Data {
                 name: metadata.data.name.replace('"', ""),
-                symbol: metadata.data.symbol,
+                symbol: metadata.data.symbol.clone(),
                 uri: arweave_uri.to_owned(),
                 seller_fee_basis_points: metadata.data.seller_fee_basis_points,
-                creators: metadata.data.creators,
+                creators: metadata.data.creators.clone(),
             };
+            if only_if_changed && data_unchanged(&metadata.data, &new_data) {
+                println!("Skipping {} because it is unchanged", key.0);
+                summary.unchanged();
+                i += 1;
+                continue;
+            }
+            if let Err(err) = check_field_lengths(&new_data.name, &new_data.symbol, &new_data.uri) {
+                report_item_error(
+                    &format!("Skipping {}.", key.0),
+                    &err,
+                    json!({ "index": i, "metadata": key.0 }),
+                );
+                summary.skipped();
+                failures.push(json!(key));
+                i += 1;
+                continue;
+            }
 
-            let signers = vec![&update_authority];
+            if !confirm_step(app_matches, &format!("Point {} at {}", key.0, arweave_uri)) {
+                summary.skipped();
+                i += 1;
+                continue;
+            }
+            let signers = vec![update_authority.as_ref()];
             let instructions = vec![update_metadata_accounts(
                 metadata_program,
                 metadata_key,
@@ -1082,13 +3785,22 @@ fn update_new_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient
             let recent_blockhash = client.get_recent_blockhash().unwrap().0;
 
             transaction.sign(&signers, recent_blockhash);
-            match client.send_transaction(&transaction) {
-                Ok(_) => {
+            match timings.time(i, &key.0, || {
+                send_transaction(app_matches, &client, &transaction)
+            }) {
+                Ok(signature) => {
+                    summary.succeeded();
                     i += 1;
                     saved.push(metadata_key.to_string());
+                    signatures.push((metadata_key.to_string(), signature.to_string()));
                 }
                 Err(err) => {
-                    println!("Transaction failed. Retry {:?}", err);
+                    record_retry(RetryReason::of(&err));
+                    report_item_error(
+                        "Transaction failed. Retry",
+                        &err.into(),
+                        json!({ "index": i, "metadata": key.0 }),
+                    );
                 }
             }
         } else {
@@ -1097,9 +3809,198 @@ fn update_new_llamas(app_matches: &ArgMatches, payer: Keypair, client: RpcClient
     }
     let saved_str = serde_json::to_string(&saved).unwrap();
     fs::write("saved_updates.json", saved_str).unwrap();
+    summary.output("saved_updates.json");
+    let signatures_str = serde_json::to_string(&signatures).unwrap();
+    fs::write(UPDATE_CHECKPOINT, signatures_str).unwrap();
+    summary.output(UPDATE_CHECKPOINT);
+    write_failures(app_matches, &mut summary, &failures);
+    timings.finish(app_matches, &client);
+    summary.print();
+}
+
+/// Reads the blockhash stored in the durable nonce account `nonce`, checking that `authority`
+/// may advance it.
+fn nonce_blockhash(
+    client: &FailoverClient,
+    nonce: &Pubkey,
+    authority: &Pubkey,
+) -> Result<Hash, CliError> {
+    let data = nonce_utils::get_account_with_commitment(client, nonce, client.commitment())
+        .and_then(|account| nonce_utils::data_from_account(&account))
+        .map_err(|err| {
+            CliError::InvalidInput(format!("{} is not a usable nonce account: {}", nonce, err))
+        })?;
+    if data.authority != *authority {
+        return Err(CliError::InvalidInput(format!(
+            "Nonce authority of {} is {}, not {}",
+            nonce, data.authority, authority
+        )));
+    }
+    Ok(data.blockhash)
+}
+
+/// Builds the instructions paying `refunds`, advancing the durable nonce first when one is given.
+fn refund_instructions(
+    payer: &Pubkey,
+    nonce: Option<(&Pubkey, &Pubkey)>,
+    refunds: &[(Pubkey, u64)],
+) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    if let Some((nonce, authority)) = nonce {
+        instructions.push(system_instruction::advance_nonce_account(nonce, authority));
+    }
+    for (recipient, amount) in refunds {
+        instructions.push(system_instruction::transfer(payer, recipient, *amount));
+    }
+    instructions
+}
+
+/// How a refund transaction that did not confirm ended.
+enum RefundError {
+    /// It did not land and no longer can, so its refunds may be paid again.
+    NotPaid(CliError),
+    /// Whether it landed could not be told, so its refunds must not be paid again.
+    Unknown(CliError),
+}
+
+/// Whether `transaction`, whose send failed with `err`, landed after all. A failed preflight
+/// simulation or a failed transaction settles it. After anything else, such as a timeout, the
+/// transaction may still land, so it is only looked up in the ledger history once it no longer
+/// can: once its blockhash has expired or, with a durable nonce, once the nonce has moved past
+/// it, which this advances the nonce for when nothing else has.
+fn landed_after_error(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    payer: &dyn Signer,
+    nonce: Option<(&Pubkey, &dyn Signer)>,
+    transaction: &Transaction,
+    err: &ClientError,
+) -> Result<bool, CliError> {
+    if send_definitely_failed(err) {
+        return Ok(false);
+    }
+    let signature = transaction.signatures[0];
+    let blockhash = transaction.message.recent_blockhash;
+    let landed = || -> Result<Option<bool>, CliError> {
+        let status = client
+            .get_signature_statuses_with_history(&[signature])?
+            .value
+            .pop()
+            .flatten();
+        Ok(status.map(|status| status.err.is_none()))
+    };
+    loop {
+        if let Some(landed) = landed()? {
+            return Ok(landed);
+        }
+        let can_land = match nonce {
+            None => client.is_blockhash_valid(&blockhash)?,
+            Some((nonce, authority)) => {
+                let can_land = nonce_blockhash(client, nonce, &authority.pubkey())? == blockhash;
+                if can_land {
+                    let mut signers = vec![payer];
+                    if authority.pubkey() != payer.pubkey() {
+                        signers.push(authority);
+                    }
+                    let instruction =
+                        system_instruction::advance_nonce_account(nonce, &authority.pubkey());
+                    let mut advance =
+                        Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+                    advance.sign(&signers, blockhash);
+                    if let Err(err) = send_and_confirm(app_matches, client, &mut advance, &signers)
+                    {
+                        eprintln!("Advancing nonce {} failed: {}", nonce, err);
+                    }
+                }
+                can_land
+            }
+        };
+        if !can_land {
+            // It may have landed just before its blockhash or nonce moved on.
+            return Ok(landed()?.unwrap_or(false));
+        }
+        backoff(app_matches, Duration::from_secs(2));
+    }
+}
+
+/// Pays `refunds` in one transaction, signed against the durable nonce when one is given. A
+/// send that fails without saying whether the transaction landed is settled with
+/// `landed_after_error` before the refunds are reported unpaid.
+fn send_refunds(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    payer: &dyn Signer,
+    nonce: Option<(&Pubkey, &dyn Signer)>,
+    refunds: &[(Pubkey, u64)],
+    index: usize,
+    timings: &mut Timings,
+) -> Result<Signature, RefundError> {
+    let mut signers = vec![payer];
+    let recent_blockhash = match nonce {
+        Some((nonce, authority)) => {
+            if authority.pubkey() != payer.pubkey() {
+                signers.push(authority);
+            }
+            nonce_blockhash(client, nonce, &authority.pubkey()).unwrap_or_else(|err| {
+                exit_with_error(err, json!({ "index": index, "nonce": nonce.to_string() }))
+            })
+        }
+        None => {
+            client
+                .get_recent_blockhash()
+                .map_err(|err| RefundError::NotPaid(err.into()))?
+                .0
+        }
+    };
+    let authority_key = nonce.map(|(_, authority)| authority.pubkey());
+    let instructions = refund_instructions(
+        &payer.pubkey(),
+        nonce.map(|(nonce, _)| nonce).zip(authority_key.as_ref()),
+        refunds,
+    );
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&signers, recent_blockhash);
+    let label: Vec<String> = refunds
+        .iter()
+        .map(|(recipient, _)| recipient.to_string())
+        .collect();
+    let err = match timings.time(index, &label.join(" "), || {
+        send_and_confirm(app_matches, client, &mut transaction, &signers)
+    }) {
+        Ok(signature) => return Ok(signature),
+        Err(err) => err,
+    };
+    let signature = transaction.signatures[0];
+    match landed_after_error(app_matches, client, payer, nonce, &transaction, &err) {
+        Ok(true) => {
+            println!("Transaction {} landed despite: {}", signature, err);
+            Ok(signature)
+        }
+        Ok(false) => Err(RefundError::NotPaid(err.into())),
+        Err(lookup) => Err(RefundError::Unknown(CliError::InvalidInput(format!(
+            "{} (cannot tell whether transaction {} landed: {}, check it before paying these \
+             refunds again)",
+            err, signature, lookup
+        )))),
+    }
 }
 
-fn file_refund(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
+/// Where `file_refunds` records the refunds it paid.
+const REFUND_RECORD: &str = "paid_refunds.json";
+
+/// Pays every refund of `--file`. With `--nonce`, each transfer is preceded by the
+/// `AdvanceNonceAccount` instruction the runtime requires first in a durable nonce transaction
+/// and signed against the stored nonce instead of a recent blockhash. The transaction then stays
+/// valid until the nonce advances, which its own success does, so a resubmission either lands
+/// the one transfer or fails, never paying twice. A batch is only split into single refunds
+/// once it is certain it did not land.
+
+fn file_refund(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
     let start = app_matches
         .value_of("start")
         .unwrap()
@@ -1110,119 +4011,245 @@ fn file_refund(app_matches: &ArgMatches, payer: Keypair, client: RpcClient) {
         .unwrap()
         .parse::<usize>()
         .unwrap();
+    let per_tx = value_of::<usize>(app_matches, "per_tx").unwrap_or(usize::MAX);
 
     let mut file = File::open(app_matches.value_of("file").unwrap()).unwrap();
     let mut contents = String::new();
     file.read_to_string(&mut contents).unwrap();
-    let keys: Vec<Value> = serde_json::from_str(&contents).unwrap();
+    let keys: Vec<Refund> = serde_json::from_str(&contents).unwrap();
+    let nonce_key = pubkey_of(app_matches, "nonce");
+    let nonce_authority =
+        nonce_key.map(|_| signer_of_or_keypair(app_matches, "nonce_authority", wallet_manager));
+    let nonce = nonce_key
+        .as_ref()
+        .zip(nonce_authority.as_ref().map(|authority| authority.as_ref()));
+    let nonce_authority_key = nonce_authority.as_ref().map(|authority| authority.pubkey());
 
-    let mut i = 0;
-    for key in keys {
-        if i >= start && i < end {
-            let instructions = [system_instruction::transfer(
+    // Packs as many refunds into each transaction as fit in a packet, at most `--per-tx`.
+    let mut batches: Vec<Vec<(Pubkey, u64)>> = vec![];
+    for key in keys.iter().take(end).skip(start) {
+        let refund = (Pubkey::from_str(&key.pubkey.0).unwrap(), key.amount);
+        let fits = batches.last().map_or(false, |batch| {
+            let mut combined = batch.clone();
+            combined.push(refund);
+            let instructions = refund_instructions(
                 &payer.pubkey(),
-                &Pubkey::from_str(key["pubkey"].as_str().unwrap()).unwrap(),
-                key["amount"].as_u64().unwrap(),
-            )];
-            println!(
-                "Paying {} lamports to {}",
-                key["amount"].as_u64().unwrap(),
-                key["pubkey"].as_str().unwrap()
+                nonce_key.as_ref().zip(nonce_authority_key.as_ref()),
+                &combined,
             );
-            let signers = [&payer];
-            let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
-            let recent_blockhash = client.get_recent_blockhash().unwrap().0;
-            transaction.sign(&signers, recent_blockhash);
-            client.send_and_confirm_transaction(&transaction).unwrap();
+            let transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+            batch.len() < per_tx
+                && bincode::serialized_size(&transaction).unwrap() as usize <= PACKET_DATA_SIZE
+        });
+        if fits {
+            batches.last_mut().unwrap().push(refund);
+        } else {
+            batches.push(vec![refund]);
         }
-        i += 1
     }
+
+    let mut timings = Timings::start(app_matches, &client);
+    timings.estimate(
+        batches.len(),
+        nonce_authority_key.map_or(1, |key| 1 + (key != payer.pubkey()) as usize),
+    );
+    let mut summary = Summary::start();
+    let mut paid = vec![];
+    let mut index = start;
+    for batch in batches {
+        for (recipient, amount) in &batch {
+            println!("Paying {} lamports to {}", amount, recipient);
+        }
+        if !confirm_step(app_matches, &format!("Pay {} refunds", batch.len())) {
+            batch.iter().for_each(|_| summary.skipped());
+            index += batch.len();
+            continue;
+        }
+        let result = send_refunds(
+            app_matches,
+            &client,
+            payer.as_ref(),
+            nonce,
+            &batch,
+            index,
+            &mut timings,
+        );
+        let results: Vec<((Pubkey, u64), Result<Signature, RefundError>)> = match result {
+            Ok(signature) => batch
+                .iter()
+                .map(|refund| (*refund, Ok(signature)))
+                .collect(),
+            Err(RefundError::NotPaid(err)) if batch.len() > 1 => {
+                report_item_error(
+                    "Batch failed, paying its refunds one by one.",
+                    &err,
+                    json!({ "index": index }),
+                );
+                batch
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, refund)| {
+                        let result = send_refunds(
+                            app_matches,
+                            &client,
+                            payer.as_ref(),
+                            nonce,
+                            &[*refund],
+                            index + offset,
+                            &mut timings,
+                        );
+                        (*refund, result)
+                    })
+                    .collect()
+            }
+            Err(RefundError::Unknown(err)) if batch.len() > 1 => batch
+                .iter()
+                .map(|refund| {
+                    let err = CliError::InvalidInput(err.to_string());
+                    (*refund, Err(RefundError::Unknown(err)))
+                })
+                .collect(),
+            Err(err) => vec![(batch[0], Err(err))],
+        };
+        for (offset, ((recipient, amount), result)) in results.into_iter().enumerate() {
+            match result {
+                Ok(signature) => {
+                    summary.succeeded();
+                    paid.push(json!({
+                        "pubkey": recipient.to_string(),
+                        "amount": amount,
+                        "signature": signature.to_string(),
+                    }));
+                }
+                Err(RefundError::NotPaid(err)) | Err(RefundError::Unknown(err)) => {
+                    report_item_error(
+                        "Refund failed.",
+                        &err,
+                        json!({ "index": index + offset, "pubkey": recipient.to_string() }),
+                    );
+                    summary.failed();
+                }
+            }
+        }
+        index += batch.len();
+    }
+    fs::write(REFUND_RECORD, serde_json::to_string(&paid).unwrap()).unwrap();
+    summary.output(REFUND_RECORD);
+    timings.finish(app_matches, &client);
+    summary.print();
+}
+
+/// Writes `mint` to `<pubkey>.json` in the `--save-mint-keypair` directory, if one was given,
+/// so the authority over a generated mint is not lost with the process.
+fn save_mint_keypair(app_matches: &ArgMatches, mint: &Keypair) {
+    let dir = match app_matches.value_of("save_mint_keypair") {
+        Some(dir) => Path::new(dir),
+        None => return,
+    };
+    let path = dir.join(format!("{}.json", mint.pubkey()));
+    let context = json!({ "mint": mint.pubkey().to_string(), "path": path.to_string_lossy() });
+    if let Err(err) = fs::create_dir_all(dir) {
+        exit_with_error(err.into(), context);
+    }
+    if let Err(err) = write_keypair_file(mint, &path) {
+        exit_with_error(
+            io::Error::new(io::ErrorKind::Other, err.to_string()).into(),
+            context,
+        );
+    }
+    println!("Saved mint keypair to {}", path.display());
 }
 
 fn create_metadata_account_call(
     app_matches: &ArgMatches,
-    payer: Keypair,
-    client: RpcClient,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
 ) -> (Metadata, Pubkey) {
-    let update_authority = read_keypair_file(
-        app_matches
-            .value_of("update_authority")
-            .unwrap_or_else(|| app_matches.value_of("keypair").unwrap()),
-    )
-    .unwrap();
+    let update_authority = signer_of_or_keypair(app_matches, "update_authority", wallet_manager);
 
-    let program_key = spl_token_metadata::id();
-    let token_key = Pubkey::from_str(TOKEN_PROGRAM_PUBKEY).unwrap();
     let name = app_matches.value_of("name").unwrap().to_owned();
     let symbol = app_matches.value_of("symbol").unwrap().to_owned();
     let uri = app_matches.value_of("uri").unwrap().to_owned();
-    let create_new_mint = !app_matches.is_present("mint");
+    if let Err(err) = check_field_lengths(&name, &symbol, &uri) {
+        exit_with_error(err, json!({ "name": name, "symbol": symbol, "uri": uri }));
+    }
+    let create_new_mint = app_matches.is_present("new_mint");
     let mutable = app_matches.is_present("mutable");
     let new_mint = Keypair::new();
     let mint_key = match app_matches.value_of("mint") {
         Some(_val) => pubkey_of(app_matches, "mint").unwrap(),
         None => new_mint.pubkey(),
     };
-    let metadata_seeds = &[PREFIX.as_bytes(), &program_key.as_ref(), mint_key.as_ref()];
-    let (metadata_key, _) = Pubkey::find_program_address(metadata_seeds, &program_key);
+    let metadata_key = program.metadata_pda(&mint_key);
 
-    let mut new_mint_instructions = vec![
-        create_account(
-            &payer.pubkey(),
-            &mint_key,
+    if app_matches.is_present("skip_existing") {
+        if let Ok(account) = client.get_account(&metadata_key) {
+            if account.data.first() == Some(&(Key::MetadataV1 as u8)) {
+                let metadata = decode_metadata(&account.data).unwrap();
+                println!("Metadata {} already exists, skipping", metadata_key);
+                println!("Metadata: {:#?}", metadata);
+                std::process::exit(0);
+            }
+        }
+    }
+
+    let new_mint_rent = if create_new_mint {
+        Some(
             client
                 .get_minimum_balance_for_rent_exemption(Mint::LEN)
                 .unwrap(),
-            Mint::LEN as u64,
-            &token_key,
-        ),
-        initialize_mint(
-            &token_key,
-            &mint_key,
-            &payer.pubkey(),
-            Some(&payer.pubkey()),
-            0,
         )
-        .unwrap(),
-    ];
-
-    let new_metadata_instruction = create_metadata_accounts(
-        program_key,
-        metadata_key,
-        mint_key,
-        payer.pubkey(),
-        payer.pubkey(),
-        update_authority.pubkey(),
+    } else {
+        None
+    };
+    let requested = Data {
         name,
         symbol,
         uri,
-        None,
-        0,
-        update_authority.pubkey() != payer.pubkey(),
+        seller_fee_basis_points: 0,
+        creators: None,
+    };
+    let instructions = build_create_metadata_instructions(
+        program,
+        &payer.pubkey(),
+        &update_authority.pubkey(),
+        &mint_key,
+        new_mint_rent,
+        requested.name.clone(),
+        requested.symbol.clone(),
+        requested.uri.clone(),
         mutable,
     );
 
-    let mut instructions = vec![];
-
-    if create_new_mint {
-        instructions.append(&mut new_mint_instructions)
-    }
-
-    instructions.push(new_metadata_instruction);
-
     let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
-    let recent_blockhash = client.get_recent_blockhash().unwrap().0;
-    let mut signers = vec![&payer];
+    let mut signers: Vec<&dyn Signer> = vec![payer.as_ref()];
     if create_new_mint {
+        save_mint_keypair(app_matches, &new_mint);
         signers.push(&new_mint);
     }
     if update_authority.pubkey() != payer.pubkey() {
-        signers.push(&update_authority)
+        signers.push(update_authority.as_ref())
     }
-    transaction.sign(&signers, recent_blockhash);
-    client.send_and_confirm_transaction(&transaction).unwrap();
+    sign_and_send(app_matches, &client, &mut transaction, &signers);
     let account = client.get_account(&metadata_key).unwrap();
-    let metadata: Metadata = try_from_slice_unchecked(&account.data).unwrap();
+    let metadata = decode_metadata(&account.data).unwrap();
+    let mut differences = data_differences(&requested, &metadata.data);
+    if metadata.update_authority != update_authority.pubkey() {
+        differences.push(format!(
+            "update_authority: {} -> {}",
+            update_authority.pubkey(),
+            metadata.update_authority
+        ));
+    }
+    if metadata.is_mutable != mutable {
+        differences.push(format!(
+            "is_mutable: {} -> {}",
+            mutable, metadata.is_mutable
+        ));
+    }
+    verify_after_send(app_matches, &metadata_key, differences);
     (metadata, metadata_key)
 }
 
@@ -1237,28 +4264,191 @@ fn main() {
                 .validator(is_valid_signer)
                 .takes_value(true)
                 .global(true)
-                .help("Filepath or URL to a keypair"),
+                .help("Filepath or URL to a keypair"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true)
+                .validator(is_url)
+                .help("JSON RPC URL for the cluster [default: devnet]. Repeat it to fail over to the next URL when one is unreachable or returns a server error"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .global(true)
+                .help("Print which RPC URL served each request to stderr"),
+        )
+        .arg(
+            Arg::with_name("update_authority")
+                .long("update_authority")
+                .value_name("UPDATE_AUTHORITY")
+                .takes_value(true)
+                .global(true)
+                .validator(is_valid_signer)
+                .help("Update authority filepath or url to keypair besides yourself (e.g. usb://ledger), defaults to normal keypair"),
+        )
+        .arg(
+            Arg::with_name("error_format")
+                .long("error-format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("Report errors as prose or as single-line JSON objects on stderr"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .takes_value(false)
+                .global(true)
+                .help("Exit with an error on the first account that does not decode instead of skipping it"),
+        )
+        .arg(
+            Arg::with_name("dump_logs")
+                .long("dump-logs")
+                .takes_value(false)
+                .global(true)
+                .help("Print the program logs of a failed transaction along with its error"),
+        )
+        .arg(
+            Arg::with_name("debug_pda")
+                .long("debug-pda")
+                .takes_value(false)
+                .global(true)
+                .help("Print the bump of every PDA derived and check it against create_program_address"),
+        )
+        .arg(
+            Arg::with_name("page_size")
+                .long("page-size")
+                .value_name("PAGE_SIZE")
+                .takes_value(true)
+                .global(true)
+                .validator(is_parsable::<usize>)
+                .default_value("100")
+                .help("Accounts fetched per request when scanning a program, at most 100"),
+        )
+        .arg(
+            Arg::with_name("auto_priority_fee")
+                .long("auto-priority-fee")
+                .takes_value(false)
+                .global(true)
+                .help("Price compute units from recent prioritization fees when airdropping or minting llamas"),
+        )
+        .arg(
+            Arg::with_name("priority_fee_percentile")
+                .long("priority-fee-percentile")
+                .value_name("PERCENTILE")
+                .takes_value(true)
+                .global(true)
+                .validator(is_parsable::<u8>)
+                .default_value("50")
+                .help("Percentile of recent prioritization fees to pay with --auto-priority-fee"),
+        )
+        .arg(
+            Arg::with_name("max_txn_age")
+                .long("max-txn-age")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .global(true)
+                .validator(is_parsable::<u64>)
+                .help("Send a transaction again if it has not confirmed after this many seconds, re-signing it with a fresh blockhash only once its own has expired"),
+        )
+        .arg(
+            Arg::with_name("min_interval")
+                .long("min-interval")
+                .value_name("MS")
+                .takes_value(true)
+                .global(true)
+                .default_value("0")
+                .validator(is_parsable::<u64>)
+                .help("Minimum number of milliseconds between two transactions sent, to stay under RPC rate limits"),
+        )
+        .arg(
+            Arg::with_name("retry_jitter")
+                .long("retry-jitter")
+                .value_name("FRACTION")
+                .takes_value(true)
+                .global(true)
+                .default_value("0.5")
+                .validator(is_jitter)
+                .help("Randomly lengthen or shorten waits between retries by up to this fraction, between 0 and 1"),
+        )
+        .arg(
+            Arg::with_name("cost_report")
+                .long("cost-report")
+                .value_name("FILE")
+                .takes_value(true)
+                .global(true)
+                .help("Print the estimated fees of a bulk command before it sends, and write the estimated and actual fee of every transaction it sent to this CSV file"),
+        )
+        .arg(
+            Arg::with_name("metadata_program")
+                .long("metadata-program")
+                .value_name("PROGRAM_ID")
+                .takes_value(true)
+                .global(true)
+                .validator(is_valid_pubkey)
+                .help("Token metadata program to derive accounts and build instructions for, such as a local fork [default: the deployed program]"),
+        )
+        .arg(
+            Arg::with_name("retry_metrics")
+                .long("retry-metrics")
+                .value_name("FILE")
+                .takes_value(true)
+                .global(true)
+                .help("Write the number of transactions sent and the retries by reason (blockhash expired, rate limited, other) to this JSON file at the end of the run"),
+        )
+        .arg(
+            Arg::with_name("min_balance")
+                .long("min-balance")
+                .value_name("SOL")
+                .takes_value(true)
+                .global(true)
+                .validator(is_parsable::<f64>)
+                .help("Check the fee payer balance before the first transaction and every ten after, pausing until it is topped up once it is below this many SOL"),
+        )
+        .arg(
+            Arg::with_name("abort_on_low_balance")
+                .long("abort-on-low-balance")
+                .takes_value(false)
+                .global(true)
+                .requires("min_balance")
+                .help("End the run with an error instead of pausing when the payer balance is below --min-balance"),
         )
         .arg(
-            Arg::with_name("json_rpc_url")
-                .long("url")
-                .value_name("URL")
-                .takes_value(true)
+            Arg::with_name("confirm_each")
+                .long("confirm-each")
+                .takes_value(false)
                 .global(true)
-                .validator(is_url)
-                .help("JSON RPC URL for the cluster [default: devnet]"),
+                .help("Before each transaction of a bulk command, print what it does and wait for enter to send it, or skip or abort"),
         )
         .arg(
-            Arg::with_name("update_authority")
-                .long("update_authority")
-                .value_name("UPDATE_AUTHORITY")
+            Arg::with_name("yes")
+                .long("yes")
+                .takes_value(false)
+                .global(true)
+                .help("Answer every --confirm-each prompt with send, to run unattended"),
+        )
+        .arg(
+            Arg::with_name("timing_output")
+                .long("timing-output")
+                .value_name("FILE")
                 .takes_value(true)
                 .global(true)
-                .help("Update authority filepath or url to keypair besides yourself, defaults to normal keypair"),
+                .help("Write the latency of every transaction a bulk command sends to this CSV file"),
         )
         .subcommand(
      SubCommand::with_name("create_metadata_accounts")
                 .about("Create Metadata Accounts")
+                .arg(blockhash_arg())
+                .arg(sign_only_arg())
+                .arg(verify_after_send_arg())
                 .arg(
                     Arg::with_name("name")
                         .long("name")
@@ -1288,8 +4478,24 @@ fn main() {
                         .long("mint")
                         .value_name("MINT")
                         .takes_value(true)
-                        .required(false)
-                        .help("Pubkey for an existing mint (random new mint otherwise)"),
+                        .required_unless("new_mint")
+                        .validator(is_valid_pubkey)
+                        .help("Pubkey for an existing mint"),
+                )
+                .arg(
+                    Arg::with_name("new_mint")
+                        .long("new-mint")
+                        .takes_value(false)
+                        .conflicts_with("mint")
+                        .help("Create a random new mint, with you as its mint authority, instead of using --mint"),
+                )
+                .arg(
+                    Arg::with_name("save_mint_keypair")
+                        .long("save-mint-keypair")
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .requires("new_mint")
+                        .help("Write the keypair of the --new-mint to <pubkey>.json in this directory"),
                 )
                 .arg(
                     Arg::with_name("mutable")
@@ -1299,6 +4505,15 @@ fn main() {
                         .required(false)
                         .help("Permit future metadata updates"),
                 )
+                .arg(
+                    Arg::with_name("skip_existing")
+                        .long("skip-existing")
+                        .value_name("SKIP_EXISTING")
+                        .takes_value(false)
+                        .required(false)
+                        .requires("mint")
+                        .help("Print the existing metadata and exit successfully if the mint already has one"),
+                )
         ).subcommand(
             SubCommand::with_name("mint_coins")
                        .about("Mint coins to your mint to an account")
@@ -1318,18 +4533,57 @@ fn main() {
                             .validator(is_valid_pubkey)
                             .takes_value(true)
                             .help("Destination account. If one isnt given, one is made."),
+                    ).arg(
+                        Arg::with_name("ata")
+                            .long("ata")
+                            .value_name("ATA")
+                            .required(false)
+                            .takes_value(false)
+                            .conflicts_with("destination")
+                            .help("Mint into the associated token account of the owner (defaults to you), creating it if needed"),
+                    ).arg(
+                        Arg::with_name("owner")
+                            .long("owner")
+                            .value_name("OWNER")
+                            .required(false)
+                            .validator(is_valid_pubkey)
+                            .takes_value(true)
+                            .conflicts_with("destination")
+                            .help("Wallet whose associated token account receives the tokens. Implies --ata."),
                     ).arg(
                         Arg::with_name("amount")
                             .long("amount")
                             .value_name("AMOUNT")
-                            .required(true)
+                            .required_unless("recipients_file")
                             .takes_value(true)
-                            .help("How many"),
+                            .help("How many, as a UI amount scaled by the mint's decimals (e.g. 1.5)"),
+                    ).arg(
+                        Arg::with_name("recipients_file")
+                            .long("recipients-file")
+                            .value_name("FILE")
+                            .takes_value(true)
+                            .conflicts_with_all(&["destination", "ata", "owner", "amount"])
+                            .help("File of owner,amount lines, minting each amount into the associated token account of its owner in batched transactions"),
+                    ).arg(
+                        Arg::with_name("raw")
+                            .long("raw")
+                            .value_name("RAW")
+                            .required(false)
+                            .takes_value(false)
+                            .help("Treat the amount as raw base units instead of a UI amount"),
+                    ).arg(
+                        Arg::with_name("disable_mint_authority")
+                            .long("disable-mint-authority")
+                            .takes_value(false)
+                            .help("After minting, remove the mint authority so no more tokens can ever be minted. Cannot be undone, so it only runs with --yes"),
                     )
                )
         .subcommand(
      SubCommand::with_name("update_metadata_accounts")
                 .about("Update Metadata Accounts")
+                .arg(blockhash_arg())
+                .arg(sign_only_arg())
+                .arg(verify_after_send_arg())
                 .arg(
                     Arg::with_name("mint")
                         .long("mint")
@@ -1355,6 +4609,14 @@ fn main() {
                         .required(false)
                         .help("new NAME for the Metadata"),
                 )
+                .arg(
+                    Arg::with_name("symbol")
+                        .long("symbol")
+                        .value_name("SYMBOL")
+                        .takes_value(true)
+                        .required(false)
+                        .help("new SYMBOL for the Metadata"),
+                )
                 .arg(
                     Arg::with_name("new_update_authority")
                         .long("new_update_authority")
@@ -1363,6 +4625,23 @@ fn main() {
                         .validator(is_valid_pubkey)
                         .takes_value(true)
                         .help("New update authority"))
+                .arg(
+                    Arg::with_name("seller_fee")
+                        .long("seller-fee")
+                        .value_name("BASIS_POINTS")
+                        .takes_value(true)
+                        .validator(is_seller_fee)
+                        .help("New seller fee in basis points, kept as it is on chain when omitted"),
+                )
+                .arg(
+                    Arg::with_name("creators")
+                        .long("creators")
+                        .value_name("PUBKEY:SHARE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .validator(|val| parse_creator_share(&val).map(|_| ()))
+                        .help("New creators with their shares, which must sum to 100. Creators already on chain keep their verified flag. Kept as they are on chain when omitted"),
+                )
         ).subcommand(
             SubCommand::with_name("show")
                 .about("Show")
@@ -1375,6 +4654,28 @@ fn main() {
                         .takes_value(true)
                         .help("Metadata mint"),
                 )
+                .arg(
+                    Arg::with_name("raw")
+                        .long("raw")
+                        .value_name("RAW")
+                        .takes_value(false)
+                        .required(false)
+                        .help("Also print a hex dump of the account data with the known fields marked"),
+                )
+                .arg(
+                    Arg::with_name("reservation_lists")
+                        .long("reservation-lists")
+                        .takes_value(false)
+                        .required(false)
+                        .help("For a deprecated master edition, also find and print its reservation lists. Scans the program's accounts"),
+                )
+                .arg(
+                    Arg::with_name("show_authority_balance")
+                        .long("show-authority-balance")
+                        .takes_value(false)
+                        .required(false)
+                        .help("Also print the SOL balance of the update authority and whether it is a wallet, a program or a PDA"),
+                )
         ).subcommand(
             SubCommand::with_name("show_reservation_list")
                 .about("Show Reservation List")
@@ -1388,9 +4689,37 @@ fn main() {
                         .help("Account key of reservation list"),
                 )
         )
+        .subcommand(
+            SubCommand::with_name("validate_reservation_list")
+                .about("Check a reservation list for duplicate addresses and spot counts that do not add up")
+                .arg(
+                    Arg::with_name("key")
+                        .long("key")
+                        .value_name("KEY")
+                        .required(true)
+                        .validator(is_valid_pubkey)
+                        .takes_value(true)
+                        .help("Account key of reservation list"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("dump_reservation_lists")
+                .about("Write every reservation list of the program, with its reservations, spot counts and supply snapshot, to a JSON file")
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .default_value("reservation_lists.json")
+                        .help("File to write the reservation lists to"),
+                )
+        )
         .subcommand(
             SubCommand::with_name("create_master_edition")
                 .about("Create Master Edition out of Metadata")
+                .arg(blockhash_arg())
+                .arg(sign_only_arg())
+                .arg(verify_after_send_arg())
                 .arg(
                     Arg::with_name("add_one_token")
                         .long("add_one_token")
@@ -1425,6 +4754,8 @@ fn main() {
         ).subcommand(
                 SubCommand::with_name("mint_new_edition_from_master_edition_via_token")
                         .about("Mint new edition from master edition via a token - this will just also mint the token for you and submit it.")
+                        .arg(blockhash_arg())
+                        .arg(sign_only_arg())
                         .arg(
                             Arg::with_name("mint")
                                 .long("mint")
@@ -1449,11 +4780,28 @@ fn main() {
                                 .validator(is_valid_signer)
                                 .takes_value(true)
                                 .help("Account's authority, defaults to you"),
+                        ).arg(
+                            Arg::with_name("edition")
+                                .long("edition")
+                                .value_name("EDITION")
+                                .required(false)
+                                .validator(is_edition_number)
+                                .takes_value(true)
+                                .help("Edition number to print instead of the one after the current supply, to fill a gap or recover a diverged supply"),
                         )
 
         ).subcommand(
                 SubCommand::with_name("puff_unpuffed_metadata")
                         .about("Take metadata that still have variable length name, symbol, and uri fields and stretch them out with null symbols so they can be searched more easily by RPC.")
+                        .arg(
+                            Arg::with_name("batch_size")
+                                .long("batch-size")
+                                .value_name("BATCH_SIZE")
+                                .takes_value(true)
+                                .validator(is_parsable::<usize>)
+                                .default_value("20")
+                                .help("Puff instructions per transaction, reduced automatically if the first batch does not fit"),
+                        )
         ).subcommand(
                 SubCommand::with_name("find_all_llamas").arg(
                     Arg::with_name("start")
@@ -1469,6 +4817,40 @@ fn main() {
                         .takes_value(true)
                         .required(true)
                         .help("end"),
+                ).arg(
+                    Arg::with_name("since_slot")
+                        .long("since-slot")
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .validator(is_parsable::<u64>)
+                        .help("Only check metadata last modified at or after this slot. Costs one extra getSignaturesForAddress request per item"),
+                ).arg(
+                    Arg::with_name("before_slot")
+                        .long("before-slot")
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .validator(is_parsable::<u64>)
+                        .help("Only check metadata last modified before this slot. Costs one extra getSignaturesForAddress request per item"),
+                ).arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("OUTPUT")
+                        .takes_value(true)
+                        .possible_values(&["json", "csv"])
+                        .default_value("json")
+                        .help("Format of the bad metadata file"),
+                ).arg(
+                    Arg::with_name("checkpoint")
+                        .long("checkpoint")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .default_value("find_all_llamas_checkpoint.jsonl")
+                        .help("JSON lines file the scan position and bad metadata found so far are appended to after each item. A re-run over the same range continues from it, adding to the bad metadata it found"),
+                ).arg(
+                    Arg::with_name("reset")
+                        .long("reset")
+                        .takes_value(false)
+                        .help("Ignore the checkpoint and scan the range from its start"),
                 )
                         .about("")
         ).subcommand(
@@ -1478,7 +4860,34 @@ fn main() {
                     .value_name("FILE")
                     .takes_value(true)
                     .required(true)
-                    .help("file"),
+                    .help("JSON array of [wallet, count] pairs, or a wallet,count CSV file if it ends in .csv"),
+            ).arg(
+                Arg::with_name("use_ata")
+                    .long("use-ata")
+                    .takes_value(false)
+                    .required(false)
+                    .help("Mint each print into the associated token account of its recipient instead of a new token account"),
+            ).arg(
+                Arg::with_name("failures")
+                    .long("failures")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .help("Write the prints that failed to this file as [wallet, count] pairs, or wallet,count lines if it ends in .csv, to re-run them with --file"),
+            ).arg(
+                Arg::with_name("reservation_list")
+                    .long("reservation-list")
+                    .value_name("RESERVATION_LIST")
+                    .takes_value(true)
+                    .validator(is_valid_pubkey)
+                    .help("Print a V1 master edition through this reservation list, claiming the spots reserved for the payer so each print gets the edition number the list gives it. Burns one of the payer's printing tokens per print"),
+            ).arg(
+                Arg::with_name("retries")
+                    .long("retries")
+                    .value_name("RETRIES")
+                    .takes_value(true)
+                    .validator(is_parsable::<usize>)
+                    .default_value("3")
+                    .help("Times to resend a grant after a network failure, once the previous send is known not to have landed"),
             )
                     .about("")
     ).subcommand(
@@ -1496,6 +4905,25 @@ fn main() {
                     .takes_value(true)
                     .required(true)
                     .help("end"),
+            ).arg(
+                Arg::with_name("progress")
+                    .long("progress")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .help("JSON lines file each pulled uri is appended to as it is fetched [default: metadata_uris_<START>_<END>.jsonl]"),
+            ).arg(
+                Arg::with_name("resume")
+                    .long("resume")
+                    .takes_value(false)
+                    .help("Continue the pull recorded in the progress file after its last item"),
+            ).arg(
+                Arg::with_name("retries")
+                    .long("retries")
+                    .value_name("RETRIES")
+                    .takes_value(true)
+                    .validator(is_parsable::<usize>)
+                    .default_value("3")
+                    .help("Times to fetch a uri again after a failed request, a server error or rate limiting"),
             )
                     .about(""))
                     .subcommand(
@@ -1513,6 +4941,56 @@ fn main() {
                                 .takes_value(true)
                                 .required(true)
                                 .help("end"),
+                        ).arg(
+                            Arg::with_name("save_mint_keypair")
+                                .long("save-mint-keypair")
+                                .value_name("DIR")
+                                .takes_value(true)
+                                .help("Write each generated mint keypair to <pubkey>.json in this directory"),
+                        ).arg(
+                            Arg::with_name("record")
+                                .long("record")
+                                .value_name("FILE")
+                                .takes_value(true)
+                                .default_value("created_llamas.jsonl")
+                                .help("File each created llama is appended to as it confirms, one JSON object of wallet, mint, metadata, edition and signature per line"),
+                        ).arg(
+                            Arg::with_name("arweave_manifest_resolve")
+                                .long("arweave-manifest-resolve")
+                                .takes_value(false)
+                                .help("Treat the manifests of llamas_new.json as Arweave transaction ids, using their https://arweave.net/ URL as the uri and skipping those that do not answer"),
+                        ).arg(
+                            Arg::with_name("manifests")
+                                .long("manifests")
+                                .value_name("FILE")
+                                .takes_value(true)
+                                .help("JSON array of { \"wallet\", \"uri\" } entries to create llamas from instead of llamas_new.json and prints.json. The name and symbol are read from the manifest fetched from each uri, unless the entry embeds it as \"manifest\". An entry's \"n\", if any, replaces its index plus --name-offset in --name-template"),
+                        ).arg(
+                            Arg::with_name("name_template")
+                                .long("name-template")
+                                .value_name("TEMPLATE")
+                                .takes_value(true)
+                                .validator(is_name_template)
+                                .help("Name the llamas from this template instead of their manifests, with {n} replaced by the index of the item plus --name-offset, e.g. \"Llama #{n}\""),
+                        ).arg(
+                            Arg::with_name("name_offset")
+                                .long("name-offset")
+                                .value_name("OFFSET")
+                                .takes_value(true)
+                                .validator(is_parsable::<u64>)
+                                .default_value("0")
+                                .help("Added to the index of each item to get its {n} in --name-template"),
+                        ).arg(
+                            Arg::with_name("no_mint_token")
+                                .long("no-mint-token")
+                                .takes_value(false)
+                                .help("Create only the mint and metadata, leaving the supply at zero. The master edition needs the token, so it is not created either: run bulk_master_edition --record with the --record file later to mint each token into its wallet and create the master edition"),
+                        ).arg(
+                            Arg::with_name("failures")
+                                .long("failures")
+                                .value_name("FILE")
+                                .takes_value(true)
+                                .help("Write the llamas that could not be created to this file as { \"wallet\", \"uri\", \"n\" } entries, with the \"manifest\" of llamas_new.json if it was read, to re-run them with --manifests under the same names"),
                         ))
                         .subcommand(
                             SubCommand::with_name("update_new_llamas").arg(
@@ -1543,6 +5021,23 @@ fn main() {
                                     .takes_value(true)
                                     .required(true)
                                     .help("end"),
+                            ).arg(
+                                Arg::with_name("verify_checkpoint")
+                                    .long("verify-checkpoint")
+                                    .value_name("CHECKPOINT")
+                                    .takes_value(true)
+                                    .help("saved_update_signatures.json of an earlier run. Updates whose transaction landed are skipped, those that did not are sent again even if listed in old_file"),
+                            ).arg(
+                                Arg::with_name("only_if_changed")
+                                    .long("only-if-changed")
+                                    .takes_value(false)
+                                    .help("Skip metadata whose data would not change, counting it as unchanged"),
+                            ).arg(
+                                Arg::with_name("failures")
+                                    .long("failures")
+                                    .value_name("FILE")
+                                    .takes_value(true)
+                                    .help("Write the updates that could not be made to this file as [metadata, uri] pairs, to re-run them with --file"),
                             ))
                             .subcommand(
                                 SubCommand::with_name("file_refunds").arg(
@@ -1566,28 +5061,759 @@ fn main() {
                                         .takes_value(true)
                                         .required(true)
                                         .help("end"),
-                                )).get_matches();
+                                ).arg(
+                                    Arg::with_name("nonce")
+                                        .long("nonce")
+                                        .value_name("NONCE")
+                                        .takes_value(true)
+                                        .validator(is_valid_pubkey)
+                                        .help("Durable nonce account to sign each refund against instead of a recent blockhash"),
+                                ).arg(
+                                    Arg::with_name("nonce_authority")
+                                        .long("nonce-authority")
+                                        .value_name("NONCE_AUTHORITY")
+                                        .takes_value(true)
+                                        .requires("nonce")
+                                        .validator(is_valid_signer)
+                                        .help("Authority of the nonce account, keypair filepath or url (e.g. usb://ledger), defaults to normal keypair"),
+                                ).arg(
+                                    Arg::with_name("per_tx")
+                                        .long("per-tx")
+                                        .value_name("N")
+                                        .takes_value(true)
+                                        .validator(is_parsable::<usize>)
+                                        .help("Most refunds paid per transaction [default: as many as fit in one]"),
+                                ))
+                                .subcommand(
+                                    SubCommand::with_name("dump_metadata_keys")
+                                        .about("Write the pubkey of every metadata account to a JSON array, suitable as all_metadata.json for pull_llama_arweave_uris.")
+                                        .arg(
+                                            Arg::with_name("output")
+                                                .long("output")
+                                                .value_name("OUTPUT")
+                                                .takes_value(true)
+                                                .required(false)
+                                                .default_value("all_metadata.json")
+                                                .help("File to write the keys to"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("migrate_uris")
+                                        .about("Rewrite the URI prefix of every metadata in a mints file, e.g. when moving to a new storage gateway.")
+                                        .arg(
+                                            Arg::with_name("mints_file")
+                                                .long("mints-file")
+                                                .value_name("MINTS_FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("JSON array of mint pubkeys"),
+                                        ).arg(
+                                            Arg::with_name("from")
+                                                .long("from")
+                                                .value_name("FROM")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("URI prefix to replace"),
+                                        ).arg(
+                                            Arg::with_name("to")
+                                                .long("to")
+                                                .value_name("TO")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("URI prefix to replace it with"),
+                                        ).arg(
+                                            Arg::with_name("dry_run")
+                                                .long("dry-run")
+                                                .value_name("DRY_RUN")
+                                                .takes_value(false)
+                                                .required(false)
+                                                .help("Only list the planned rewrites"),
+                                        ).arg(
+                                            Arg::with_name("output_unsigned_batch")
+                                                .long("output-unsigned-batch")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .conflicts_with("dry_run")
+                                                .help("Write the updates to FILE as unsigned transactions instead of sending them, for the update authority to sign elsewhere and broadcast_batch to submit. --update_authority may be a pubkey"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("dedup_creators")
+                                        .about("Merge duplicate creator entries of a metadata, summing their shares.")
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Mint of the Metadata"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("holder")
+                                        .about("Print the wallet currently holding an NFT, or the top holder of a fungible mint.")
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Mint of the token"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("inventory")
+                                        .about("List every NFT held by a wallet.")
+                                        .arg(
+                                            Arg::with_name("owner")
+                                                .long("owner")
+                                                .value_name("OWNER")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Wallet to list"),
+                                        ).arg(
+                                            Arg::with_name("collection")
+                                                .long("collection")
+                                                .value_name("COLLECTION")
+                                                .required(false)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Only list this master mint and editions printed from it"),
+                                        ).arg(
+                                            Arg::with_name("include_burned")
+                                                .long("include-burned")
+                                                .takes_value(false)
+                                                .help("Also list NFTs the wallet burned, labelled as such"),
+                                        ).arg(
+                                            Arg::with_name("output")
+                                                .long("output")
+                                                .value_name("OUTPUT")
+                                                .takes_value(true)
+                                                .possible_values(&["text", "json", "csv"])
+                                                .default_value("text")
+                                                .help("Output format"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("fix_nulls")
+                                        .about("Strip null bytes embedded in the middle of the name, symbol or uri of every metadata in a mints file.")
+                                        .arg(
+                                            Arg::with_name("mints_file")
+                                                .long("mints-file")
+                                                .value_name("MINTS_FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("JSON array of mint pubkeys"),
+                                        ).arg(
+                                            Arg::with_name("dry_run")
+                                                .long("dry-run")
+                                                .value_name("DRY_RUN")
+                                                .takes_value(false)
+                                                .required(false)
+                                                .help("Only list the affected mints"),
+                                        ).arg(
+                                            Arg::with_name("output_unsigned_batch")
+                                                .long("output-unsigned-batch")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .conflicts_with("dry_run")
+                                                .help("Write the updates to FILE as unsigned transactions instead of sending them, for the update authority to sign elsewhere and broadcast_batch to submit. --update_authority may be a pubkey"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("audit_supply")
+                                        .about("Compare the supply recorded on a master edition with the number of editions printed from it.")
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Mint of the master edition"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("strip_quotes")
+                                        .about("Strip double quotes wrapping the name or symbol of every metadata in a mints file.")
+                                        .arg(
+                                            Arg::with_name("mints_file")
+                                                .long("mints-file")
+                                                .value_name("MINTS_FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("JSON array of mint pubkeys"),
+                                        ).arg(
+                                            Arg::with_name("dry_run")
+                                                .long("dry-run")
+                                                .value_name("DRY_RUN")
+                                                .takes_value(false)
+                                                .required(false)
+                                                .help("Only list the affected mints"),
+                                        ).arg(
+                                            Arg::with_name("output_unsigned_batch")
+                                                .long("output-unsigned-batch")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .conflicts_with("dry_run")
+                                                .help("Write the updates to FILE as unsigned transactions instead of sending them, for the update authority to sign elsewhere and broadcast_batch to submit. --update_authority may be a pubkey"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("bulk_master_edition")
+                                        .about("Create a master edition for every mint in a mints file, minting the one token of mints that have none.")
+                                        .arg(
+                                            Arg::with_name("mints_file")
+                                                .long("mints-file")
+                                                .value_name("MINTS_FILE")
+                                                .takes_value(true)
+                                                .required_unless("record")
+                                                .help("JSON array of mint pubkeys"),
+                                        ).arg(
+                                            Arg::with_name("record")
+                                                .long("record")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .conflicts_with("mints_file")
+                                                .help("Read the mints from a create_new_llamas --record file instead, minting the token of each into the wallet it was created for rather than to you"),
+                                        ).arg(
+                                            Arg::with_name("max_supply")
+                                                .long("max-supply")
+                                                .value_name("MAX_SUPPLY")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .validator(is_max_supply)
+                                                .help("Maximum number of editions that can be printed from each master edition, or `unlimited`"),
+                                        ).arg(
+                                            Arg::with_name("mint_authority")
+                                                .long("mint_authority")
+                                                .value_name("MINT_AUTHORITY")
+                                                .validator(is_valid_signer)
+                                                .takes_value(true)
+                                                .required(false)
+                                                .help("Filepath or URL to a keypair representing mint authority, defaults to you"),
+                                        ).arg(
+                                            Arg::with_name("batch_size")
+                                                .long("batch-size")
+                                                .value_name("BATCH_SIZE")
+                                                .takes_value(true)
+                                                .validator(is_parsable::<usize>)
+                                                .default_value("4")
+                                                .help("Most master editions created per transaction, fewer when they do not fit in one"),
+                                        ).arg(
+                                            Arg::with_name("failed_file")
+                                                .long("failed-file")
+                                                .value_name("FAILED_FILE")
+                                                .takes_value(true)
+                                                .default_value("failed_master_editions.json")
+                                                .help("Where to write the mints that failed, as a mints file to retry with, or as a record with --record"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("approve")
+                                        .about("Approve a delegate, such as a marketplace escrow, to transfer the token of a mint you hold.")
+                                        .arg(blockhash_arg())
+                                        .arg(sign_only_arg())
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .help("Mint of the token"),
+                                        ).arg(
+                                            Arg::with_name("owner")
+                                                .long("owner")
+                                                .value_name("OWNER")
+                                                .takes_value(true)
+                                                .validator(is_valid_signer)
+                                                .help("Filepath or URL to a keypair of the token owner, defaults to you"),
+                                        ).arg(
+                                            Arg::with_name("delegate")
+                                                .long("delegate")
+                                                .value_name("DELEGATE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .help("Account allowed to transfer the token"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("revoke")
+                                        .about("Revoke the delegate of the token of a mint you hold.")
+                                        .arg(blockhash_arg())
+                                        .arg(sign_only_arg())
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .help("Mint of the token"),
+                                        ).arg(
+                                            Arg::with_name("owner")
+                                                .long("owner")
+                                                .value_name("OWNER")
+                                                .takes_value(true)
+                                                .validator(is_valid_signer)
+                                                .help("Filepath or URL to a keypair of the token owner, defaults to you"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("freeze")
+                                        .about("Freeze a token account, so the token cannot move until it is thawed.")
+                                        .arg(blockhash_arg())
+                                        .arg(sign_only_arg())
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .help("Mint of the token"),
+                                        ).arg(
+                                            Arg::with_name("account")
+                                                .long("account")
+                                                .value_name("ACCOUNT")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .help("Token account holding the token"),
+                                        ).arg(
+                                            Arg::with_name("freeze_authority")
+                                                .long("freeze-authority")
+                                                .value_name("FREEZE_AUTHORITY")
+                                                .takes_value(true)
+                                                .validator(is_valid_signer)
+                                                .help("Filepath or URL to a keypair of the freeze authority of the mint, defaults to you"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("thaw")
+                                        .about("Thaw a frozen token account.")
+                                        .arg(blockhash_arg())
+                                        .arg(sign_only_arg())
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .help("Mint of the token"),
+                                        ).arg(
+                                            Arg::with_name("account")
+                                                .long("account")
+                                                .value_name("ACCOUNT")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .help("Token account holding the token"),
+                                        ).arg(
+                                            Arg::with_name("freeze_authority")
+                                                .long("freeze-authority")
+                                                .value_name("FREEZE_AUTHORITY")
+                                                .takes_value(true)
+                                                .validator(is_valid_signer)
+                                                .help("Filepath or URL to a keypair of the freeze authority of the mint, defaults to you"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("validate_file")
+                                        .about("Check the input file of a bulk command offline, reporting every invalid entry.")
+                                        .arg(
+                                            Arg::with_name("command")
+                                                .long("command")
+                                                .value_name("COMMAND")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .possible_values(VALIDATED_COMMANDS)
+                                                .help("Command the file is for, `mints` for any command taking a mints file"),
+                                        ).arg(
+                                            Arg::with_name("file")
+                                                .long("file")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("Input file to check"),
+                                        ).arg(
+                                            Arg::with_name("manifests")
+                                                .long("manifests")
+                                                .takes_value(false)
+                                                .help("The file is a create_new_llamas --manifests file of { \"wallet\", \"uri\" } entries rather than llamas_new.json"),
+                                        ).arg(
+                                            Arg::with_name("prints")
+                                                .long("prints")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .default_value("prints.json")
+                                                .help("With create_new_llamas, the prints.json whose wallets are paired with the llamas of llamas_new.json, checked to be pubkeys as many as the llamas"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("check_metadata_exists")
+                                        .about("Split the mints of a mints file into those with metadata and those without, fetching the metadata in pages.")
+                                        .arg(
+                                            Arg::with_name("mints_file")
+                                                .long("mints-file")
+                                                .value_name("MINTS_FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("JSON array of mint pubkeys"),
+                                        ).arg(
+                                            Arg::with_name("verify_decode")
+                                                .long("verify-decode")
+                                                .takes_value(false)
+                                                .required(false)
+                                                .help("Also decode each metadata and list the mints whose metadata does not decode"),
+                                        ).arg(
+                                            Arg::with_name("output")
+                                                .long("output")
+                                                .value_name("OUTPUT")
+                                                .takes_value(true)
+                                                .possible_values(&["text", "json"])
+                                                .default_value("text")
+                                                .help("Output format"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("remove_creator")
+                                        .about("Remove a creator from a metadata, scaling the shares of the others back up to 100.")
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Mint of the Metadata"),
+                                        ).arg(
+                                            Arg::with_name("creator")
+                                                .long("creator")
+                                                .value_name("CREATOR")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Creator to remove"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("print_schema")
+                                        .about("Print the JSON Schema of the input file of a bulk command.")
+                                        .arg(
+                                            Arg::with_name("command")
+                                                .long("command")
+                                                .value_name("COMMAND")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .possible_values(SCHEMA_COMMANDS)
+                                                .help("Command to describe the input file of"),
+                                        ).arg(
+                                            Arg::with_name("manifests")
+                                                .long("manifests")
+                                                .takes_value(false)
+                                                .help("Describe the create_new_llamas --manifests file of { \"wallet\", \"uri\" } entries rather than llamas_new.json"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("bulk_reservation_lists")
+                                        .about("Summarize the spots, supply snapshot and reservations of many reservation lists.")
+                                        .arg(
+                                            Arg::with_name("keys_file")
+                                                .long("keys-file")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("JSON array of reservation list pubkeys"),
+                                        ).arg(
+                                            Arg::with_name("output")
+                                                .long("output")
+                                                .value_name("FORMAT")
+                                                .takes_value(true)
+                                                .possible_values(&["text", "json"])
+                                                .default_value("text")
+                                                .help("Output format"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("find_by_symbol")
+                                        .about("Print the mint and name of every puffed metadata with a symbol.")
+                                        .arg(
+                                            Arg::with_name("symbol")
+                                                .long("symbol")
+                                                .value_name("SYMBOL")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("Symbol to look for, matched exactly"),
+                                        ).arg(
+                                            Arg::with_name("include_burned")
+                                                .long("include-burned")
+                                                .takes_value(false)
+                                                .help("Also list mints with no supply left, labelled as burned"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("authority_scan")
+                                        .about("Count the metadata of every update authority, fetching only the authority of each account.")
+                                        .arg(
+                                            Arg::with_name("output")
+                                                .long("output")
+                                                .value_name("FORMAT")
+                                                .takes_value(true)
+                                                .possible_values(&["text", "json"])
+                                                .default_value("text")
+                                                .help("Output format"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("check_images")
+                                        .about("Fetch the JSON at the uri of every mint of a mints file and check that its image and animation_url are reachable.")
+                                        .arg(
+                                            Arg::with_name("mints_file")
+                                                .long("mints-file")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("JSON array of mint pubkeys"),
+                                        ).arg(
+                                            Arg::with_name("output")
+                                                .long("output")
+                                                .value_name("FORMAT")
+                                                .takes_value(true)
+                                                .possible_values(&["text", "json"])
+                                                .default_value("text")
+                                                .help("Output format"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("wallet_editions")
+                                        .about("List the editions of a master edition a wallet holds, with their edition numbers.")
+                                        .arg(
+                                            Arg::with_name("owner")
+                                                .long("owner")
+                                                .value_name("OWNER")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Wallet to list"),
+                                        ).arg(
+                                            Arg::with_name("master")
+                                                .long("master")
+                                                .value_name("MASTER_MINT")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Mint of the master edition"),
+                                        ).arg(
+                                            Arg::with_name("output")
+                                                .long("output")
+                                                .value_name("FORMAT")
+                                                .takes_value(true)
+                                                .possible_values(&["text", "json"])
+                                                .default_value("text")
+                                                .help("Output format"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("selftest")
+                                        .about("Check that the metadata and edition of a known mint decode and serialize back to the bytes on chain, showing where they differ if not.")
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Mint whose accounts to check"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("bootstrap")
+                                        .about("Ready a local validator for the other commands: airdrop SOL to the payer and optionally deploy a build of the token metadata program. Only runs against a --url on this machine.")
+                                        .arg(
+                                            Arg::with_name("sol")
+                                                .long("sol")
+                                                .value_name("SOL")
+                                                .takes_value(true)
+                                                .validator(is_parsable::<f64>)
+                                                .default_value("100")
+                                                .help("SOL to airdrop to the payer"),
+                                        ).arg(
+                                            Arg::with_name("program")
+                                                .long("program")
+                                                .value_name("SO_FILE")
+                                                .takes_value(true)
+                                                .help("Token metadata program build to deploy, e.g. target/deploy/spl_token_metadata.so"),
+                                        ).arg(
+                                            Arg::with_name("program_keypair")
+                                                .long("program-keypair")
+                                                .value_name("KEYPAIR")
+                                                .takes_value(true)
+                                                .requires("program")
+                                                .help("Keypair file of the address to deploy the program to [default: a new address]"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("broadcast_batch")
+                                        .about("Submit the transactions of a batch written by --output-unsigned-batch once their signatures have been added, reporting the outcome of each.")
+                                        .arg(
+                                            Arg::with_name("file")
+                                                .long("file")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("Batch file, with PUBKEY=SIGNATURE pairs added to the signatures list of each transaction"),
+                                        ).arg(
+                                            Arg::with_name("retries")
+                                                .long("retries")
+                                                .value_name("RETRIES")
+                                                .takes_value(true)
+                                                .validator(is_parsable::<usize>)
+                                                .default_value("3")
+                                                .help("Times to resend a transaction that failed for a reason other than its program"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("royalties")
+                                        .about("Print the seller fee of a mint, whether its primary sale happened and the lamports each creator receives from a sale at a given price.")
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Mint of the NFT"),
+                                        ).arg(
+                                            Arg::with_name("sale_price")
+                                                .long("sale-price")
+                                                .value_name("LAMPORTS")
+                                                .required(true)
+                                                .validator(is_parsable::<u64>)
+                                                .takes_value(true)
+                                                .help("Price of the sale in lamports"),
+                                        ).arg(
+                                            Arg::with_name("output")
+                                                .long("output")
+                                                .value_name("FORMAT")
+                                                .takes_value(true)
+                                                .possible_values(&["text", "json"])
+                                                .default_value("text")
+                                                .help("Output format"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("checksum")
+                                        .about("Record a hash of the metadata of every mint in a mints file, or check them against an earlier record to find metadata that changed since.")
+                                        .arg(
+                                            Arg::with_name("mints_file")
+                                                .long("mints-file")
+                                                .value_name("MINTS_FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("JSON array of mint pubkeys"),
+                                        ).arg(
+                                            Arg::with_name("manifest")
+                                                .long("manifest")
+                                                .value_name("FILE")
+                                                .takes_value(true)
+                                                .default_value("metadata_checksums.json")
+                                                .help("File to write the checksums to"),
+                                        ).arg(
+                                            Arg::with_name("verify")
+                                                .long("verify")
+                                                .value_name("MANIFEST")
+                                                .takes_value(true)
+                                                .help("Compare against the checksums in this file instead of writing them, exiting with an error if any metadata changed"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("bulk_burn")
+                                        .about("Burn the token of every NFT in a mints file that the owner holds and close its token account, reclaiming the rent. Cannot be undone, so it only runs with --yes.")
+                                        .arg(
+                                            Arg::with_name("mints_file")
+                                                .long("mints-file")
+                                                .value_name("MINTS_FILE")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("JSON array of mint pubkeys"),
+                                        ).arg(
+                                            Arg::with_name("owner")
+                                                .long("owner")
+                                                .value_name("OWNER")
+                                                .takes_value(true)
+                                                .validator(is_valid_signer)
+                                                .help("Filepath or URL to a keypair of the token owner, defaults to you"),
+                                        ).arg(
+                                            Arg::with_name("destination")
+                                                .long("destination")
+                                                .value_name("DESTINATION")
+                                                .takes_value(true)
+                                                .validator(is_valid_pubkey)
+                                                .help("Account the rent of the closed token accounts goes to [default: the owner]"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("marker_utilization")
+                                        .about("Read the edition markers of a master edition and print how many edition numbers are taken, how many are free and the first free ones.")
+                                        .arg(
+                                            Arg::with_name("mint")
+                                                .long("mint")
+                                                .value_name("MINT")
+                                                .required(true)
+                                                .validator(is_valid_pubkey)
+                                                .takes_value(true)
+                                                .help("Mint of the master edition"),
+                                        ).arg(
+                                            Arg::with_name("free")
+                                                .long("free")
+                                                .value_name("COUNT")
+                                                .takes_value(true)
+                                                .validator(is_parsable::<usize>)
+                                                .default_value("20")
+                                                .help("Number of free edition numbers to list"),
+                                        ))
+                                .subcommand(
+                                    SubCommand::with_name("scan")
+                                        .about("Print the key, mint and name of every metadata matching a filter, such as 'seller_fee > 500 && !mutable'.")
+                                        .arg(
+                                            Arg::with_name("filter")
+                                                .long("filter")
+                                                .value_name("EXPR")
+                                                .takes_value(true)
+                                                .required(true)
+                                                .help("Condition on name, symbol, uri, update_authority, mint, seller_fee, creators, verified, mutable and primary_sale, combined with ! && || and compared with == != < <= > >= contains"),
+                                        )).get_matches();
 
-    let client = RpcClient::new(
-        app_matches
-            .value_of("json_rpc_url")
-            .unwrap_or(&"https://api.devnet.solana.com".to_owned())
-            .to_owned(),
+    set_json_errors(app_matches.value_of("error_format") == Some("json"));
+    set_strict(app_matches.is_present("strict"));
+    set_dump_logs(app_matches.is_present("dump_logs"));
+    let mut program = MetadataProgram::new(
+        pubkey_of(&app_matches, "metadata_program").unwrap_or_else(spl_token_metadata::id),
     );
+    program.debug_pda = app_matches.is_present("debug_pda");
 
-    let payer = read_keypair_file(app_matches.value_of("keypair").unwrap()).unwrap();
+    let urls = app_matches
+        .values_of("json_rpc_url")
+        .map(|urls| urls.map(str::to_owned).collect())
+        .unwrap_or_else(|| vec!["https://api.devnet.solana.com".to_owned()]);
+    let client = FailoverClient::new(urls, app_matches.is_present("verbose"));
+
+    let mut wallet_manager = None;
+    let payer = signer_from_path(
+        &app_matches,
+        app_matches.value_of("keypair").unwrap(),
+        "keypair",
+        &mut wallet_manager,
+    )
+    .unwrap();
 
     let (sub_command, sub_matches) = app_matches.subcommand();
+    if !WITHOUT_METADATA_PROGRAM.contains(&sub_command) {
+        if let Err(err) = check_program_deployed(&client, &program.id) {
+            exit_with_error(err, json!({ "program": program.id.to_string() }));
+        }
+    }
     match (sub_command, sub_matches) {
         ("create_metadata_accounts", Some(arg_matches)) => {
-            let (metadata, metadata_key) = create_metadata_account_call(arg_matches, payer, client);
+            let (metadata, metadata_key) = create_metadata_account_call(
+                arg_matches,
+                payer,
+                client,
+                &program,
+                &mut wallet_manager,
+            );
             println!(
                 "Create metadata account with mint {:?} and key {:?} and name of {:?} and symbol of {:?}",
                 metadata.mint, metadata_key, metadata.data.name, metadata.data.symbol
             );
         }
         ("update_metadata_accounts", Some(arg_matches)) => {
-            let (metadata, metadata_key) = update_metadata_account_call(arg_matches, payer, client);
+            let (metadata, metadata_key) = update_metadata_account_call(
+                arg_matches,
+                payer,
+                client,
+                &program,
+                &mut wallet_manager,
+            );
             println!(
                 "Update metadata account with mint {:?} and key {:?} which now has URI of {:?}",
                 metadata.mint, metadata_key, metadata.data.uri
@@ -1595,52 +5821,572 @@ fn main() {
         }
         ("create_master_edition", Some(arg_matches)) => {
             let (master_edition, master_edition_key) =
-                master_edition_call(arg_matches, payer, client);
+                master_edition_call(arg_matches, payer, client, &program, &mut wallet_manager);
             println!(
                 "Created master edition {:?} with key {:?}",
                 master_edition, master_edition_key
             );
         }
         ("mint_new_edition_from_master_edition_via_token", Some(arg_matches)) => {
-            let (edition, edition_key, mint) =
-                mint_edition_via_token_call(arg_matches, payer, client);
+            let (edition, edition_key, mint) = mint_edition_via_token_call(
+                arg_matches,
+                payer,
+                client,
+                &program,
+                &mut wallet_manager,
+            );
             println!(
                 "New edition: {:?}\nParent edition: {:?}\nEdition number: {:?}\nToken mint: {:?}",
                 edition_key, edition.parent, edition.edition, mint
             );
         }
         ("show", Some(arg_matches)) => {
-            show(arg_matches, payer, client);
+            show(arg_matches, payer, client, &program);
         }
         ("show_reservation_list", Some(arg_matches)) => {
             show_reservation_list(arg_matches, payer, client);
         }
+        ("validate_reservation_list", Some(arg_matches)) => {
+            validate_reservation_list(arg_matches, payer, client, &program);
+        }
+        ("dump_reservation_lists", Some(arg_matches)) => {
+            dump_reservation_lists(arg_matches, payer, client, &program);
+        }
         ("mint_coins", Some(arg_matches)) => {
             mint_coins(arg_matches, payer, client);
         }
         ("puff_unpuffed_metadata", Some(arg_matches)) => {
-            puff_unpuffed_metadata(arg_matches, payer, client);
+            puff_unpuffed_metadata(arg_matches, payer, client, &program);
         }
         ("find_all_llamas", Some(arg_matches)) => {
-            find_all_llamas(arg_matches, payer, client);
+            find_all_llamas(arg_matches, payer, client, &program);
         }
 
         ("pull_llama_arweave_uris", Some(arg_matches)) => {
             pull_llama_arweave_uris(arg_matches, payer, client);
         }
         ("airdrop", Some(arg_matches)) => {
-            airdrop(arg_matches, payer, client);
+            airdrop(arg_matches, payer, client, &program, &mut wallet_manager);
         }
         ("create_new_llamas", Some(arg_matches)) => {
-            create_new_llamas(arg_matches, payer, client);
+            create_new_llamas(arg_matches, payer, client, &program);
         }
         ("update_new_llamas", Some(arg_matches)) => {
-            update_new_llamas(arg_matches, payer, client);
+            update_new_llamas(arg_matches, payer, client, &program, &mut wallet_manager);
         }
         ("file_refunds", Some(arg_matches)) => {
-            file_refund(arg_matches, payer, client);
+            file_refund(arg_matches, payer, client, &mut wallet_manager);
+        }
+        ("dump_metadata_keys", Some(arg_matches)) => {
+            dump_metadata_keys(arg_matches, payer, client, &program);
+        }
+        ("migrate_uris", Some(arg_matches)) => {
+            migrate_uris(arg_matches, payer, client, &program, &mut wallet_manager);
+        }
+        ("dedup_creators", Some(arg_matches)) => {
+            dedup_creators(arg_matches, payer, client, &program, &mut wallet_manager);
+        }
+        ("holder", Some(arg_matches)) => {
+            holder(arg_matches, payer, client);
+        }
+        ("inventory", Some(arg_matches)) => {
+            inventory(arg_matches, payer, client, &program);
+        }
+        ("fix_nulls", Some(arg_matches)) => {
+            fix_nulls(arg_matches, payer, client, &program, &mut wallet_manager);
+        }
+        ("audit_supply", Some(arg_matches)) => {
+            audit_supply(arg_matches, payer, client, &program);
+        }
+        ("strip_quotes", Some(arg_matches)) => {
+            strip_quotes(arg_matches, payer, client, &program, &mut wallet_manager);
+        }
+        ("bulk_master_edition", Some(arg_matches)) => {
+            bulk_master_edition(arg_matches, payer, client, &program, &mut wallet_manager);
+        }
+        ("approve", Some(arg_matches)) => {
+            approve(arg_matches, payer, client, &mut wallet_manager);
+        }
+        ("revoke", Some(arg_matches)) => {
+            revoke(arg_matches, payer, client, &mut wallet_manager);
+        }
+        ("freeze", Some(arg_matches)) => {
+            freeze(arg_matches, payer, client, &mut wallet_manager);
+        }
+        ("thaw", Some(arg_matches)) => {
+            thaw(arg_matches, payer, client, &mut wallet_manager);
+        }
+        ("validate_file", Some(arg_matches)) => {
+            validate_file(arg_matches);
+        }
+        ("check_metadata_exists", Some(arg_matches)) => {
+            check_metadata_exists(arg_matches, payer, client, &program);
+        }
+        ("remove_creator", Some(arg_matches)) => {
+            remove_creator(arg_matches, payer, client, &program, &mut wallet_manager);
+        }
+        ("print_schema", Some(arg_matches)) => {
+            print_schema(arg_matches);
+        }
+        ("bulk_reservation_lists", Some(arg_matches)) => {
+            bulk_reservation_lists(arg_matches, payer, client, &program);
+        }
+        ("find_by_symbol", Some(arg_matches)) => {
+            find_by_symbol(arg_matches, payer, client, &program);
+        }
+        ("authority_scan", Some(arg_matches)) => {
+            authority_scan(arg_matches, payer, client, &program);
+        }
+        ("check_images", Some(arg_matches)) => {
+            check_images(arg_matches, payer, client, &program);
+        }
+        ("wallet_editions", Some(arg_matches)) => {
+            wallet_editions(arg_matches, payer, client, &program);
+        }
+        ("selftest", Some(arg_matches)) => {
+            selftest(arg_matches, payer, client, &program);
+        }
+        ("bootstrap", Some(arg_matches)) => {
+            bootstrap(arg_matches, payer, client);
+        }
+        ("broadcast_batch", Some(arg_matches)) => {
+            broadcast_batch(arg_matches, payer, client);
+        }
+        ("royalties", Some(arg_matches)) => {
+            royalties(arg_matches, payer, client, &program);
+        }
+        ("checksum", Some(arg_matches)) => {
+            checksum(arg_matches, payer, client, &program);
+        }
+        ("bulk_burn", Some(arg_matches)) => {
+            bulk_burn(arg_matches, payer, client, &mut wallet_manager);
+        }
+        ("marker_utilization", Some(arg_matches)) => {
+            marker_utilization(arg_matches, payer, client, &program);
+        }
+        ("scan", Some(arg_matches)) => {
+            scan(arg_matches, payer, client, &program);
         }
 
         _ => unreachable!(),
     }
+    write_retry_metrics(sub_matches.unwrap_or(&app_matches));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {
+        spl_token_metadata::state::{
+            Reservation, ReservationListV2, EDITION_MARKER_BIT_SIZE, PREFIX,
+        },
+        spl_token_metadata_test_client::compute_and_print_pda_bump,
+    };
+
+    #[test]
+    fn ui_amounts_convert_to_base_units() {
+        assert_eq!(ui_amount_to_base_units("1.5", 9), Ok(1_500_000_000));
+        assert_eq!(ui_amount_to_base_units(".25", 2), Ok(25));
+        assert_eq!(ui_amount_to_base_units("3.", 2), Ok(300));
+        assert_eq!(ui_amount_to_base_units("0.000", 3), Ok(0));
+        assert_eq!(ui_amount_to_base_units("42", 0), Ok(42));
+
+        assert!(ui_amount_to_base_units("1.234", 2)
+            .unwrap_err()
+            .contains("more than the 2 decimals"));
+        assert!(ui_amount_to_base_units("0.5", 0).is_err());
+
+        assert_eq!(
+            ui_amount_to_base_units("18446744073709551615", 0),
+            Ok(u64::MAX)
+        );
+        assert!(ui_amount_to_base_units("18446744073.709551616", 9)
+            .unwrap_err()
+            .contains("overflows"));
+        assert!(ui_amount_to_base_units("20000000000", 9).is_err());
+
+        for invalid in &["", ".", "1.2.3", "-1", "1e9", " 1"] {
+            assert!(ui_amount_to_base_units(invalid, 9).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn raw_amounts_are_base_units() {
+        let app = || App::new("test").arg(Arg::with_name("raw").long("raw"));
+        let raw = app().get_matches_from(vec!["test", "--raw"]);
+        let ui = app().get_matches_from(vec!["test"]);
+
+        assert_eq!(amount_of(&raw, "15", 9), Ok(15));
+        assert!(amount_of(&raw, "1.5", 9).is_err());
+        assert_eq!(amount_of(&ui, "15", 9), Ok(15_000_000_000));
+    }
+
+    #[test]
+    fn uri_progress_drops_a_cut_off_line() {
+        let contents = concat!(
+            r#"{"index":4,"uri":"https://arweave.net/a","body":null,"metadata":"M1"}"#,
+            "\n",
+            r#"{"index":5,"uri":"https://arweave.net/b","body":"{}","metadata":"M2"}"#,
+            "\n",
+        );
+        let entries = read_uri_progress(contents).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1]["index"], 5);
+
+        let cut_off = format!("{}{}", contents, r#"{"index":6,"uri":"htt"#);
+        assert_eq!(read_uri_progress(&cut_off).unwrap().len(), 2);
+
+        let corrupt = format!("not json\n{}", contents);
+        assert!(read_uri_progress(&corrupt).is_err());
+    }
+
+    #[test]
+    fn pdas_match_mainnet() {
+        let program = MetadataProgram::default();
+        // Accounts of the deployed program, for the wrapped SOL mint and the participation
+        // trophy that airdrop prints from.
+        for (mint, metadata, edition, marker) in [
+            (
+                "So11111111111111111111111111111111111111112",
+                "6dM4TqWyWJsbx7obrdLcviBkTafD5E8av61zfU6jq57X",
+                "7r1W5yu5i7ev1wPNGsNuRLcdKW1sCy2x4rwyQkdi9ew2",
+                "4Y1mQRDnq8F8vrvtkgte6Pi1LFgRSqVRXtqh2eMsRyBs",
+            ),
+            (
+                "Gt2VHnTpWhczM2EvYQSVAf3BHCVNyR1q5yUGibzb6sEX",
+                "FkUg155rZcjSipE1AaFtR5BPUrgdyDw8Q84Lixhf7TWi",
+                "7n3ThF88osiG1QQ9hXgHLwNLr3s2Gj4nEXmfSF9sabk5",
+                "FMQN2YEt3JWKjuwJyXakbidfZ5XD6YVYqWzh4nV2FPRC",
+            ),
+        ] {
+            let mint = Pubkey::from_str(mint).unwrap();
+            assert_eq!(program.metadata_pda(&mint).to_string(), metadata);
+            assert_eq!(program.edition_pda(&mint).to_string(), edition);
+            assert_eq!(program.edition_marker_pda(&mint, 1).to_string(), marker);
+            assert_eq!(
+                program
+                    .edition_marker_pda(&mint, EDITION_MARKER_BIT_SIZE - 1)
+                    .to_string(),
+                marker
+            );
+        }
+    }
+
+    #[test]
+    fn pda_bumps_are_checked() {
+        let program_key = MetadataProgram::default().id;
+        let mint = Pubkey::new_unique();
+        let seeds: &[&[u8]] = &[PREFIX.as_bytes(), program_key.as_ref(), mint.as_ref()];
+        let (address, bump) = Pubkey::find_program_address(seeds, &program_key);
+        compute_and_print_pda_bump("Metadata", seeds, &program_key, &address, bump);
+
+        // The seeds in another order give a valid looking address the program will not sign for.
+        let swapped: &[&[u8]] = &[PREFIX.as_bytes(), mint.as_ref(), program_key.as_ref()];
+        let result = std::panic::catch_unwind(|| {
+            compute_and_print_pda_bump("Metadata", swapped, &program_key, &address, bump)
+        });
+        assert!(result.is_err());
+    }
+    #[test]
+    fn edition_numbers_follow_supply_and_skip_taken() {
+        // Printing starts right after the supply.
+        assert_eq!(next_edition_number(10, 1, |_| false), 11);
+        // Numbers this run sent but that have not confirmed are not reused.
+        assert_eq!(next_edition_number(10, 14, |_| false), 14);
+        // Prints by another process raise the supply past what this run has sent.
+        assert_eq!(next_edition_number(20, 14, |_| false), 21);
+        // Numbers already marked as printed are skipped.
+        assert_eq!(
+            next_edition_number(10, 1, |edition| edition == 11 || edition == 12),
+            13
+        );
+    }
+
+    #[test]
+    fn print_room_is_checked_against_max_supply() {
+        assert!(check_print_room(10, None, 1_000).is_ok());
+        assert!(check_print_room(10, Some(15), 5).is_ok());
+        assert!(check_print_room(10, Some(15), 6).is_err());
+        assert!(check_print_room(15, Some(15), 1).is_err());
+    }
+
+    #[test]
+    fn padded_data_is_unchanged() {
+        let data = Data {
+            name: "Llama".to_owned(),
+            symbol: "LLAMA".to_owned(),
+            uri: "https://arweave.net/llama".to_owned(),
+            seller_fee_basis_points: 500,
+            creators: None,
+        };
+        let mut padded = data.clone();
+        padded.name = format!("{:\0<32}", data.name);
+        padded.uri = format!("{:\0<200}", data.uri);
+
+        assert!(data_unchanged(&padded, &data));
+        padded.seller_fee_basis_points = 0;
+        assert!(!data_unchanged(&padded, &data));
+    }
+
+    #[test]
+    fn manifest_strings_are_unquoted() {
+        let arweave = json!({ "name": "Llama #1", "symbol": "LLAMA" });
+        let (name, symbol) =
+            manifest_name_and_symbol("https://arweave.net/llama", &arweave).unwrap();
+
+        assert_eq!(name, "Llama #1");
+        assert_eq!(symbol, "LLAMA");
+    }
+
+    #[test]
+    fn arweave_ids_resolve_to_gateway_urls() {
+        let id = "bWVzc2FnZS1mcm9tLXRoZS1sbGFtYS1kcm9wLTAwMDA";
+
+        assert_eq!(
+            arweave_manifest_url(id).unwrap(),
+            format!("https://arweave.net/{}", id)
+        );
+        assert!(arweave_manifest_url("https://arweave.net/llama").is_err());
+    }
+
+    #[test]
+    fn reservation_list_problems_are_reported() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let reservation = |address, spots_remaining, total_spots| Reservation {
+            address,
+            spots_remaining,
+            total_spots,
+        };
+        let mut res_list = ReservationListV2 {
+            key: Key::ReservationListV2,
+            master_edition: Pubkey::new_unique(),
+            supply_snapshot: Some(0),
+            reservations: vec![
+                reservation(a, 1, 2),
+                reservation(b, 2, 2),
+                reservation(Pubkey::default(), 0, 0),
+            ],
+            total_reservation_spots: 4,
+            current_reservation_spots: 4,
+        };
+        assert!(reservation_list_problems(&res_list).is_empty());
+
+        res_list.reservations.push(reservation(a, 1, 1));
+        res_list.current_reservation_spots = 5;
+
+        assert_eq!(reservation_list_problems(&res_list).len(), 3);
+    }
+
+    #[test]
+    fn reserved_editions_follow_the_list() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let reservation = |address, spots_remaining, total_spots| Reservation {
+            address,
+            spots_remaining,
+            total_spots,
+        };
+        let mut res_list = ReservationListV2 {
+            key: Key::ReservationListV2,
+            master_edition: Pubkey::new_unique(),
+            supply_snapshot: Some(10),
+            reservations: vec![reservation(a, 2, 2), reservation(b, 3, 3)],
+            total_reservation_spots: 5,
+            current_reservation_spots: 5,
+        };
+        // b's spots follow a's and are handed out from the last.
+        assert_eq!(reserved_edition(&res_list, &a), Some((12, 2)));
+        assert_eq!(reserved_edition(&res_list, &b), Some((15, 3)));
+        assert_eq!(reserved_edition(&res_list, &Pubkey::new_unique()), None);
+
+        res_list.reservations[0] = reservation(solana_program::system_program::id(), 0, 20);
+        assert_eq!(reserved_edition(&res_list, &b), Some((33, 3)));
+        res_list.reservations[1].spots_remaining = 0;
+        assert_eq!(reserved_edition(&res_list, &b), None);
+        res_list.supply_snapshot = None;
+        assert_eq!(reserved_edition(&res_list, &a), None);
+    }
+
+    #[test]
+    fn scan_checkpoint_round_trips() {
+        let checkpoint = ScanCheckpoint {
+            start: 100,
+            end: 200,
+            next: 142,
+            bad_metadata: vec![(
+                json!({ "name": "Llama #7" }),
+                Pubkey::new_unique().to_string(),
+            )],
+        };
+
+        let mut resumed = ScanCheckpoint::from_lines(&checkpoint.to_lines()).unwrap();
+        assert_eq!(resumed, checkpoint);
+
+        // An appended record moves the position, and a cut-off last line is dropped.
+        let mut lines = checkpoint.to_lines();
+        lines.push_str(&ScanCheckpoint::record(143, None));
+        lines.push_str(r#"{"next":1"#);
+        resumed.next = 143;
+        assert_eq!(ScanCheckpoint::from_lines(&lines), Some(resumed));
+
+        assert_eq!(ScanCheckpoint::from_lines("{\"next\":1}\n"), None);
+        assert_eq!(
+            ScanCheckpoint::from_lines(&format!("{}x\n{}", lines, lines)),
+            None
+        );
+    }
+
+    #[test]
+    fn reservation_list_exports_spots() {
+        let (key, address) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let res_list = ReservationListV2 {
+            key: Key::ReservationListV2,
+            master_edition: Pubkey::new_unique(),
+            supply_snapshot: Some(3),
+            reservations: vec![Reservation {
+                address,
+                spots_remaining: 1,
+                total_spots: 2,
+            }],
+            total_reservation_spots: 2,
+            current_reservation_spots: 2,
+        };
+
+        let exported = reservation_list_json(&key, Key::ReservationListV2, &res_list);
+
+        assert_eq!(exported["version"], "ReservationListV2");
+        assert_eq!(exported["supply_snapshot"], 3);
+        assert_eq!(exported["reservations"][0]["address"], address.to_string());
+        assert_eq!(exported["reservations"][0]["spots_remaining"], 1);
+    }
+
+    #[test]
+    fn names_are_numbered_from_template() {
+        assert_eq!(numbered_name("Llama #{n}", 42), "Llama #42");
+        assert!(is_name_template("Llama".to_owned()).is_err());
+        // A name the template makes too long is caught before the llama is created.
+        let name = numbered_name(&format!("{}{{n}}", "L".repeat(MAX_NAME_LENGTH)), 1);
+        assert!(check_field_lengths(&name, "LLAMA", "https://arweave.net/llama").is_err());
+    }
+
+    #[test]
+    fn airdrop_failures_reread_as_grants() {
+        let grants = vec![
+            (Pubkey::new_unique().to_string(), 2),
+            (Pubkey::new_unique().to_string(), 1),
+        ];
+        for name in &["airdrop_failures.json", "airdrop_failures.csv"] {
+            let path = std::env::temp_dir().join(name);
+            let path = path.to_str().unwrap();
+            write_airdrop_grants(path, &grants).unwrap();
+            assert_eq!(read_airdrop_grants(path), grants);
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn created_llamas_reread_from_record() {
+        let llamas = vec![
+            (Pubkey::new_unique(), Pubkey::new_unique()),
+            (Pubkey::new_unique(), Pubkey::new_unique()),
+        ];
+        let path = std::env::temp_dir().join("created_llamas.jsonl");
+        let path = path.to_str().unwrap();
+        let lines: String = llamas
+            .iter()
+            .map(|(mint, wallet)| {
+                format!(
+                    "{}\n",
+                    json!({
+                        "wallet": wallet.to_string(),
+                        "mint": mint.to_string(),
+                        "edition": null,
+                    })
+                )
+            })
+            .collect();
+        fs::write(path, lines).unwrap();
+        assert_eq!(read_record_file(path), llamas);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn llama_failures_reread_as_manifests() {
+        let wallet = PubkeyString(Pubkey::new_unique().to_string());
+        let entries = vec![
+            ManifestEntry {
+                wallet: wallet.clone(),
+                uri: UriString("https://arweave.net/a".to_owned()),
+                manifest: Some(ManifestJson(json!({ "name": "Llama", "symbol": "LLAMA" }))),
+                n: Some(7),
+            },
+            ManifestEntry {
+                wallet,
+                uri: UriString("https://arweave.net/b".to_owned()),
+                manifest: None,
+                n: Some(9),
+            },
+        ];
+        let path = std::env::temp_dir().join("llama_failures.json");
+        let path = path.to_str().unwrap();
+        fs::write(path, serde_json::to_string(&entries).unwrap()).unwrap();
+        // The names keep the n of the first run, whatever the offset of this one.
+        assert_eq!(
+            read_manifest_entries(path, &fs::read_to_string(path).unwrap(), 100),
+            entries
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn authorities_are_described() {
+        let program = MetadataProgram::default();
+        let wallet = Keypair::new().pubkey();
+        let pda = program.metadata_pda(&wallet);
+        let mut account =
+            solana_sdk::account::Account::new(1, 0, &solana_program::system_program::id());
+
+        assert_eq!(
+            describe_authority(&wallet, Some(&account)),
+            "system account, wallet address"
+        );
+        assert_eq!(
+            describe_authority(&pda, None),
+            "PDA, no private key can sign for it with no account"
+        );
+        account.executable = true;
+        assert_eq!(describe_authority(&pda, Some(&account)), "program");
+    }
+
+    #[test]
+    fn data_differences_ignore_padding() {
+        let requested = Data {
+            name: "Llama".to_owned(),
+            symbol: "LLAMA".to_owned(),
+            uri: "https://arweave.net/llama".to_owned(),
+            seller_fee_basis_points: 500,
+            creators: None,
+        };
+        let mut actual = Data {
+            name: format!("{:\0<32}", "Llama"),
+            ..requested.clone()
+        };
+        assert!(data_differences(&requested, &actual).is_empty());
+
+        actual.name = "Llam".to_owned();
+        actual.creators = Some(vec![]);
+        assert_eq!(
+            data_differences(&requested, &actual),
+            vec![
+                "name: \"Llama\" -> \"Llam\"".to_owned(),
+                "creators: None -> Some([])".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn fail_manifest_without_symbol() {
+        let arweave = json!({ "name": "Llama #1" });
+
+        assert!(manifest_name_and_symbol("https://arweave.net/llama", &arweave).is_err());
+    }
 }
@@ -0,0 +1,86 @@
+use {
+    crate::rpc::FailoverClient,
+    clap::ArgMatches,
+    serde_json::{json, Value},
+    solana_client::rpc_request::RpcRequest,
+    solana_program::instruction::Instruction,
+    solana_sdk::pubkey::Pubkey,
+    std::str::FromStr,
+};
+
+const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+
+/// Discriminator of the compute budget program's `SetComputeUnitPrice` instruction.
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+/// Builds a compute budget instruction paying `micro_lamports` per compute unit. The SDK this
+/// client is pinned to predates the compute budget program, so the instruction is encoded here.
+pub fn set_compute_unit_price(micro_lamports: u64) -> Instruction {
+    let mut data = vec![SET_COMPUTE_UNIT_PRICE];
+    data.extend_from_slice(&micro_lamports.to_le_bytes());
+    Instruction {
+        program_id: Pubkey::from_str(COMPUTE_BUDGET_PROGRAM_ID).unwrap(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Returns the priority fees, in micro-lamports per compute unit, paid in recent slots by
+/// transactions writing to any of `accounts`.
+pub fn get_recent_prioritization_fees(client: &FailoverClient, accounts: &[Pubkey]) -> Vec<u64> {
+    let addresses: Vec<String> = accounts.iter().map(|key| key.to_string()).collect();
+    let fees: Vec<Value> = client
+        .send(
+            RpcRequest::Custom {
+                method: "getRecentPrioritizationFees",
+            },
+            json!([addresses]),
+        )
+        .unwrap();
+    fees.iter()
+        .filter_map(|fee| fee["prioritizationFee"].as_u64())
+        .collect()
+}
+
+/// Nearest-rank percentile of `fees`, zero when there are none.
+fn fee_percentile(mut fees: Vec<u64>, percent: usize) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let rank = (percent * fees.len() + 99) / 100;
+    fees[rank.clamp(1, fees.len()) - 1]
+}
+
+/// With `--auto-priority-fee`, returns an instruction pricing compute units at the
+/// `--priority-fee-percentile` of recent fees paid for the accounts `instructions` write to.
+/// Returns `None` when the option is off or nobody has been paying a priority fee.
+pub fn priority_fee_instruction(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    instructions: &[Instruction],
+) -> Option<Instruction> {
+    if !app_matches.is_present("auto_priority_fee") {
+        return None;
+    }
+    let percent = app_matches
+        .value_of("priority_fee_percentile")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    let mut writable: Vec<Pubkey> = instructions
+        .iter()
+        .flat_map(|instruction| instruction.accounts.iter())
+        .filter(|meta| meta.is_writable)
+        .map(|meta| meta.pubkey)
+        .collect();
+    writable.sort();
+    writable.dedup();
+
+    let price = fee_percentile(get_recent_prioritization_fees(client, &writable), percent);
+    if price == 0 {
+        None
+    } else {
+        Some(set_compute_unit_price(price))
+    }
+}
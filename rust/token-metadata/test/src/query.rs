@@ -0,0 +1,1074 @@
+use {
+    super::{get_metadata_account_or_exit, http_client, read_mints_file, strip_padding},
+    crate::{
+        csv::write_csv,
+        error::{exit_with_error, report_undecodable, CliError},
+        filter::Filter,
+        rpc::FailoverClient,
+        scan::{get_program_account_slices, memcmp_filter, page_size_of, scan_program_accounts},
+        version::decode_metadata,
+    },
+    borsh::BorshSerialize,
+    clap::ArgMatches,
+    serde_json::{json, Value},
+    solana_account_decoder::UiAccountData,
+    solana_clap_utils::input_parsers::{pubkey_of, value_of},
+    solana_client::{
+        client_error::reqwest,
+        rpc_request::{RpcRequest, TokenAccountsFilter},
+        rpc_response::{Response, RpcTokenAccountBalance},
+    },
+    solana_program::{borsh::try_from_slice_unchecked, program_pack::Pack},
+    solana_sdk::{
+        hash::{hash, Hash},
+        pubkey::Pubkey,
+        signature::Signer,
+    },
+    spl_token::state::{Account, Mint},
+    spl_token_metadata::state::{
+        Data, Edition, EditionMarker, Key, MasterEditionV1, MasterEditionV2, Metadata,
+        EDITION_MARKER_BIT_SIZE, MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH,
+    },
+    spl_token_metadata_test_client::MetadataProgram,
+    std::{
+        collections::{HashMap, HashSet},
+        convert::TryInto,
+        fs, io,
+        str::FromStr,
+    },
+};
+
+/// Returns the token accounts of `mint` with a nonzero balance, largest first.
+pub fn get_token_largest_accounts(
+    client: &FailoverClient,
+    mint: &Pubkey,
+) -> Vec<RpcTokenAccountBalance> {
+    let response: Response<Vec<RpcTokenAccountBalance>> = client
+        .send(
+            RpcRequest::Custom {
+                method: "getTokenLargestAccounts",
+            },
+            json!([mint.to_string()]),
+        )
+        .unwrap();
+    response
+        .value
+        .into_iter()
+        .filter(|balance| balance.amount.amount != "0")
+        .collect()
+}
+
+pub fn holder(app_matches: &ArgMatches, _payer: Box<dyn Signer>, client: FailoverClient) {
+    let mint_key = pubkey_of(app_matches, "mint").unwrap();
+    let mint_account = client.get_account(&mint_key).unwrap();
+    let mint = Mint::unpack(&mint_account.data).unwrap();
+
+    let largest = get_token_largest_accounts(&client, &mint_key);
+    let top = match largest.first() {
+        Some(top) if mint.supply > 0 => top,
+        _ => {
+            println!("Mint {} has no holders, it has been burned", mint_key);
+            return;
+        }
+    };
+    let token_account_key = Pubkey::from_str(&top.address).unwrap();
+    let token_account =
+        Account::unpack_unchecked(&client.get_account(&token_account_key).unwrap().data).unwrap();
+
+    if mint.decimals == 0 && mint.supply == 1 {
+        println!("Holder: {}", token_account.owner);
+        println!("Token account: {}", token_account_key);
+    } else {
+        println!(
+            "Mint {} is fungible with supply {} and {} accounts holding it",
+            mint_key,
+            mint.supply,
+            largest.len()
+        );
+        println!(
+            "Top holder: {} with {} in token account {}",
+            token_account.owner, top.amount.ui_amount_string, token_account_key
+        );
+    }
+}
+
+/// Returns the `(token account, mint)` pairs of every token account of `owner` holding `amount`
+/// tokens of a zero decimal mint: "1" for the NFTs it holds, "0" for the empty accounts left
+/// behind when one was burned or sent away.
+pub fn get_nft_token_accounts(
+    client: &FailoverClient,
+    owner: &Pubkey,
+    amount: &str,
+) -> Vec<(Pubkey, Pubkey)> {
+    client
+        .get_token_accounts_by_owner(owner, TokenAccountsFilter::ProgramId(spl_token::id()))
+        .unwrap()
+        .iter()
+        .filter_map(|keyed_account| match &keyed_account.account.data {
+            UiAccountData::Json(parsed) => {
+                let info = &parsed.parsed["info"];
+                if info["tokenAmount"]["amount"] == amount && info["tokenAmount"]["decimals"] == 0 {
+                    Some((
+                        Pubkey::from_str(&keyed_account.pubkey).unwrap(),
+                        Pubkey::from_str(info["mint"].as_str().unwrap()).unwrap(),
+                    ))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the mints of `mints` that were burned, having no supply left or no account at all.
+/// The mints are fetched `page_size` at a time.
+pub fn burned_mints(
+    client: &FailoverClient,
+    mints: &[Pubkey],
+    page_size: usize,
+) -> HashSet<Pubkey> {
+    let mut burned = HashSet::new();
+    for page in mints.chunks(page_size) {
+        let accounts = client.get_multiple_accounts(page).unwrap();
+        for (mint, account) in page.iter().zip(accounts) {
+            let supply = account
+                .and_then(|account| Mint::unpack(&account.data).ok())
+                .map(|mint| mint.supply);
+            if supply.unwrap_or(0) == 0 {
+                burned.insert(*mint);
+            }
+        }
+    }
+    burned
+}
+
+/// Whether `mint` is `master_mint` itself or an edition printed from it.
+fn is_in_collection(
+    client: &FailoverClient,
+    program: &MetadataProgram,
+    mint: &Pubkey,
+    master_mint: &Pubkey,
+) -> bool {
+    if mint == master_mint {
+        return true;
+    }
+    match client.get_account(&program.edition_pda(mint)) {
+        Ok(account) if account.data[0] == Key::EditionV1 as u8 => {
+            let edition: Edition = try_from_slice_unchecked(&account.data).unwrap();
+            edition.parent == program.edition_pda(master_mint)
+        }
+        _ => false,
+    }
+}
+
+pub fn inventory(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let owner = pubkey_of(app_matches, "owner").unwrap();
+    let collection = pubkey_of(app_matches, "collection");
+    let output = app_matches.value_of("output").unwrap();
+    let include_burned = app_matches.is_present("include_burned");
+
+    let mut token_accounts = get_nft_token_accounts(&client, &owner, "1");
+    let mut burned = HashSet::new();
+    if include_burned {
+        // Of the empty accounts, only those whose mint has no supply left held a burned NFT.
+        let empty = get_nft_token_accounts(&client, &owner, "0");
+        let mints: Vec<Pubkey> = empty.iter().map(|(_, mint)| *mint).collect();
+        burned = burned_mints(&client, &mints, page_size_of(app_matches));
+        token_accounts.extend(empty.into_iter().filter(|(_, mint)| burned.contains(mint)));
+    }
+    let mut items: Vec<Value> = vec![];
+    for (token_account, mint) in token_accounts {
+        if let Some(master_mint) = &collection {
+            if !is_in_collection(&client, program, &mint, master_mint) {
+                continue;
+            }
+        }
+        let metadata_key = program.metadata_pda(&mint);
+        let metadata: Metadata = match client.get_account(&metadata_key) {
+            Ok(account) => try_from_slice_unchecked(&account.data).unwrap(),
+            Err(_) => continue,
+        };
+        items.push(json!({
+            "mint": mint.to_string(),
+            "token_account": token_account.to_string(),
+            "metadata": metadata_key.to_string(),
+            "name": strip_padding(&metadata.data.name),
+            "symbol": strip_padding(&metadata.data.symbol),
+            "uri": strip_padding(&metadata.data.uri),
+            "burned": burned.contains(&mint),
+        }));
+    }
+
+    if output == "json" {
+        println!("{}", serde_json::to_string_pretty(&items).unwrap());
+    } else if output == "csv" {
+        let columns = [
+            "mint",
+            "token_account",
+            "metadata",
+            "name",
+            "symbol",
+            "uri",
+            "burned",
+        ];
+        write_csv(&mut io::stdout(), &columns, &items).unwrap();
+    } else {
+        for item in &items {
+            println!(
+                "{} {} {}{}",
+                item["name"].as_str().unwrap(),
+                item["mint"].as_str().unwrap(),
+                item["uri"].as_str().unwrap(),
+                if item["burned"] == true {
+                    " (burned)"
+                } else {
+                    ""
+                }
+            );
+        }
+        let held = items.iter().filter(|item| item["burned"] == false).count();
+        println!("{} NFTs held by {}", held, owner);
+        if include_burned {
+            println!("{} burned", items.len() - held);
+        }
+    }
+}
+
+/// Returns every edition printed from the master edition at `master_edition_key`.
+pub fn get_editions_of(
+    client: &FailoverClient,
+    program: &MetadataProgram,
+    master_edition_key: &Pubkey,
+    page_size: usize,
+) -> Vec<(Pubkey, Edition)> {
+    scan_program_accounts(
+        client,
+        &program.id,
+        vec![
+            memcmp_filter(0, &[Key::EditionV1 as u8]),
+            memcmp_filter(1, master_edition_key.as_ref()),
+        ],
+        page_size,
+    )
+    .map(|(key, account)| (key, try_from_slice_unchecked(&account.data).unwrap()))
+    .collect()
+}
+
+pub fn audit_supply(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let master_mint = pubkey_of(app_matches, "mint").unwrap();
+    let master_edition_key = program.edition_pda(&master_mint);
+    let master_edition_account = match client.get_account(&master_edition_key) {
+        Ok(account) => account,
+        Err(err) => exit_with_error(err.into(), json!({ "mint": master_mint.to_string() })),
+    };
+    let supply = if master_edition_account.data[0] == Key::MasterEditionV1 as u8 {
+        let master_edition: MasterEditionV1 =
+            try_from_slice_unchecked(&master_edition_account.data).unwrap();
+        master_edition.supply
+    } else if master_edition_account.data[0] == Key::MasterEditionV2 as u8 {
+        let master_edition: MasterEditionV2 =
+            try_from_slice_unchecked(&master_edition_account.data).unwrap();
+        master_edition.supply
+    } else {
+        exit_with_error(
+            CliError::InvalidInput(format!("{} is not a master edition mint", master_mint)),
+            json!({ "mint": master_mint.to_string() }),
+        )
+    };
+
+    let editions = get_editions_of(
+        &client,
+        program,
+        &master_edition_key,
+        page_size_of(app_matches),
+    );
+    let highest = editions.iter().map(|(_, e)| e.edition).max().unwrap_or(0);
+    println!("Master edition: {}", master_edition_key);
+    println!("Recorded supply: {}", supply);
+    println!("Editions found: {}", editions.len());
+    println!("Highest edition number: {}", highest);
+
+    if editions.len() as u64 == supply {
+        println!("Supply is consistent");
+    } else {
+        println!(
+            "Supply mismatch: master records {} but {} editions exist",
+            supply,
+            editions.len()
+        );
+        std::process::exit(1);
+    }
+}
+
+pub fn check_metadata_exists(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let mints = read_mints_file(app_matches.value_of("mints_file").unwrap());
+    let verify_decode = app_matches.is_present("verify_decode");
+    let output = app_matches.value_of("output").unwrap();
+
+    let mut with_metadata = vec![];
+    let mut without_metadata = vec![];
+    let mut undecodable = vec![];
+    for page in mints.chunks(page_size_of(app_matches)) {
+        let metadata_keys: Vec<Pubkey> =
+            page.iter().map(|mint| program.metadata_pda(mint)).collect();
+        let accounts = client.get_multiple_accounts(&metadata_keys).unwrap();
+        for (mint, account) in page.iter().zip(accounts) {
+            match account {
+                Some(account) if verify_decode && decode_metadata(&account.data).is_err() => {
+                    undecodable.push(mint.to_string())
+                }
+                Some(_) => with_metadata.push(mint.to_string()),
+                None => without_metadata.push(mint.to_string()),
+            }
+        }
+    }
+
+    if output == "json" {
+        let mut lists = json!({
+            "with_metadata": with_metadata,
+            "without_metadata": without_metadata,
+        });
+        if verify_decode {
+            lists["undecodable"] = json!(undecodable);
+        }
+        println!("{}", serde_json::to_string_pretty(&lists).unwrap());
+    } else {
+        println!("With metadata ({}):", with_metadata.len());
+        for mint in &with_metadata {
+            println!("  {}", mint);
+        }
+        println!("Without metadata ({}):", without_metadata.len());
+        for mint in &without_metadata {
+            println!("  {}", mint);
+        }
+        if verify_decode {
+            println!("Metadata that does not decode ({}):", undecodable.len());
+            for mint in &undecodable {
+                println!("  {}", mint);
+            }
+        }
+    }
+}
+
+/// Offsets of the fixed fields of a `Metadata`, for queries that fetch a slice of it rather
+/// than the whole account.
+pub const UPDATE_AUTHORITY_OFFSET: usize = 1;
+pub const MINT_OFFSET: usize = 1 + 32;
+pub const NAME_OFFSET: usize = 1 + 32 + 32;
+
+/// Offset of the symbol in a puffed `Metadata`: the key byte, the update authority and mint,
+/// then the name as a four byte length and `MAX_NAME_LENGTH` bytes. Metadata created before
+/// puffing stores the name at its own length, so its symbol sits elsewhere and is not found
+/// until `puff_unpuffed_metadata` has run over it.
+pub const PUFFED_SYMBOL_OFFSET: usize = NAME_OFFSET + 4 + MAX_NAME_LENGTH;
+
+/// The symbol as a puffed `Metadata` stores it: a four byte little endian length of
+/// `MAX_SYMBOL_LENGTH` followed by the symbol padded with nulls to that length.
+pub fn puffed_symbol_bytes(symbol: &str) -> Vec<u8> {
+    let mut bytes = (MAX_SYMBOL_LENGTH as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(symbol.as_bytes());
+    bytes.resize(4 + MAX_SYMBOL_LENGTH, 0);
+    bytes
+}
+
+pub fn find_by_symbol(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let symbol = app_matches.value_of("symbol").unwrap();
+    if symbol.len() > MAX_SYMBOL_LENGTH {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "Symbol {} is longer than {} bytes",
+                symbol, MAX_SYMBOL_LENGTH
+            )),
+            json!({ "symbol": symbol }),
+        );
+    }
+    let filters = vec![
+        memcmp_filter(0, &[Key::MetadataV1 as u8]),
+        memcmp_filter(PUFFED_SYMBOL_OFFSET, &puffed_symbol_bytes(symbol)),
+    ];
+    // Only the mint and the puffed name are fetched, the rest of the account is not needed.
+    let slices = get_program_account_slices(
+        &client,
+        &program.id,
+        filters,
+        MINT_OFFSET,
+        32 + 4 + MAX_NAME_LENGTH,
+    );
+    let mut found = vec![];
+    for (key, slice) in &slices {
+        match decode_mint_and_name(slice) {
+            Some(item) => found.push(item),
+            None => report_undecodable(key, "the mint or name is malformed"),
+        }
+    }
+    let mints: Vec<Pubkey> = found.iter().map(|(mint, _)| *mint).collect();
+    let burned = burned_mints(&client, &mints, page_size_of(app_matches));
+    let include_burned = app_matches.is_present("include_burned");
+    let mut listed = 0;
+    for (mint, name) in &found {
+        if !burned.contains(mint) {
+            println!("{} {}", mint, name);
+        } else if include_burned {
+            println!("{} {} (burned)", mint, name);
+        } else {
+            continue;
+        }
+        listed += 1;
+    }
+    println!("Found {} metadata with symbol {}", listed, symbol);
+}
+
+/// Prints the metadata key, mint and name of every metadata account matching `--filter`,
+/// decoding the accounts a page at a time as they are fetched.
+pub fn scan(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let input = app_matches.value_of("filter").unwrap();
+    let filter = Filter::parse(input).unwrap_or_else(|err| {
+        exit_with_error(
+            CliError::InvalidInput(format!("Invalid filter: {}", err)),
+            json!({ "filter": input }),
+        )
+    });
+    let accounts = scan_program_accounts(
+        &client,
+        &program.id,
+        vec![memcmp_filter(0, &[Key::MetadataV1 as u8])],
+        page_size_of(app_matches),
+    );
+    let mut found = 0;
+    for (key, account) in accounts {
+        match decode_metadata(&account.data) {
+            Ok(metadata) if filter.matches(&metadata) => {
+                println!(
+                    "{} {} {}",
+                    key,
+                    metadata.mint,
+                    strip_padding(&metadata.data.name)
+                );
+                found += 1;
+            }
+            Ok(_) => {}
+            Err(err) => report_undecodable(&key, &err),
+        }
+    }
+    println!("Found {} metadata matching {}", found, input);
+}
+
+/// Decodes a slice starting at the mint of a `Metadata` into the mint and its name, reading the
+/// borsh length of the name rather than assuming it is puffed.
+pub fn decode_mint_and_name(slice: &[u8]) -> Option<(Pubkey, String)> {
+    let mint = Pubkey::new(slice.get(..32)?);
+    let length = u32::from_le_bytes(slice.get(32..36)?.try_into().ok()?) as usize;
+    let name = std::str::from_utf8(slice.get(36..36 + length)?).ok()?;
+    Some((mint, strip_padding(name).to_owned()))
+}
+
+/// Counts the metadata accounts of every update authority, fetching only the 32 bytes of the
+/// authority from each.
+pub fn authority_scan(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let output = app_matches.value_of("output").unwrap();
+    let slices = get_program_account_slices(
+        &client,
+        &program.id,
+        vec![memcmp_filter(0, &[Key::MetadataV1 as u8])],
+        UPDATE_AUTHORITY_OFFSET,
+        32,
+    );
+    let mut counts: HashMap<Pubkey, usize> = HashMap::new();
+    for (_, slice) in &slices {
+        *counts.entry(Pubkey::new(slice)).or_default() += 1;
+    }
+    let mut counts: Vec<(Pubkey, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    if output == "json" {
+        let counts: Vec<Value> = counts
+            .iter()
+            .map(|(authority, count)| json!({ "update_authority": authority.to_string(), "metadata": count }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&counts).unwrap());
+    } else {
+        for (authority, count) in &counts {
+            println!("{} {}", authority, count);
+        }
+        println!(
+            "{} metadata across {} update authorities",
+            slices.len(),
+            counts.len()
+        );
+    }
+}
+
+/// Checks that `url` answers with a success status. HEAD is tried first so assets are not
+/// downloaded, then GET for servers that do not allow HEAD.
+pub fn check_url(http: &reqwest::blocking::Client, url: &str) -> Result<(), String> {
+    let mut response = http.head(url).send().map_err(|err| err.to_string())?;
+    if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED {
+        response = http.get(url).send().map_err(|err| err.to_string())?;
+    }
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(response.status().to_string())
+    }
+}
+
+/// Fetches the JSON at the uri of `metadata` and checks its `image` and `animation_url`, returning
+/// the `(asset, url, error)` of every part that is broken.
+fn broken_assets(
+    http: &reqwest::blocking::Client,
+    metadata: &Metadata,
+) -> Vec<(&'static str, String, String)> {
+    let uri = strip_padding(&metadata.data.uri).to_owned();
+    let manifest: Value = match http
+        .get(&uri)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json())
+    {
+        Ok(manifest) => manifest,
+        Err(err) => return vec![("metadata", uri, err.to_string())],
+    };
+    ["image", "animation_url"]
+        .iter()
+        .filter_map(|asset| {
+            let url = manifest[*asset].as_str()?;
+            check_url(http, url)
+                .err()
+                .map(|err| (*asset, url.to_owned(), err))
+        })
+        .collect()
+}
+
+pub fn check_images(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let mints = read_mints_file(app_matches.value_of("mints_file").unwrap());
+    let output = app_matches.value_of("output").unwrap();
+    let http = http_client();
+
+    let mut broken: Vec<Value> = vec![];
+    let mut broken_mints = 0;
+    for page in mints.chunks(page_size_of(app_matches)) {
+        let metadata_keys: Vec<Pubkey> =
+            page.iter().map(|mint| program.metadata_pda(mint)).collect();
+        let accounts = client.get_multiple_accounts(&metadata_keys).unwrap();
+        for (mint, account) in page.iter().zip(accounts) {
+            let metadata = match account.map(|account| decode_metadata(&account.data)) {
+                Some(Ok(metadata)) => metadata,
+                Some(Err(err)) => {
+                    report_undecodable(&program.metadata_pda(mint), &err);
+                    continue;
+                }
+                None => {
+                    // On stderr, so it does not mix into the `--output json` report.
+                    eprintln!("Skipping {} because it has no metadata", mint);
+                    continue;
+                }
+            };
+            let assets = broken_assets(&http, &metadata);
+            if !assets.is_empty() {
+                broken_mints += 1;
+            }
+            for (asset, url, error) in assets {
+                if output == "text" {
+                    println!("{} {} {}: {}", mint, asset, url, error);
+                }
+                broken.push(json!({
+                    "mint": mint.to_string(),
+                    "asset": asset,
+                    "url": url,
+                    "error": error,
+                }));
+            }
+        }
+    }
+
+    if output == "json" {
+        println!("{}", serde_json::to_string_pretty(&broken).unwrap());
+    } else {
+        println!(
+            "{} of {} mints have a broken asset",
+            broken_mints,
+            mints.len()
+        );
+    }
+}
+
+/// Returns the `(edition number, mint)` of every edition of `master_mint` among `mints`, in
+/// edition order. The edition accounts are fetched `page_size` at a time.
+pub fn editions_among(
+    client: &FailoverClient,
+    program: &MetadataProgram,
+    mints: &[Pubkey],
+    master_mint: &Pubkey,
+    page_size: usize,
+) -> Vec<(u64, Pubkey)> {
+    let master_edition_key = program.edition_pda(master_mint);
+    let mut editions = vec![];
+    for page in mints.chunks(page_size) {
+        let edition_keys: Vec<Pubkey> = page.iter().map(|mint| program.edition_pda(mint)).collect();
+        let accounts = client.get_multiple_accounts(&edition_keys).unwrap();
+        for (mint, account) in page.iter().zip(accounts) {
+            if let Some(account) =
+                account.filter(|a| a.data.first() == Some(&(Key::EditionV1 as u8)))
+            {
+                let edition: Edition = try_from_slice_unchecked(&account.data).unwrap();
+                if edition.parent == master_edition_key {
+                    editions.push((edition.edition, *mint));
+                }
+            }
+        }
+    }
+    editions.sort();
+    editions
+}
+
+pub fn wallet_editions(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let owner = pubkey_of(app_matches, "owner").unwrap();
+    let master_mint = pubkey_of(app_matches, "master").unwrap();
+
+    let token_accounts = get_nft_token_accounts(&client, &owner, "1");
+    let mints: Vec<Pubkey> = token_accounts.iter().map(|(_, mint)| *mint).collect();
+    let editions = editions_among(
+        &client,
+        program,
+        &mints,
+        &master_mint,
+        page_size_of(app_matches),
+    );
+    let items: Vec<Value> = editions
+        .iter()
+        .map(|(edition, mint)| {
+            let token_account = token_accounts.iter().find(|(_, m)| m == mint).unwrap().0;
+            json!({
+                "edition": edition,
+                "mint": mint.to_string(),
+                "token_account": token_account.to_string(),
+            })
+        })
+        .collect();
+
+    if app_matches.value_of("output") == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&items).unwrap());
+    } else {
+        for (edition, mint) in &editions {
+            println!("Edition {} {}", edition, mint);
+        }
+        println!(
+            "{} holds {} editions of {}",
+            owner,
+            editions.len(),
+            master_mint
+        );
+    }
+}
+
+/// Lamports of a `sale_price` sale that go to the creators: all of it on the primary sale, and
+/// the `seller_fee_basis_points` cut on any sale after it.
+pub fn royalty_pool(data: &Data, primary_sale_happened: bool, sale_price: u64) -> u64 {
+    if !primary_sale_happened {
+        return sale_price;
+    }
+    (sale_price as u128 * data.seller_fee_basis_points as u128 / 10_000) as u64
+}
+
+/// Splits `pool` among the creators of `data` by share, rounding each amount down.
+pub fn creator_royalties(data: &Data, pool: u64) -> Vec<(Pubkey, u8, u64)> {
+    data.creators
+        .iter()
+        .flatten()
+        .map(|c| {
+            let amount = (pool as u128 * c.share as u128 / 100) as u64;
+            (c.address, c.share, amount)
+        })
+        .collect()
+}
+
+pub fn royalties(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let mint = pubkey_of(app_matches, "mint").unwrap();
+    let sale_price = value_of::<u64>(app_matches, "sale_price").unwrap();
+    let metadata_key = program.metadata_pda(&mint);
+    let account = get_metadata_account_or_exit(&client, &mint, &metadata_key);
+    let metadata = decode_metadata(&account.data).unwrap();
+    let pool = royalty_pool(&metadata.data, metadata.primary_sale_happened, sale_price);
+    let royalties = creator_royalties(&metadata.data, pool);
+
+    if app_matches.value_of("output") == Some("json") {
+        let creators: Vec<Value> = royalties
+            .iter()
+            .map(|(address, share, amount)| {
+                json!({ "address": address.to_string(), "share": share, "lamports": amount })
+            })
+            .collect();
+        let report = json!({
+            "mint": mint.to_string(),
+            "sale_price": sale_price,
+            "seller_fee_basis_points": metadata.data.seller_fee_basis_points,
+            "primary_sale_happened": metadata.primary_sale_happened,
+            "royalties": pool,
+            "creators": creators,
+        });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return;
+    }
+    println!(
+        "Seller fee: {} basis points",
+        metadata.data.seller_fee_basis_points
+    );
+    println!("Primary sale happened: {}", metadata.primary_sale_happened);
+    if metadata.primary_sale_happened {
+        println!("Royalties on a sale for {} lamports: {}", sale_price, pool);
+    } else {
+        println!(
+            "Royalties on a sale for {} lamports: {}, the primary sale goes to the creators in full",
+            sale_price, pool
+        );
+    }
+    if royalties.is_empty() {
+        println!("{} has no creators to receive them", mint);
+    }
+    for (address, share, amount) in &royalties {
+        println!("  {} ({}%): {}", address, share, amount);
+    }
+}
+
+/// A hash of `data` that stays the same as long as what it says does: the name, symbol and uri
+/// without their padding, the seller fee and the creators with their shares and verified flags.
+pub fn data_checksum(data: &Data) -> Hash {
+    let canonical = Data {
+        name: strip_padding(&data.name).to_owned(),
+        symbol: strip_padding(&data.symbol).to_owned(),
+        uri: strip_padding(&data.uri).to_owned(),
+        ..data.clone()
+    };
+    hash(&canonical.try_to_vec().unwrap())
+}
+
+/// The checksum of the metadata of each of `mints`, or `None` for those without metadata that
+/// decodes.
+fn metadata_checksums(
+    client: &FailoverClient,
+    program: &MetadataProgram,
+    mints: &[Pubkey],
+    page_size: usize,
+) -> Vec<(Pubkey, Option<Hash>)> {
+    let mut checksums = vec![];
+    for page in mints.chunks(page_size) {
+        let metadata_keys: Vec<Pubkey> =
+            page.iter().map(|mint| program.metadata_pda(mint)).collect();
+        let accounts = client.get_multiple_accounts(&metadata_keys).unwrap();
+        for (mint, account) in page.iter().zip(accounts) {
+            let checksum = account
+                .and_then(|account| decode_metadata(&account.data).ok())
+                .map(|metadata| data_checksum(&metadata.data));
+            checksums.push((*mint, checksum));
+        }
+    }
+    checksums
+}
+
+/// Writes the checksum of the metadata of every mint in `--mints-file` to `--manifest`, or with
+/// `--verify` compares them against an earlier manifest and reports the mints whose metadata
+/// changed since.
+pub fn checksum(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let mints = read_mints_file(app_matches.value_of("mints_file").unwrap());
+    let checksums = metadata_checksums(&client, program, &mints, page_size_of(app_matches));
+
+    let path = match app_matches.value_of("verify") {
+        Some(path) => path,
+        None => {
+            let path = app_matches.value_of("manifest").unwrap();
+            let manifest: serde_json::Map<String, Value> = checksums
+                .iter()
+                .map(|(mint, checksum)| (mint.to_string(), json!(checksum.map(|c| c.to_string()))))
+                .collect();
+            if let Err(err) = fs::write(path, serde_json::to_string_pretty(&manifest).unwrap()) {
+                exit_with_error(err.into(), json!({ "manifest": path }));
+            }
+            println!(
+                "Wrote the checksums of {} mints to {}",
+                checksums.len(),
+                path
+            );
+            return;
+        }
+    };
+    let manifest: HashMap<String, Option<String>> = fs::read_to_string(path)
+        .map_err(CliError::from)
+        .and_then(|contents| serde_json::from_str(&contents).map_err(CliError::from))
+        .unwrap_or_else(|err| exit_with_error(err, json!({ "manifest": path })));
+
+    let mut changed = 0;
+    for (mint, checksum) in &checksums {
+        let recorded = match manifest.get(&mint.to_string()) {
+            Some(recorded) => recorded,
+            None => {
+                println!("{} is not in {}", mint, path);
+                continue;
+            }
+        };
+        let current = checksum.map(|c| c.to_string());
+        if *recorded != current {
+            println!(
+                "{} changed: {} -> {}",
+                mint,
+                recorded.as_deref().unwrap_or("no metadata"),
+                current.as_deref().unwrap_or("no metadata")
+            );
+            changed += 1;
+        }
+    }
+    if changed > 0 {
+        println!("{} of {} metadata changed", changed, checksums.len());
+        std::process::exit(1);
+    }
+    println!("All {} metadata match {}", checksums.len(), path);
+}
+
+/// Counts the editions from 1 to `last` taken in `markers`, the edition markers by marker
+/// number with `None` for those not created yet, and lists the first `limit` free ones.
+pub fn marker_usage(markers: &[Option<EditionMarker>], last: u64, limit: usize) -> (u64, Vec<u64>) {
+    let mut taken = 0;
+    let mut free = vec![];
+    for edition in 1..=last {
+        let marker = markers
+            .get((edition / EDITION_MARKER_BIT_SIZE) as usize)
+            .and_then(Option::as_ref);
+        if marker.map_or(false, |marker| marker.edition_taken(edition).unwrap()) {
+            taken += 1;
+        } else if free.len() < limit {
+            free.push(edition);
+        }
+    }
+    (taken, free)
+}
+
+/// Reads every edition marker of a master edition to show how many of its edition numbers are
+/// taken and which are still free. Without a max supply the numbers up to the end of the marker
+/// holding the current supply are counted.
+pub fn marker_utilization(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let master_mint = pubkey_of(app_matches, "mint").unwrap();
+    let limit = value_of::<usize>(app_matches, "free").unwrap();
+    let master_edition_key = program.edition_pda(&master_mint);
+    let context = json!({ "mint": master_mint.to_string() });
+    let account = client
+        .get_account(&master_edition_key)
+        .unwrap_or_else(|err| exit_with_error(err.into(), context.clone()));
+    if account.data[0] != Key::MasterEditionV2 as u8 {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "{} is not a V2 master edition mint, only those print editions by number",
+                master_mint
+            )),
+            context,
+        );
+    }
+    let master_edition: MasterEditionV2 = try_from_slice_unchecked(&account.data).unwrap();
+    let last = master_edition.max_supply.unwrap_or(
+        (master_edition.supply / EDITION_MARKER_BIT_SIZE + 1) * EDITION_MARKER_BIT_SIZE - 1,
+    );
+
+    let marker_keys: Vec<Pubkey> = (0..=last / EDITION_MARKER_BIT_SIZE)
+        .map(|marker| program.edition_marker_pda(&master_mint, marker * EDITION_MARKER_BIT_SIZE))
+        .collect();
+    let mut markers = vec![];
+    for page in marker_keys.chunks(page_size_of(app_matches)) {
+        let accounts = client
+            .get_multiple_accounts(page)
+            .unwrap_or_else(|err| exit_with_error(err.into(), context.clone()));
+        markers.extend(accounts.into_iter().map(|account| {
+            account.map(|account| try_from_slice_unchecked(&account.data).unwrap())
+        }));
+    }
+    let (taken, free) = marker_usage(&markers, last, limit);
+
+    println!("Master edition: {}", master_edition_key);
+    println!(
+        "Supply: {}, max supply: {}",
+        master_edition.supply,
+        master_edition
+            .max_supply
+            .map_or("unlimited".to_owned(), |max| max.to_string())
+    );
+    println!(
+        "Editions 1 to {}: {} taken, {} free, {:.2}% used",
+        last,
+        taken,
+        last - taken,
+        taken as f64 * 100.0 / last.max(1) as f64
+    );
+    if taken != master_edition.supply {
+        println!(
+            "The markers record {} editions but the supply is {}",
+            taken, master_edition.supply
+        );
+    }
+    let free: Vec<String> = free.iter().map(u64::to_string).collect();
+    println!("First free edition numbers: {}", free.join(", "));
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        borsh::BorshSerialize,
+        spl_token_metadata::{state::Creator, utils::puff_out_data_fields},
+    };
+
+    #[test]
+    fn marker_usage_counts_taken_editions() {
+        let mut first = EditionMarker {
+            key: Key::EditionMarker,
+            ledger: [0; 31],
+        };
+        for edition in &[1, 2, 4, 247] {
+            first.insert_edition(*edition).unwrap();
+        }
+        let mut third = first.clone();
+        third.ledger = [0; 31];
+        third.insert_edition(500).unwrap();
+        let markers = vec![Some(first), None, Some(third)];
+
+        assert_eq!(marker_usage(&markers, 10, 3), (3, vec![3, 5, 6]));
+        assert_eq!(marker_usage(&markers, 600, 0).0, 5);
+        assert_eq!(marker_usage(&markers, 600, 2).1, vec![3, 5]);
+    }
+
+    #[test]
+    fn checksum_ignores_padding() {
+        let data = Data {
+            name: "Llama".to_owned(),
+            symbol: "LLAMA".to_owned(),
+            uri: "https://arweave.net/llama".to_owned(),
+            seller_fee_basis_points: 500,
+            creators: None,
+        };
+        let padded = Data {
+            name: format!("{:\0<32}", data.name),
+            ..data.clone()
+        };
+        let repriced = Data {
+            seller_fee_basis_points: 1000,
+            ..data.clone()
+        };
+
+        assert_eq!(data_checksum(&data), data_checksum(&padded));
+        assert_ne!(data_checksum(&data), data_checksum(&repriced));
+    }
+
+    #[test]
+    fn royalties_split_by_share() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let data = Data {
+            name: "Llama".to_owned(),
+            symbol: "LLAMA".to_owned(),
+            uri: "https://arweave.net/llama".to_owned(),
+            seller_fee_basis_points: 500,
+            creators: Some(vec![
+                Creator {
+                    address: a,
+                    verified: true,
+                    share: 70,
+                },
+                Creator {
+                    address: b,
+                    verified: false,
+                    share: 30,
+                },
+            ]),
+        };
+
+        assert_eq!(royalty_pool(&data, true, 2_000_000_000), 100_000_000);
+        assert_eq!(royalty_pool(&data, false, 2_000_000_000), 2_000_000_000);
+        assert_eq!(royalty_pool(&data, true, u64::MAX), u64::MAX / 20);
+        assert_eq!(
+            creator_royalties(&data, 100_000_001),
+            vec![(a, 70, 70_000_000), (b, 30, 30_000_000)]
+        );
+    }
+
+    #[test]
+    fn puffed_fields_sit_at_their_offsets() {
+        let mut metadata = Metadata {
+            key: Key::MetadataV1,
+            update_authority: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            data: Data {
+                name: "Llama".to_owned(),
+                symbol: "LLAMA".to_owned(),
+                uri: "https://arweave.net/llama".to_owned(),
+                seller_fee_basis_points: 500,
+                creators: None,
+            },
+            primary_sale_happened: false,
+            is_mutable: true,
+            edition_nonce: None,
+        };
+        puff_out_data_fields(&mut metadata);
+        let data = metadata.try_to_vec().unwrap();
+        let symbol = puffed_symbol_bytes("LLAMA");
+
+        assert_eq!(
+            &data[PUFFED_SYMBOL_OFFSET..PUFFED_SYMBOL_OFFSET + symbol.len()],
+            symbol.as_slice()
+        );
+        assert_eq!(
+            decode_mint_and_name(&data[MINT_OFFSET..]),
+            Some((metadata.mint, "Llama".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,767 @@
+use {
+    super::{read_mints_file, send_and_confirm, signer_of_or_keypair, strip_padding},
+    crate::{
+        batch::UnsignedBatch,
+        confirm::confirm_step,
+        error::{exit_with_error, report_item_error, CliError},
+        rpc::FailoverClient,
+        timing::Timings,
+        version::decode_metadata,
+    },
+    clap::ArgMatches,
+    serde_json::json,
+    solana_clap_utils::input_parsers::{pubkey_of, pubkey_of_signer},
+    solana_client::client_error::ClientError,
+    solana_remote_wallet::remote_wallet::RemoteWalletManager,
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Signature, Signer},
+        transaction::Transaction,
+    },
+    spl_token_metadata::{
+        instruction::update_metadata_accounts,
+        state::{Creator, Data, Metadata, MAX_CREATOR_LIMIT, MAX_URI_LENGTH},
+    },
+    spl_token_metadata_test_client::MetadataProgram,
+    std::{str::FromStr, sync::Arc},
+};
+
+/// An unsigned transaction replacing the data of `metadata_key`, leaving the update authority and
+/// primary sale flag alone.
+pub fn update_data_transaction(
+    payer: &Pubkey,
+    update_authority: &Pubkey,
+    metadata_key: Pubkey,
+    data: Data,
+    program: &MetadataProgram,
+) -> Transaction {
+    let instructions = [update_metadata_accounts(
+        program.id,
+        metadata_key,
+        *update_authority,
+        None,
+        Some(data),
+        None,
+    )];
+    Transaction::new_with_payer(&instructions, Some(payer))
+}
+
+/// Replaces the data of `metadata_key`, leaving the update authority and primary sale flag alone.
+pub fn send_update_data(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    program: &MetadataProgram,
+    payer: &dyn Signer,
+    update_authority: &dyn Signer,
+    metadata_key: Pubkey,
+    data: Data,
+) -> Result<Signature, ClientError> {
+    let mut transaction = update_data_transaction(
+        &payer.pubkey(),
+        &update_authority.pubkey(),
+        metadata_key,
+        data,
+        program,
+    );
+    let recent_blockhash = client.get_recent_blockhash()?.0;
+    transaction.sign(&[payer, update_authority], recent_blockhash);
+    send_and_confirm(
+        app_matches,
+        client,
+        &mut transaction,
+        &[payer, update_authority],
+    )
+}
+
+/// The update authority of a bulk update: loaded as a signer, or with `--output-unsigned-batch`
+/// only its pubkey, so a key held elsewhere can be named.
+fn bulk_update_authority(
+    app_matches: &ArgMatches,
+    payer: &dyn Signer,
+    batch: &Option<UnsignedBatch>,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) -> (Option<Box<dyn Signer>>, Pubkey) {
+    if batch.is_none() {
+        let signer = signer_of_or_keypair(app_matches, "update_authority", wallet_manager);
+        let pubkey = signer.pubkey();
+        return (Some(signer), pubkey);
+    }
+    let pubkey = pubkey_of_signer(app_matches, "update_authority", wallet_manager)
+        .unwrap_or_else(|err| {
+            exit_with_error(
+                CliError::InvalidInput(err.to_string()),
+                json!({ "update_authority": app_matches.value_of("update_authority") }),
+            )
+        })
+        .unwrap_or_else(|| payer.pubkey());
+    (None, pubkey)
+}
+
+pub fn migrate_uris(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    let mut batch = UnsignedBatch::from_matches(app_matches, &client);
+    let (update_authority, authority_key) =
+        bulk_update_authority(app_matches, payer.as_ref(), &batch, wallet_manager);
+    let mints = read_mints_file(app_matches.value_of("mints_file").unwrap());
+    let from = app_matches.value_of("from").unwrap();
+    let to = app_matches.value_of("to").unwrap();
+    let dry_run = app_matches.is_present("dry_run");
+
+    let mut timings = Timings::start(app_matches, &client);
+    timings.estimate(mints.len(), 1 + (authority_key != payer.pubkey()) as usize);
+    let mut rewritten = 0;
+    for (index, mint) in mints.into_iter().enumerate() {
+        let metadata_key = program.metadata_pda(&mint);
+        let metadata_account = match client.get_account(&metadata_key) {
+            Ok(account) => account,
+            Err(_) => {
+                println!("Skipping {} because it has no metadata", mint);
+                continue;
+            }
+        };
+        let metadata = decode_metadata(&metadata_account.data).unwrap();
+        let uri = strip_padding(&metadata.data.uri);
+        if !uri.starts_with(from) {
+            continue;
+        }
+        let new_uri = format!("{}{}", to, &uri[from.len()..]);
+        if new_uri.len() > MAX_URI_LENGTH {
+            println!(
+                "Skipping {} because {} is longer than {} characters",
+                mint, new_uri, MAX_URI_LENGTH
+            );
+            continue;
+        }
+        println!("{}: {} -> {}", mint, uri, new_uri);
+        if dry_run {
+            rewritten += 1;
+            continue;
+        }
+
+        let new_data = Data {
+            uri: new_uri,
+            ..metadata.data
+        };
+        if let Some(batch) = &mut batch {
+            let transaction = update_data_transaction(
+                &payer.pubkey(),
+                &authority_key,
+                metadata_key,
+                new_data,
+                program,
+            );
+            batch.push(&mint.to_string(), transaction);
+            rewritten += 1;
+            continue;
+        }
+        if !confirm_step(app_matches, &format!("Update the URI of {}", mint)) {
+            continue;
+        }
+        match timings.time(index, &mint.to_string(), || {
+            send_update_data(
+                app_matches,
+                &client,
+                program,
+                payer.as_ref(),
+                update_authority.as_deref().unwrap(),
+                metadata_key,
+                new_data,
+            )
+        }) {
+            Ok(_) => rewritten += 1,
+            Err(err) => report_item_error(
+                &format!("Transaction failed for {}.", mint),
+                &err.into(),
+                json!({ "mint": mint.to_string() }),
+            ),
+        }
+    }
+
+    if dry_run {
+        println!("{} URIs would be rewritten", rewritten);
+    } else if let Some(batch) = batch {
+        batch.write();
+    } else {
+        println!("Rewrote {} URIs", rewritten);
+        timings.finish(app_matches, &client);
+    }
+}
+
+/// Removes null bytes that sit between other characters of a puffed string field, returning
+/// `None` when there are none. Trailing padding is dropped too; the program puffs the field
+/// back out on update.
+pub fn remove_interior_nulls(value: &str) -> Option<String> {
+    let stripped = strip_padding(value);
+    if stripped.contains('\u{0000}') {
+        Some(stripped.replace('\u{0000}', ""))
+    } else {
+        None
+    }
+}
+
+/// Applies `repair` to the metadata of every mint in `--mints-file` and sends an update for
+/// each one it changes, or only lists them with `--dry-run`.
+fn repair_metadata(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+    repair: impl Fn(&Data) -> Option<Data>,
+) {
+    let mut batch = UnsignedBatch::from_matches(app_matches, &client);
+    let (update_authority, authority_key) =
+        bulk_update_authority(app_matches, payer.as_ref(), &batch, wallet_manager);
+    let mints = read_mints_file(app_matches.value_of("mints_file").unwrap());
+    let dry_run = app_matches.is_present("dry_run");
+
+    let mut timings = Timings::start(app_matches, &client);
+    timings.estimate(mints.len(), 1 + (authority_key != payer.pubkey()) as usize);
+    let mut fixed = 0;
+    for (index, mint) in mints.into_iter().enumerate() {
+        let metadata_key = program.metadata_pda(&mint);
+        let metadata_account = match client.get_account(&metadata_key) {
+            Ok(account) => account,
+            Err(_) => {
+                println!("Skipping {} because it has no metadata", mint);
+                continue;
+            }
+        };
+        let metadata = decode_metadata(&metadata_account.data).unwrap();
+        let new_data = match repair(&metadata.data) {
+            Some(new_data) => new_data,
+            None => continue,
+        };
+        let changes = [
+            ("name", &metadata.data.name, &new_data.name),
+            ("symbol", &metadata.data.symbol, &new_data.symbol),
+            ("uri", &metadata.data.uri, &new_data.uri),
+        ];
+        for (field, old, new) in changes.iter() {
+            if strip_padding(old) != strip_padding(new) {
+                println!(
+                    "{}: {} {:?} -> {:?}",
+                    mint,
+                    field,
+                    strip_padding(old),
+                    strip_padding(new)
+                );
+            }
+        }
+        if dry_run {
+            fixed += 1;
+            continue;
+        }
+        if let Some(batch) = &mut batch {
+            let transaction = update_data_transaction(
+                &payer.pubkey(),
+                &authority_key,
+                metadata_key,
+                new_data,
+                program,
+            );
+            batch.push(&mint.to_string(), transaction);
+            fixed += 1;
+            continue;
+        }
+        if !confirm_step(app_matches, &format!("Update the metadata of {}", mint)) {
+            continue;
+        }
+
+        match timings.time(index, &mint.to_string(), || {
+            send_update_data(
+                app_matches,
+                &client,
+                program,
+                payer.as_ref(),
+                update_authority.as_deref().unwrap(),
+                metadata_key,
+                new_data,
+            )
+        }) {
+            Ok(_) => fixed += 1,
+            Err(err) => report_item_error(
+                &format!("Transaction failed for {}.", mint),
+                &err.into(),
+                json!({ "mint": mint.to_string() }),
+            ),
+        }
+    }
+
+    if dry_run {
+        println!("{} metadata would be fixed", fixed);
+    } else if let Some(batch) = batch {
+        batch.write();
+    } else {
+        println!("Fixed {} metadata", fixed);
+        timings.finish(app_matches, &client);
+    }
+}
+
+pub fn fix_nulls(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    repair_metadata(
+        app_matches,
+        payer,
+        client,
+        program,
+        wallet_manager,
+        |data| {
+            let name = remove_interior_nulls(&data.name);
+            let symbol = remove_interior_nulls(&data.symbol);
+            let uri = remove_interior_nulls(&data.uri);
+            if name.is_none() && symbol.is_none() && uri.is_none() {
+                return None;
+            }
+            Some(Data {
+                name: name.unwrap_or_else(|| data.name.clone()),
+                symbol: symbol.unwrap_or_else(|| data.symbol.clone()),
+                uri: uri.unwrap_or_else(|| data.uri.clone()),
+                ..data.clone()
+            })
+        },
+    );
+}
+
+/// Removes a pair of double quotes wrapping a puffed string field, returning `None` when the
+/// field is not quoted.
+pub fn strip_surrounding_quotes(value: &str) -> Option<String> {
+    let stripped = strip_padding(value);
+    if stripped.len() >= 2 && stripped.starts_with('"') && stripped.ends_with('"') {
+        Some(stripped[1..stripped.len() - 1].to_owned())
+    } else {
+        None
+    }
+}
+
+pub fn strip_quotes(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    repair_metadata(
+        app_matches,
+        payer,
+        client,
+        program,
+        wallet_manager,
+        |data| {
+            let name = strip_surrounding_quotes(&data.name);
+            let symbol = strip_surrounding_quotes(&data.symbol);
+            if name.is_none() && symbol.is_none() {
+                return None;
+            }
+            Some(Data {
+                name: name.unwrap_or_else(|| data.name.clone()),
+                symbol: symbol.unwrap_or_else(|| data.symbol.clone()),
+                ..data.clone()
+            })
+        },
+    );
+}
+
+/// Merges creator entries that share an address, summing their shares and keeping the entry
+/// verified if any of the duplicates was.
+pub fn merge_duplicate_creators(creators: &[Creator]) -> Result<Vec<Creator>, String> {
+    let mut merged: Vec<Creator> = vec![];
+    for creator in creators {
+        match merged.iter_mut().find(|c| c.address == creator.address) {
+            Some(existing) => {
+                existing.share = existing
+                    .share
+                    .checked_add(creator.share)
+                    .ok_or_else(|| format!("Shares of {} overflow", creator.address))?;
+                existing.verified = existing.verified || creator.verified;
+            }
+            None => merged.push(creator.clone()),
+        }
+    }
+    validate_creators(&merged)?;
+    Ok(merged)
+}
+
+/// Parses a `pubkey:share` creator argument.
+pub fn parse_creator_share(value: &str) -> Result<(Pubkey, u8), String> {
+    let (address, share) = value
+        .split_once(':')
+        .ok_or_else(|| format!("{} is not of the form pubkey:share", value))?;
+    let address = Pubkey::from_str(address).map_err(|_| format!("{} is not a pubkey", address))?;
+    let share = share
+        .parse::<u8>()
+        .map_err(|_| format!("{} is not a share between 0 and 100", share))?;
+    Ok((address, share))
+}
+
+/// Builds the creators of an update from `shares`, carrying over the verified flag of any
+/// creator already in `existing` so that only the creators themselves change it.
+pub fn creators_from_shares(shares: &[(Pubkey, u8)], existing: &[Creator]) -> Vec<Creator> {
+    shares
+        .iter()
+        .map(|&(address, share)| Creator {
+            address,
+            verified: existing.iter().any(|c| c.address == address && c.verified),
+            share,
+        })
+        .collect()
+}
+
+/// Checks the rules the program enforces on any creators it is given: between 1 and
+/// `MAX_CREATOR_LIMIT` of them, each listed once, with shares summing to 100. Every path that
+/// builds a creators list runs it before sending, so a list the program would reject costs no
+/// fee.
+pub fn validate_creators(creators: &[Creator]) -> Result<(), String> {
+    if creators.is_empty() || creators.len() > MAX_CREATOR_LIMIT {
+        return Err(format!(
+            "There must be between 1 and {} creators, not {}",
+            MAX_CREATOR_LIMIT,
+            creators.len()
+        ));
+    }
+    for (index, creator) in creators.iter().enumerate() {
+        if creators[index + 1..]
+            .iter()
+            .any(|c| c.address == creator.address)
+        {
+            return Err(format!("{} is listed more than once", creator.address));
+        }
+    }
+    let total: u16 = creators.iter().map(|c| c.share as u16).sum();
+    if total != 100 {
+        return Err(format!("Creator shares sum to {}, not 100", total));
+    }
+    Ok(())
+}
+
+/// Checks `creators` against the rules the program enforces on an update by
+/// `update_authority`, on top of those of `validate_creators`.
+pub fn validate_creator_update(
+    creators: &[Creator],
+    existing: &[Creator],
+    update_authority: &Pubkey,
+) -> Result<(), String> {
+    validate_creators(creators)?;
+    for creator in creators {
+        if creator.address == *update_authority {
+            continue;
+        }
+        let was_verified = existing
+            .iter()
+            .any(|c| c.address == creator.address && c.verified);
+        if creator.verified != was_verified {
+            return Err(format!(
+                "Only {} itself can change whether it is verified",
+                creator.address
+            ));
+        }
+    }
+    if !creators.iter().any(|c| c.address == *update_authority) {
+        return Err(format!(
+            "The update authority {} must be one of the creators",
+            update_authority
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `update_authority` may update `metadata`, checked before a transaction is built so
+/// a mismatch costs no fee.
+pub fn can_update(
+    metadata_key: &Pubkey,
+    metadata: &Metadata,
+    update_authority: &Pubkey,
+) -> Result<(), CliError> {
+    if !metadata.is_mutable {
+        return Err(CliError::InvalidInput(format!(
+            "Metadata {} is immutable, cannot update",
+            metadata_key
+        )));
+    }
+    if metadata.update_authority != *update_authority {
+        return Err(CliError::InvalidInput(format!(
+            "Update authority of {} is {}, not {}",
+            metadata_key, metadata.update_authority, update_authority
+        )));
+    }
+    Ok(())
+}
+
+/// Exits unless `update_authority` may update the metadata of `mint`.
+pub fn check_can_update(
+    mint: &Pubkey,
+    metadata_key: &Pubkey,
+    metadata: &Metadata,
+    update_authority: &dyn Signer,
+) {
+    if let Err(err) = can_update(metadata_key, metadata, &update_authority.pubkey()) {
+        exit_with_error(err, json!({ "mint": mint.to_string() }));
+    }
+}
+
+pub fn dedup_creators(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    let update_authority = signer_of_or_keypair(app_matches, "update_authority", wallet_manager);
+    let mint = pubkey_of(app_matches, "mint").unwrap();
+    let metadata_key = program.metadata_pda(&mint);
+    let metadata_account = client.get_account(&metadata_key).unwrap();
+    let metadata = decode_metadata(&metadata_account.data).unwrap();
+
+    let creators = match &metadata.data.creators {
+        Some(creators) => creators.clone(),
+        None => {
+            println!("Metadata {} has no creators", metadata_key);
+            return;
+        }
+    };
+    let merged = match merge_duplicate_creators(&creators) {
+        Ok(merged) => merged,
+        Err(err) => exit_with_error(
+            CliError::InvalidInput(format!(
+                "Cannot deduplicate creators of {}: {}",
+                metadata_key, err
+            )),
+            json!({ "mint": mint.to_string() }),
+        ),
+    };
+    if merged.len() == creators.len() {
+        println!("Metadata {} has no duplicate creators", metadata_key);
+        return;
+    }
+    println!("Before: {:#?}", creators);
+    println!("After: {:#?}", merged);
+
+    check_can_update(&mint, &metadata_key, &metadata, update_authority.as_ref());
+
+    let new_data = Data {
+        creators: Some(merged),
+        ..metadata.data
+    };
+    let signature = send_update_data(
+        app_matches,
+        &client,
+        program,
+        payer.as_ref(),
+        update_authority.as_ref(),
+        metadata_key,
+        new_data,
+    )
+    .unwrap();
+    println!("Deduplicated creators of {} in {}", metadata_key, signature);
+}
+
+/// Removes `creator` from `creators` and scales the remaining shares back up to 100, handing
+/// the units lost to rounding to the creators with the largest remainders, earliest first.
+pub fn remove_creator_share(
+    creators: &[Creator],
+    creator: &Pubkey,
+) -> Result<Vec<Creator>, String> {
+    if !creators.iter().any(|c| c.address == *creator) {
+        return Err(format!("{} is not a creator", creator));
+    }
+    let mut remaining: Vec<Creator> = creators
+        .iter()
+        .filter(|c| c.address != *creator)
+        .cloned()
+        .collect();
+    let total: u32 = remaining.iter().map(|c| c.share as u32).sum();
+    if total == 0 {
+        return Err("No shares would be left to renormalize".to_owned());
+    }
+
+    let mut remainders = vec![];
+    for (index, c) in remaining.iter_mut().enumerate() {
+        let scaled = c.share as u32 * 100;
+        c.share = (scaled / total) as u8;
+        remainders.push((scaled % total, index));
+    }
+    let assigned: u32 = remaining.iter().map(|c| c.share as u32).sum();
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for &(_, index) in remainders.iter().take((100 - assigned) as usize) {
+        remaining[index].share += 1;
+    }
+
+    validate_creators(&remaining)?;
+    Ok(remaining)
+}
+
+pub fn remove_creator(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    let update_authority = signer_of_or_keypair(app_matches, "update_authority", wallet_manager);
+    let mint = pubkey_of(app_matches, "mint").unwrap();
+    let creator = pubkey_of(app_matches, "creator").unwrap();
+    let metadata_key = program.metadata_pda(&mint);
+    let metadata_account = client.get_account(&metadata_key).unwrap();
+    let metadata = decode_metadata(&metadata_account.data).unwrap();
+    let context = json!({ "mint": mint.to_string(), "creator": creator.to_string() });
+
+    let creators = metadata.data.creators.clone().unwrap_or_default();
+    let remaining = match remove_creator_share(&creators, &creator) {
+        Ok(remaining) => remaining,
+        Err(err) => exit_with_error(
+            CliError::InvalidInput(format!(
+                "Cannot remove {} from the creators of {}: {}",
+                creator, metadata_key, err
+            )),
+            context,
+        ),
+    };
+    // The program rejects creator lists the update authority is not part of.
+    if !remaining
+        .iter()
+        .any(|c| c.address == metadata.update_authority)
+    {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "The update authority {} must remain a creator of {}",
+                metadata.update_authority, metadata_key
+            )),
+            context,
+        );
+    }
+    println!("Before: {:#?}", creators);
+    println!("After: {:#?}", remaining);
+
+    check_can_update(&mint, &metadata_key, &metadata, update_authority.as_ref());
+
+    let new_data = Data {
+        creators: Some(remaining),
+        ..metadata.data
+    };
+    let signature = send_update_data(
+        app_matches,
+        &client,
+        program,
+        payer.as_ref(),
+        update_authority.as_ref(),
+        metadata_key,
+        new_data,
+    )
+    .unwrap();
+    println!(
+        "Removed {} from the creators of {} in {}",
+        creator, metadata_key, signature
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn creator(address: Pubkey, share: u8) -> Creator {
+        Creator {
+            address,
+            verified: false,
+            share,
+        }
+    }
+
+    #[test]
+    fn remove_creator_renormalizes_shares() {
+        let (a, b, c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let creators = [creator(a, 40), creator(b, 30), creator(c, 30)];
+
+        let remaining = remove_creator_share(&creators, &a).unwrap();
+
+        assert_eq!(remaining, vec![creator(b, 50), creator(c, 50)]);
+    }
+
+    #[test]
+    fn remove_creator_hands_out_rounding_remainder() {
+        let (a, b, c, d) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let creators = [
+            creator(a, 10),
+            creator(b, 30),
+            creator(c, 30),
+            creator(d, 30),
+        ];
+
+        let remaining = remove_creator_share(&creators, &a).unwrap();
+
+        assert_eq!(remaining.iter().map(|c| c.share as u32).sum::<u32>(), 100);
+        assert_eq!(remaining[0], creator(b, 34));
+    }
+
+    #[test]
+    fn creators_keep_verified_flags() {
+        let (authority, a, b) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let existing = [
+            creator(authority, 50),
+            Creator {
+                verified: true,
+                ..creator(a, 50)
+            },
+        ];
+
+        let creators = creators_from_shares(&[(authority, 40), (a, 30), (b, 30)], &existing);
+
+        assert!(creators[1].verified && !creators[2].verified);
+        assert_eq!(
+            validate_creator_update(&creators, &existing, &authority),
+            Ok(())
+        );
+        assert!(validate_creator_update(&creators[1..], &existing, &authority).is_err());
+        assert!(validate_creator_update(&creators[..2], &existing, &authority).is_err());
+    }
+
+    #[test]
+    fn fail_creator_shares_not_summing_to_100() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        assert_eq!(validate_creators(&[creator(a, 50), creator(b, 50)]), Ok(()));
+        assert!(validate_creators(&[creator(a, 50), creator(b, 49)]).is_err());
+        assert!(validate_creators(&[creator(a, 100), creator(b, 1)]).is_err());
+    }
+
+    #[test]
+    fn fail_no_creators() {
+        assert!(validate_creators(&[]).is_err());
+    }
+
+    #[test]
+    fn fail_duplicate_creator() {
+        let a = Pubkey::new_unique();
+
+        assert!(validate_creators(&[creator(a, 50), creator(a, 50)]).is_err());
+    }
+
+    #[test]
+    fn fail_remove_only_creator() {
+        let a = Pubkey::new_unique();
+
+        assert!(remove_creator_share(&[creator(a, 100)], &a).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use {
+    crate::error::{exit_with_error, CliError},
+    clap::ArgMatches,
+    serde_json::json,
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        rpc_request::RpcError,
+    },
+    solana_sdk::transaction::TransactionError,
+    std::{cell::RefCell, fs},
+};
+
+/// Why a send had to be retried.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetryReason {
+    BlockhashExpired,
+    RateLimited,
+    Other,
+}
+
+impl RetryReason {
+    pub fn of(err: &ClientError) -> Self {
+        if err.get_transaction_error() == Some(TransactionError::BlockhashNotFound) {
+            return RetryReason::BlockhashExpired;
+        }
+        match err.kind() {
+            ClientErrorKind::Reqwest(err) if err.status().map_or(false, |s| s.as_u16() == 429) => {
+                RetryReason::RateLimited
+            }
+            // Returned by `send_and_confirm_transaction` when the blockhash expired before the
+            // transaction confirmed.
+            ClientErrorKind::RpcError(RpcError::ForUser(message))
+                if message.contains("transaction expiration") =>
+            {
+                RetryReason::BlockhashExpired
+            }
+            _ => RetryReason::Other,
+        }
+    }
+}
+
+#[derive(Default)]
+struct RetryMetrics {
+    attempts: u64,
+    blockhash_expired: u64,
+    rate_limited: u64,
+    other: u64,
+}
+
+thread_local! {
+    /// Sends made by the shared send helpers over the run, for `--retry-metrics`.
+    static METRICS: RefCell<RetryMetrics> = RefCell::new(RetryMetrics::default());
+}
+
+/// Counts a transaction sent by one of the shared send helpers.
+pub fn record_attempt() {
+    METRICS.with(|metrics| metrics.borrow_mut().attempts += 1);
+}
+
+/// Counts a send that failed or went unconfirmed and is sent again. Called where the retry is
+/// made, as a failure given up on is no retry.
+pub fn record_retry(reason: RetryReason) {
+    METRICS.with(|metrics| {
+        let mut metrics = metrics.borrow_mut();
+        match reason {
+            RetryReason::BlockhashExpired => metrics.blockhash_expired += 1,
+            RetryReason::RateLimited => metrics.rate_limited += 1,
+            RetryReason::Other => metrics.other += 1,
+        }
+    });
+}
+
+/// Writes the sends of the run to `--retry-metrics`, when given.
+pub fn write_retry_metrics(app_matches: &ArgMatches) {
+    let path = match app_matches.value_of("retry_metrics") {
+        Some(path) => path,
+        None => return,
+    };
+    let report = METRICS.with(|metrics| {
+        let metrics = metrics.borrow();
+        json!({
+            "attempts": metrics.attempts,
+            "retries": {
+                "blockhash_expired": metrics.blockhash_expired,
+                "rate_limited": metrics.rate_limited,
+                "other": metrics.other,
+            },
+        })
+    });
+    if let Err(err) = fs::write(path, serde_json::to_string_pretty(&report).unwrap()) {
+        exit_with_error(CliError::from(err), json!({ "retry_metrics": path }));
+    }
+    println!("Wrote retry metrics to {}", path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_reasons() {
+        let expired: ClientError = TransactionError::BlockhashNotFound.into();
+        let unconfirmed: ClientError = RpcError::ForUser(
+            "unable to confirm transaction. This can happen in situations such as transaction \
+             expiration and insufficient fee-payer funds"
+                .to_owned(),
+        )
+        .into();
+        let failed: ClientError = TransactionError::AccountNotFound.into();
+
+        assert_eq!(RetryReason::of(&expired), RetryReason::BlockhashExpired);
+        assert_eq!(RetryReason::of(&unconfirmed), RetryReason::BlockhashExpired);
+        assert_eq!(RetryReason::of(&failed), RetryReason::Other);
+    }
+}
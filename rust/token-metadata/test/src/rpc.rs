@@ -0,0 +1,342 @@
+use {
+    crate::retry::{record_attempt, record_retry, RetryReason},
+    solana_account_decoder::parse_token::UiTokenAmount,
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+        rpc_client::RpcClient,
+        rpc_config::RpcProgramAccountsConfig,
+        rpc_request::{RpcError, TokenAccountsFilter},
+        rpc_response::{RpcKeyedAccount, RpcResult, RpcSimulateTransactionResult},
+    },
+    solana_sdk::{
+        account::Account, commitment_config::CommitmentConfig, fee_calculator::FeeCalculator,
+        hash::Hash, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+    },
+    solana_transaction_status::{
+        EncodedConfirmedTransaction, TransactionStatus, UiTransactionEncoding,
+    },
+    std::{cell::Cell, ops::Deref},
+};
+
+/// An `RpcClient` over one or more `--url` endpoints. The requests the commands send go to the
+/// endpoint in use, and one failing with a connection error or a 5xx status is sent again to the
+/// next, so a degraded provider does not end a long run. Anything it does not wrap is sent to
+/// the endpoint in use through `Deref`.
+pub struct FailoverClient {
+    urls: Vec<String>,
+    clients: Vec<RpcClient>,
+    current: Cell<usize>,
+    verbose: bool,
+}
+
+impl FailoverClient {
+    pub fn new(urls: Vec<String>, verbose: bool) -> Self {
+        let clients = urls.iter().map(|url| RpcClient::new(url.clone())).collect();
+        FailoverClient {
+            urls,
+            clients,
+            current: Cell::new(0),
+            verbose,
+        }
+    }
+
+    /// The endpoint requests currently go to.
+    pub fn url(&self) -> &str {
+        &self.urls[self.current.get()]
+    }
+
+    /// Every endpoint requests may go to, in the order they are failed over to.
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Whether `err` says the endpoint rather than the request is at fault.
+    fn is_endpoint_error(err: &ClientError) -> bool {
+        match err.kind() {
+            ClientErrorKind::Io(_) => true,
+            // The client asks for the node version before its first request and reports a
+            // failure to get it this way, whatever the cause.
+            ClientErrorKind::RpcError(RpcError::RpcRequestError(message)) => {
+                message.starts_with("cluster version query failed")
+            }
+            ClientErrorKind::Reqwest(err) => {
+                err.is_connect()
+                    || err.is_timeout()
+                    || err
+                        .status()
+                        .map_or(false, |status| status.is_server_error())
+            }
+            _ => false,
+        }
+    }
+
+    /// Sends `request` to the endpoint in use, moving on to the next one each time it fails
+    /// with an endpoint error until every endpoint has been tried once.
+    fn with_failover<T>(
+        &self,
+        method: &str,
+        request: impl Fn(&RpcClient) -> ClientResult<T>,
+    ) -> ClientResult<T> {
+        let mut attempts = 1;
+        loop {
+            let result = request(&self.clients[self.current.get()]);
+            match &result {
+                Err(err) if Self::is_endpoint_error(err) && attempts < self.clients.len() => {
+                    let failed = self.url().to_owned();
+                    self.current
+                        .set((self.current.get() + 1) % self.clients.len());
+                    eprintln!(
+                        "RPC endpoint {} failed ({}), switching to {}",
+                        failed,
+                        err,
+                        self.url()
+                    );
+                    if method == "sendTransaction" {
+                        record_attempt();
+                        record_retry(RetryReason::of(err));
+                    }
+                    attempts += 1;
+                }
+                _ => {
+                    if self.verbose {
+                        eprintln!("{} served by {}", method, self.url());
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    pub fn get_account(&self, pubkey: &Pubkey) -> ClientResult<Account> {
+        self.with_failover("getAccountInfo", |client| client.get_account(pubkey))
+    }
+
+    pub fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment_config: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        self.with_failover("getAccountInfo", |client| {
+            client.get_account_with_commitment(pubkey, commitment_config)
+        })
+    }
+
+    pub fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> ClientResult<Vec<Option<Account>>> {
+        self.with_failover("getMultipleAccounts", |client| {
+            client.get_multiple_accounts(pubkeys)
+        })
+    }
+
+    pub fn get_program_accounts_with_config(
+        &self,
+        pubkey: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        self.with_failover("getProgramAccounts", |client| {
+            client.get_program_accounts_with_config(pubkey, config.clone())
+        })
+    }
+
+    pub fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        token_account_filter: TokenAccountsFilter,
+    ) -> ClientResult<Vec<RpcKeyedAccount>> {
+        self.with_failover("getTokenAccountsByOwner", |client| {
+            let filter = match &token_account_filter {
+                TokenAccountsFilter::Mint(mint) => TokenAccountsFilter::Mint(*mint),
+                TokenAccountsFilter::ProgramId(id) => TokenAccountsFilter::ProgramId(*id),
+            };
+            client.get_token_accounts_by_owner(owner, filter)
+        })
+    }
+
+    pub fn get_token_account_balance(&self, pubkey: &Pubkey) -> ClientResult<UiTokenAmount> {
+        self.with_failover("getTokenAccountBalance", |client| {
+            client.get_token_account_balance(pubkey)
+        })
+    }
+
+    pub fn get_token_supply(&self, mint: &Pubkey) -> ClientResult<UiTokenAmount> {
+        self.with_failover("getTokenSupply", |client| client.get_token_supply(mint))
+    }
+
+    pub fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> ClientResult<u64> {
+        self.with_failover("getMinimumBalanceForRentExemption", |client| {
+            client.get_minimum_balance_for_rent_exemption(data_len)
+        })
+    }
+
+    pub fn get_recent_blockhash(&self) -> ClientResult<(Hash, FeeCalculator)> {
+        self.with_failover("getRecentBlockhash", |client| client.get_recent_blockhash())
+    }
+
+    pub fn get_new_blockhash(&self, blockhash: &Hash) -> ClientResult<(Hash, FeeCalculator)> {
+        self.with_failover("getRecentBlockhash", |client| {
+            client.get_new_blockhash(blockhash)
+        })
+    }
+
+    pub fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.with_failover("getSignatureStatuses", |client| {
+            client.get_signature_statuses(signatures)
+        })
+    }
+
+    /// Like `get_signature_statuses`, but also searches the ledger history, so signatures older
+    /// than the recent status cache are found.
+    pub fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.with_failover("getSignatureStatuses", |client| {
+            client.get_signature_statuses_with_history(signatures)
+        })
+    }
+
+    /// Whether a transaction signed with `blockhash` can still land.
+    pub fn is_blockhash_valid(&self, blockhash: &Hash) -> ClientResult<bool> {
+        self.with_failover("getFeeCalculatorForBlockhash", |client| {
+            client
+                .get_fee_calculator_for_blockhash(blockhash)
+                .map(|fee_calculator| fee_calculator.is_some())
+        })
+    }
+
+    pub fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> RpcResult<RpcSimulateTransactionResult> {
+        self.with_failover("simulateTransaction", |client| {
+            client.simulate_transaction(transaction)
+        })
+    }
+
+    pub fn get_transaction(
+        &self,
+        signature: &Signature,
+        encoding: UiTransactionEncoding,
+    ) -> ClientResult<EncodedConfirmedTransaction> {
+        self.with_failover("getTransaction", |client| {
+            client.get_transaction(signature, encoding)
+        })
+    }
+
+    pub fn send_transaction(&self, transaction: &Transaction) -> ClientResult<Signature> {
+        self.with_failover("sendTransaction", |client| {
+            client.send_transaction(transaction)
+        })
+    }
+
+    pub fn send_and_confirm_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ClientResult<Signature> {
+        self.with_failover("sendTransaction", |client| {
+            client.send_and_confirm_transaction(transaction)
+        })
+    }
+}
+
+impl Deref for FailoverClient {
+    type Target = RpcClient;
+
+    fn deref(&self) -> &RpcClient {
+        &self.clients[self.current.get()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_client::client_error::reqwest,
+        std::{
+            io::{self, Read, Write},
+            net::TcpListener,
+            thread,
+        },
+    };
+
+    /// The error of a request to a server answering it with `status`.
+    fn status_error(status: u16) -> ClientError {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // The request fits in one read, and only needs to arrive before the answer.
+            assert!(stream.read(&mut [0; 1024]).unwrap() > 0);
+            write!(
+                stream,
+                "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+        });
+        let err = reqwest::blocking::get(&url)
+            .and_then(|response| response.error_for_status())
+            .unwrap_err();
+        server.join().unwrap();
+        err.into()
+    }
+
+    #[test]
+    fn only_endpoint_errors_fail_over() {
+        let io = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+        assert!(FailoverClient::is_endpoint_error(&io.into()));
+        // Nothing listens on the port the bound listener held.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let connect = reqwest::blocking::get(&format!("http://127.0.0.1:{}", port)).unwrap_err();
+        assert!(connect.is_connect());
+        assert!(FailoverClient::is_endpoint_error(&connect.into()));
+        assert!(FailoverClient::is_endpoint_error(&status_error(503)));
+        let version = RpcError::RpcRequestError("cluster version query failed: 502".to_owned());
+        assert!(FailoverClient::is_endpoint_error(&version.into()));
+
+        assert!(!FailoverClient::is_endpoint_error(&status_error(429)));
+        assert!(!FailoverClient::is_endpoint_error(&status_error(404)));
+        let rpc = RpcError::RpcResponseError {
+            code: -32002,
+            message: "Transaction simulation failed".to_owned(),
+            data: solana_client::rpc_request::RpcResponseErrorData::Empty,
+        };
+        assert!(!FailoverClient::is_endpoint_error(&rpc.into()));
+    }
+
+    #[test]
+    fn every_endpoint_is_tried_once() {
+        let urls: Vec<String> = (0..3)
+            .map(|i| format!("http://127.0.0.1:{}", 8899 + i))
+            .collect();
+        let client = FailoverClient::new(urls.clone(), false);
+        let tried = Cell::new(0);
+        let result: ClientResult<()> = client.with_failover("getAccountInfo", |_| {
+            tried.set(tried.get() + 1);
+            Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused").into())
+        });
+        assert!(result.is_err());
+        assert_eq!(tried.get(), 3);
+        assert_eq!(client.url(), urls[2]);
+
+        // The endpoint that answered stays in use, and an error it is not at fault for is
+        // returned without moving on.
+        tried.set(0);
+        let result: ClientResult<()> = client.with_failover("getAccountInfo", |_| {
+            tried.set(tried.get() + 1);
+            match tried.get() {
+                1 => Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused").into()),
+                _ => Err(RpcError::ForUser("not found".to_owned()).into()),
+            }
+        });
+        assert!(result.is_err());
+        assert_eq!(tried.get(), 2);
+        assert_eq!(client.url(), urls[0]);
+    }
+}
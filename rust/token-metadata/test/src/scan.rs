@@ -0,0 +1,137 @@
+use {
+    crate::rpc::FailoverClient,
+    clap::ArgMatches,
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
+    solana_client::{
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+    },
+    solana_sdk::{account::Account, bs58, pubkey::Pubkey},
+};
+
+/// Most accounts a single `getMultipleAccounts` request may ask for.
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// Filter matching accounts whose data holds `bytes` at `offset`.
+pub fn memcmp_filter(offset: usize, bytes: &[u8]) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp {
+        offset,
+        bytes: MemcmpEncodedBytes::Binary(bs58::encode(bytes).into_string()),
+        encoding: None,
+    })
+}
+
+/// Returns the `--page-size` given to the command, capped at what one request can fetch.
+pub fn page_size_of(app_matches: &ArgMatches) -> usize {
+    app_matches
+        .value_of("page_size")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap()
+        .clamp(1, MAX_PAGE_SIZE)
+}
+
+/// Returns the keys of the accounts of `program_id` matching `filters`. No account data is
+/// requested, which keeps the response small enough for mainnet-sized programs.
+pub fn get_program_account_keys(
+    client: &FailoverClient,
+    program_id: &Pubkey,
+    filters: Vec<RpcFilterType>,
+) -> Vec<Pubkey> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(filters),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig {
+                offset: 0,
+                length: 0,
+            }),
+            commitment: None,
+        },
+        with_context: None,
+    };
+    client
+        .get_program_accounts_with_config(program_id, config)
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Returns the `length` bytes at `offset` of every account of `program_id` matching `filters`.
+/// Only the slice crosses the wire, so a query needing a field or two of a large account costs a
+/// fraction of fetching it whole. Accounts shorter than the slice come back with fewer bytes.
+pub fn get_program_account_slices(
+    client: &FailoverClient,
+    program_id: &Pubkey,
+    filters: Vec<RpcFilterType>,
+    offset: usize,
+    length: usize,
+) -> Vec<(Pubkey, Vec<u8>)> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(filters),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig { offset, length }),
+            commitment: None,
+        },
+        with_context: None,
+    };
+    client
+        .get_program_accounts_with_config(program_id, config)
+        .unwrap()
+        .into_iter()
+        .map(|(key, account)| (key, account.data))
+        .collect()
+}
+
+/// Iterates the accounts of `program_id` matching `filters`, fetching their data `page_size`
+/// accounts at a time as the iterator is consumed.
+pub fn scan_program_accounts<'a>(
+    client: &'a FailoverClient,
+    program_id: &Pubkey,
+    filters: Vec<RpcFilterType>,
+    page_size: usize,
+) -> ProgramAccounts<'a> {
+    ProgramAccounts {
+        client,
+        keys: get_program_account_keys(client, program_id, filters),
+        next_key: 0,
+        page_size,
+        page: vec![].into_iter(),
+    }
+}
+
+pub struct ProgramAccounts<'a> {
+    client: &'a FailoverClient,
+    keys: Vec<Pubkey>,
+    next_key: usize,
+    page_size: usize,
+    page: std::vec::IntoIter<(Pubkey, Account)>,
+}
+
+impl Iterator for ProgramAccounts<'_> {
+    type Item = (Pubkey, Account);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.page.next() {
+                return Some(item);
+            }
+            if self.next_key >= self.keys.len() {
+                return None;
+            }
+            let end = (self.next_key + self.page_size).min(self.keys.len());
+            let keys = &self.keys[self.next_key..end];
+            let accounts = self.client.get_multiple_accounts(keys).unwrap();
+            // Accounts closed since the key query come back as None and are skipped.
+            self.page = keys
+                .iter()
+                .zip(accounts)
+                .filter_map(|(key, account)| account.map(|account| (*key, account)))
+                .collect::<Vec<_>>()
+                .into_iter();
+            self.next_key = end;
+        }
+    }
+}
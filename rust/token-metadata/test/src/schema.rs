@@ -0,0 +1,301 @@
+use {
+    crate::error::{exit_with_error, CliError},
+    clap::ArgMatches,
+    serde::{Deserialize, Serialize},
+    serde_json::{json, Map, Value},
+    spl_token_metadata::state::{MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH},
+};
+
+/// Bulk commands `print_schema` describes the input file of.
+pub const SCHEMA_COMMANDS: &[&str] = &[
+    "airdrop",
+    "create_new_llamas",
+    "update_new_llamas",
+    "file_refunds",
+];
+
+const DRAFT: &str = "http://json-schema.org/draft-07/schema#";
+
+/// The JSON Schema of what a type deserializes from.
+pub trait InputSchema {
+    /// Whether a field of this type may be left out of an object.
+    const OPTIONAL: bool = false;
+
+    fn schema() -> Value;
+}
+
+impl InputSchema for String {
+    fn schema() -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl InputSchema for u32 {
+    fn schema() -> Value {
+        json!({ "type": "integer", "minimum": 0, "maximum": u32::MAX })
+    }
+}
+
+impl InputSchema for u64 {
+    fn schema() -> Value {
+        json!({ "type": "integer", "minimum": 0 })
+    }
+}
+
+impl<T: InputSchema> InputSchema for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn schema() -> Value {
+        T::schema()
+    }
+}
+
+/// `schema` with `docs`, the doc comment lines of the field or type it is for, as its
+/// description.
+fn described(mut schema: Value, docs: &[&str]) -> Value {
+    let description = docs
+        .iter()
+        .map(|line| line.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !description.is_empty() {
+        schema["description"] = json!(description);
+    }
+    schema
+}
+
+/// Declares an input type the commands deserialize their files into, along with its
+/// `InputSchema`. A tuple struct is read from an array of its fields, a struct with named fields
+/// from an object. The doc comments of the type and its fields become the descriptions.
+macro_rules! input_type {
+    (
+        $(#[doc = $doc:literal])*
+        pub struct $name:ident($($(#[doc = $field_doc:literal])* pub $ty:ty),+ $(,)?);
+    ) => {
+        $(#[doc = $doc])*
+        #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+        pub struct $name($(pub $ty),+);
+
+        impl InputSchema for $name {
+            fn schema() -> Value {
+                let items = vec![$(described(<$ty>::schema(), &[$($field_doc),*])),+];
+                described(
+                    json!({
+                        "type": "array",
+                        "minItems": items.len(),
+                        "maxItems": items.len(),
+                        "items": items,
+                    }),
+                    &[$($doc),*],
+                )
+            }
+        }
+    };
+    (
+        $(#[doc = $doc:literal])*
+        pub struct $name:ident {
+            $($(#[doc = $field_doc:literal])* $(#[serde($($serde:tt)*)])* pub $field:ident: $ty:ty),+ $(,)?
+        }
+    ) => {
+        $(#[doc = $doc])*
+        #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+        pub struct $name {
+            $($(#[doc = $field_doc])* $(#[serde($($serde)*)])* pub $field: $ty),+
+        }
+
+        impl InputSchema for $name {
+            fn schema() -> Value {
+                let mut properties = Map::new();
+                let mut required = vec![];
+                $(
+                    properties.insert(
+                        stringify!($field).to_owned(),
+                        described(<$ty>::schema(), &[$($field_doc),*]),
+                    );
+                    if !<$ty as InputSchema>::OPTIONAL {
+                        required.push(stringify!($field));
+                    }
+                )+
+                described(
+                    json!({
+                        "type": "object",
+                        "required": required,
+                        "properties": properties,
+                    }),
+                    &[$($doc),*],
+                )
+            }
+        }
+    };
+}
+
+/// A base58 pubkey.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct PubkeyString(pub String);
+
+impl InputSchema for PubkeyString {
+    fn schema() -> Value {
+        json!({ "type": "string", "pattern": "^[1-9A-HJ-NP-Za-km-z]{32,44}$" })
+    }
+}
+
+/// A uri that fits on chain.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct UriString(pub String);
+
+impl InputSchema for UriString {
+    fn schema() -> Value {
+        json!({ "type": "string", "maxLength": MAX_URI_LENGTH })
+    }
+}
+
+/// A manifest, kept whole, whose name and symbol fit on chain.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ManifestJson(pub Value);
+
+impl InputSchema for ManifestJson {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["name", "symbol"],
+            "properties": {
+                "name": { "type": "string", "maxLength": MAX_NAME_LENGTH },
+                "symbol": { "type": "string", "maxLength": MAX_SYMBOL_LENGTH },
+            },
+        })
+    }
+}
+
+impl<T: InputSchema> InputSchema for Vec<T> {
+    fn schema() -> Value {
+        json!({ "type": "array", "items": T::schema() })
+    }
+}
+
+input_type! {
+    /// An `airdrop --file` row. A .csv file holds the same pairs as wallet,count lines under an
+    /// optional wallet header.
+    pub struct AirdropGrant(
+        /// Wallet receiving the prints
+        pub PubkeyString,
+        /// Number of prints to grant
+        pub u32,
+    );
+}
+
+input_type! {
+    /// A llamas_new.json row, read by create_new_llamas. The wallet of each llama is the entry
+    /// at the same index of prints.json, an array of pubkeys of the same length.
+    pub struct LlamaKey(
+        /// Uri of the manifest
+        pub UriString,
+        /// Manifest the name and symbol are taken from
+        pub ManifestJson,
+    );
+}
+
+input_type! {
+    /// A `create_new_llamas --manifests` entry, also the format of its `--failures`.
+    pub struct ManifestEntry {
+        /// Wallet receiving the llama
+        pub wallet: PubkeyString,
+        /// Uri of the manifest
+        pub uri: UriString,
+        /// Manifest the name and symbol are taken from, fetched from the uri when left out
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub manifest: Option<ManifestJson>,
+        /// Number replacing {n} in --name-template, the index plus --name-offset when left out
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub n: Option<u64>,
+    }
+}
+
+input_type! {
+    /// An `update_new_llamas --file` row, setting the uri of a metadata account. --old_file
+    /// holds rows of the same shape for the metadata already updated.
+    pub struct UriUpdate(
+        /// Metadata account to update
+        pub PubkeyString,
+        /// New uri
+        pub UriString,
+    );
+}
+
+input_type! {
+    /// A `file_refunds --file` entry.
+    pub struct Refund {
+        /// Wallet to refund
+        pub pubkey: PubkeyString,
+        /// Lamports to pay
+        pub amount: u64,
+    }
+}
+
+/// The JSON Schema of a file holding an array of `T`.
+fn file_schema<T: InputSchema>(title: &str) -> Value {
+    let mut schema = Vec::<T>::schema();
+    schema["$schema"] = json!(DRAFT);
+    schema["title"] = json!(title);
+    schema
+}
+
+/// The JSON Schema of the input file of `command`, one of `SCHEMA_COMMANDS`.
+pub fn input_schema(command: &str) -> Value {
+    match command {
+        "airdrop" => file_schema::<AirdropGrant>("airdrop --file"),
+        "create_new_llamas" => file_schema::<LlamaKey>("llamas_new.json"),
+        "update_new_llamas" => file_schema::<UriUpdate>("update_new_llamas --file"),
+        _ => file_schema::<Refund>("file_refunds --file"),
+    }
+}
+
+pub fn print_schema(app_matches: &ArgMatches) {
+    let command = app_matches.value_of("command").unwrap();
+    let schema = if app_matches.is_present("manifests") {
+        if command != "create_new_llamas" {
+            exit_with_error(
+                CliError::InvalidInput("Only create_new_llamas takes --manifests".to_owned()),
+                json!({ "command": command }),
+            );
+        }
+        file_schema::<ManifestEntry>("create_new_llamas --manifests")
+    } else {
+        input_schema(command)
+    };
+    println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemas_follow_the_input_types() {
+        let schema = Refund::schema();
+        assert_eq!(schema["required"], json!(["pubkey", "amount"]));
+        assert_eq!(
+            schema["properties"]["amount"]["description"],
+            "Lamports to pay"
+        );
+
+        // Fields that may be left out are not required, and are read back as left out.
+        let schema = ManifestEntry::schema();
+        assert_eq!(schema["required"], json!(["wallet", "uri"]));
+        let entry = json!({ "wallet": "Llama", "uri": "https://arweave.net/llama" });
+        let read: ManifestEntry = serde_json::from_value(entry.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), entry);
+
+        let schema = AirdropGrant::schema();
+        assert_eq!(schema["minItems"], 2);
+        assert_eq!(schema["items"][1]["maximum"], u32::MAX);
+
+        for command in SCHEMA_COMMANDS {
+            let schema = input_schema(command);
+            assert_eq!(schema["$schema"], DRAFT);
+            assert_eq!(schema["type"], "array");
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use {
+    super::get_metadata_account_or_exit,
+    crate::{
+        error::{exit_with_error, CliError},
+        rpc::FailoverClient,
+    },
+    borsh::{BorshDeserialize, BorshSerialize},
+    clap::ArgMatches,
+    serde_json::json,
+    solana_clap_utils::input_parsers::pubkey_of,
+    solana_program::borsh::try_from_slice_unchecked,
+    solana_sdk::signature::Signer,
+    spl_token_metadata::state::{Edition, Key, MasterEditionV1, MasterEditionV2, Metadata},
+    spl_token_metadata_test_client::MetadataProgram,
+};
+
+/// Bytes shown on either side of the first mismatch.
+const DIFF_CONTEXT: usize = 16;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes `data` as a `T` the way the commands do and serializes it back, returning where and
+/// how the bytes differ when they do not match. Accounts are allocated at their maximum size,
+/// so on a match the number of non-zero bytes past the serialized value is returned: left over
+/// from an earlier, longer value, or a field the client does not know of.
+pub fn round_trip<T: BorshSerialize + BorshDeserialize>(data: &[u8]) -> Result<usize, String> {
+    let value: T = try_from_slice_unchecked(data).map_err(|err| err.to_string())?;
+    let expected = value.try_to_vec().map_err(|err| err.to_string())?;
+    if expected.len() > data.len() {
+        return Err(format!(
+            "Serializes to {} bytes, the account holds {}",
+            expected.len(),
+            data.len()
+        ));
+    }
+    match expected.iter().zip(data).position(|(a, b)| a != b) {
+        None => Ok(data[expected.len()..].iter().filter(|b| **b != 0).count()),
+        Some(offset) => {
+            let start = offset.saturating_sub(DIFF_CONTEXT);
+            let end = offset + DIFF_CONTEXT;
+            Err(format!(
+                "Bytes differ from offset {}\n  on chain:   {}\n  round trip: {}",
+                offset,
+                hex(&data[start..end.min(data.len())]),
+                hex(&expected[start..end.min(expected.len())])
+            ))
+        }
+    }
+}
+
+pub fn selftest(
+    app_matches: &ArgMatches,
+    _payer: Box<dyn Signer>,
+    client: FailoverClient,
+    program: &MetadataProgram,
+) {
+    let mint = pubkey_of(app_matches, "mint").unwrap();
+    let metadata_key = program.metadata_pda(&mint);
+    let metadata_account = get_metadata_account_or_exit(&client, &mint, &metadata_key);
+    let mut checks = vec![(
+        "Metadata",
+        metadata_key,
+        round_trip::<Metadata>(&metadata_account.data),
+    )];
+
+    let edition_key = program.edition_pda(&mint);
+    if let Ok(account) = client.get_account(&edition_key) {
+        let check = match account.data.first() {
+            Some(key) if *key == Key::MasterEditionV2 as u8 => (
+                "MasterEditionV2",
+                round_trip::<MasterEditionV2>(&account.data),
+            ),
+            Some(key) if *key == Key::MasterEditionV1 as u8 => (
+                "MasterEditionV1",
+                round_trip::<MasterEditionV1>(&account.data),
+            ),
+            Some(key) if *key == Key::EditionV1 as u8 => {
+                ("Edition", round_trip::<Edition>(&account.data))
+            }
+            key => ("Edition", Err(format!("Unknown key {:?}", key))),
+        };
+        checks.push((check.0, edition_key, check.1));
+    }
+
+    let mut failed = 0;
+    for (kind, key, result) in &checks {
+        match result {
+            Ok(0) => println!("{} {} round-trips", kind, key),
+            Ok(stale) => println!(
+                "{} {} round-trips, with {} non-zero bytes past its end",
+                kind, key, stale
+            ),
+            Err(diff) => {
+                println!("{} {} does not round-trip: {}", kind, key, diff);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "{} of {} accounts did not round-trip, the client's account layouts do not \
+                 match the program's",
+                failed,
+                checks.len()
+            )),
+            json!({ "mint": mint.to_string() }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::pubkey::Pubkey,
+        spl_token_metadata::state::{MAX_EDITION_LEN, MAX_MASTER_EDITION_LEN},
+    };
+
+    /// A flag that decodes any non-zero byte as set, so it does not round-trip.
+    #[derive(BorshSerialize)]
+    struct Flag(u8);
+
+    impl BorshDeserialize for Flag {
+        fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+            Ok(Flag(u8::deserialize(buf)?.min(1)))
+        }
+    }
+
+    #[test]
+    fn round_trip_checks_layout() {
+        let master_edition = MasterEditionV2 {
+            key: Key::MasterEditionV2,
+            supply: 3,
+            max_supply: Some(10),
+        };
+        let mut data = master_edition.try_to_vec().unwrap();
+        data.resize(MAX_MASTER_EDITION_LEN, 0);
+
+        assert_eq!(round_trip::<MasterEditionV2>(&data), Ok(0));
+        data[MAX_MASTER_EDITION_LEN - 1] = 1;
+        assert_eq!(round_trip::<MasterEditionV2>(&data), Ok(1));
+
+        // A max supply flag of 2 is no valid option.
+        data[9] = 2;
+        assert!(round_trip::<MasterEditionV2>(&data).is_err());
+
+        let edition = Edition {
+            key: Key::EditionV1,
+            parent: Pubkey::new_unique(),
+            edition: 1,
+        };
+        let mut data = edition.try_to_vec().unwrap();
+        data.resize(MAX_EDITION_LEN, 0);
+        assert_eq!(round_trip::<Edition>(&data), Ok(0));
+    }
+
+    #[test]
+    fn round_trip_diff_near_the_end() {
+        // The account is longer than the value, which differs in its last byte.
+        let mut data = vec![0; 32];
+        data[8] = 2;
+        let diff = round_trip::<(u64, Flag)>(&data).unwrap_err();
+        assert!(diff.starts_with("Bytes differ from offset 8"));
+        assert!(diff.ends_with("round trip: 000000000000000001"));
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Instant;
+
+/// Outcome of every item a bulk command handles, printed as a table when the command ends.
+pub struct Summary {
+    started: Instant,
+    succeeded: usize,
+    failed: usize,
+    skipped: usize,
+    unchanged: usize,
+    outputs: Vec<String>,
+}
+
+impl Summary {
+    pub fn start() -> Self {
+        Summary {
+            started: Instant::now(),
+            succeeded: 0,
+            failed: 0,
+            skipped: 0,
+            unchanged: 0,
+            outputs: vec![],
+        }
+    }
+
+    pub fn succeeded(&mut self) {
+        self.succeeded += 1;
+    }
+
+    pub fn failed(&mut self) {
+        self.failed += 1;
+    }
+
+    pub fn skipped(&mut self) {
+        self.skipped += 1;
+    }
+
+    /// Counts an item left alone because it already matched what the command would write.
+    pub fn unchanged(&mut self) {
+        self.unchanged += 1;
+    }
+
+    /// Whether every item was handled without a failure or a skip.
+    pub fn all_succeeded(&self) -> bool {
+        self.failed == 0 && self.skipped == 0
+    }
+
+    /// Records a file the command wrote, to be listed in the table.
+    pub fn output(&mut self, path: &str) {
+        self.outputs.push(path.to_owned());
+    }
+
+    pub fn print(&self) {
+        let mut rows = vec![
+            ("Attempted", (self.succeeded + self.failed).to_string()),
+            ("Succeeded", self.succeeded.to_string()),
+            ("Failed", self.failed.to_string()),
+            ("Skipped", self.skipped.to_string()),
+        ];
+        if self.unchanged > 0 {
+            rows.push(("Unchanged", self.unchanged.to_string()));
+        }
+        rows.push((
+            "Elapsed",
+            format!("{:.2}s", self.started.elapsed().as_secs_f64()),
+        ));
+        for output in &self.outputs {
+            rows.push(("Output", output.clone()));
+        }
+        println!("Summary");
+        for (label, value) in rows {
+            println!("  {:<10} {:>10}", label, value);
+        }
+    }
+}
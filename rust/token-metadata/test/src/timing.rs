@@ -0,0 +1,211 @@
+use {
+    crate::{
+        error::{exit_with_error, CliError},
+        rpc::FailoverClient,
+    },
+    clap::ArgMatches,
+    serde_json::json,
+    solana_sdk::signature::Signature,
+    solana_transaction_status::UiTransactionEncoding,
+    std::{
+        fs::File,
+        io::Write,
+        time::{Duration, Instant},
+    },
+};
+
+struct Sample {
+    index: usize,
+    label: String,
+    latency: Duration,
+    signature: Option<Signature>,
+}
+
+/// Fees of a landed transaction, in lamports.
+struct Fees {
+    estimated: u64,
+    actual: u64,
+}
+
+/// Latency of every transaction a bulk command submits, plus the wall clock for the whole run.
+pub struct Timings {
+    started: Instant,
+    /// Fee rate at the start of the run, only looked up for `--cost-report`.
+    lamports_per_signature: Option<u64>,
+    samples: Vec<Sample>,
+}
+
+impl Timings {
+    /// Starts the wall clock for a run. With `--cost-report`, notes the fee rate at the start to
+    /// estimate costs with.
+    pub fn start(app_matches: &ArgMatches, client: &FailoverClient) -> Self {
+        let lamports_per_signature = app_matches.value_of("cost_report").map(|path| {
+            client
+                .get_fees()
+                .unwrap_or_else(|err| exit_with_error(err.into(), json!({ "cost_report": path })))
+                .fee_calculator
+                .lamports_per_signature
+        });
+        Timings {
+            started: Instant::now(),
+            lamports_per_signature,
+            samples: vec![],
+        }
+    }
+
+    /// With `--cost-report`, prints what sending `transactions` transactions of `signatures`
+    /// signatures each should cost. Commands that pack items into as few transactions as fit
+    /// pass their upper bounds.
+    pub fn estimate(&self, transactions: usize, signatures: usize) {
+        if let Some(lamports_per_signature) = self.lamports_per_signature {
+            println!(
+                "Estimated fees at most {} lamports: {} transactions of {} signatures at {} \
+                 lamports per signature",
+                transactions as u64 * signatures as u64 * lamports_per_signature,
+                transactions,
+                signatures,
+                lamports_per_signature
+            );
+        }
+    }
+
+    /// Runs `send` and records how long it took under `index` and `label`, whether or not it
+    /// succeeded. Retries of the same item are recorded as separate samples.
+    pub fn time<E>(
+        &mut self,
+        index: usize,
+        label: &str,
+        send: impl FnOnce() -> Result<Signature, E>,
+    ) -> Result<Signature, E> {
+        let start = Instant::now();
+        let result = send();
+        self.samples.push(Sample {
+            index,
+            label: label.to_owned(),
+            latency: start.elapsed(),
+            signature: result.as_ref().ok().copied(),
+        });
+        result
+    }
+
+    /// Looks up what the transaction sent for `sample` cost, if it landed. The estimate prices
+    /// its signatures at the fee rate of the start of the run.
+    fn fees(&self, client: &FailoverClient, sample: &Sample) -> Option<Fees> {
+        let confirmed = client
+            .get_transaction(sample.signature.as_ref()?, UiTransactionEncoding::Base64)
+            .ok()?;
+        let signatures = confirmed.transaction.transaction.decode()?.signatures.len() as u64;
+        Some(Fees {
+            estimated: signatures * self.lamports_per_signature?,
+            actual: confirmed.transaction.meta?.fee,
+        })
+    }
+
+    /// Prints the throughput summary. With `--timing-output`, also writes one CSV row per sample
+    /// with its latency. With `--cost-report`, looks up and prints the fees paid and writes one
+    /// CSV row per sample with its fees.
+    pub fn finish(&self, app_matches: &ArgMatches, client: &FailoverClient) {
+        let total = self.started.elapsed();
+        let mut latencies: Vec<Duration> = self.samples.iter().map(|s| s.latency).collect();
+        latencies.sort();
+        let failed = self
+            .samples
+            .iter()
+            .filter(|s| s.signature.is_none())
+            .count();
+
+        println!(
+            "Sent {} transactions ({} failed) in {:.2}s",
+            self.samples.len(),
+            failed,
+            total.as_secs_f64()
+        );
+        if !latencies.is_empty() {
+            let mean = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+            println!(
+                "Latency mean {}ms, median {}ms, p95 {}ms",
+                mean.as_millis(),
+                percentile(&latencies, 50).as_millis(),
+                percentile(&latencies, 95).as_millis()
+            );
+            println!(
+                "Throughput {:.2} transactions/s",
+                latencies.len() as f64 / total.as_secs_f64()
+            );
+        }
+
+        if let Some(path) = app_matches.value_of("timing_output") {
+            if let Err(err) = self.write_csv(path) {
+                exit_with_error(CliError::from(err), json!({ "timing_output": path }));
+            }
+            println!("Wrote timings to {}", path);
+        }
+
+        if let Some(path) = app_matches.value_of("cost_report") {
+            let fees: Vec<Option<Fees>> = self
+                .samples
+                .iter()
+                .map(|sample| self.fees(client, sample))
+                .collect();
+            println!(
+                "Fees estimated {} lamports, actually paid {} lamports",
+                fees.iter()
+                    .flatten()
+                    .map(|fees| fees.estimated)
+                    .sum::<u64>(),
+                fees.iter().flatten().map(|fees| fees.actual).sum::<u64>()
+            );
+            if let Err(err) = self.write_cost_report(path, &fees) {
+                exit_with_error(CliError::from(err), json!({ "cost_report": path }));
+            }
+            println!("Wrote fees to {}", path);
+        }
+    }
+
+    fn write_csv(&self, path: &str) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "index,label,latency_ms,status")?;
+        for sample in &self.samples {
+            writeln!(
+                file,
+                "{},{},{},{}",
+                sample.index,
+                sample.label,
+                sample.latency.as_millis(),
+                if sample.signature.is_some() {
+                    "ok"
+                } else {
+                    "failed"
+                }
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_cost_report(&self, path: &str, fees: &[Option<Fees>]) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(
+            file,
+            "index,label,signature,estimated_lamports,actual_lamports"
+        )?;
+        for (sample, fees) in self.samples.iter().zip(fees) {
+            let signature = sample.signature.map(|s| s.to_string()).unwrap_or_default();
+            let (estimated, actual) = match fees {
+                Some(fees) => (fees.estimated.to_string(), fees.actual.to_string()),
+                None => (String::new(), String::new()),
+            };
+            writeln!(
+                file,
+                "{},{},{},{},{}",
+                sample.index, sample.label, signature, estimated, actual
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Nearest-rank percentile of already sorted, non-empty `latencies`.
+fn percentile(latencies: &[Duration], percent: usize) -> Duration {
+    let rank = (percent * latencies.len() + 99) / 100;
+    latencies[rank.max(1) - 1]
+}
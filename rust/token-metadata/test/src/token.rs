@@ -0,0 +1,339 @@
+use {
+    super::{read_mints_file, send_and_confirm, sign_and_send, signer_of_or_keypair},
+    crate::{
+        error::{exit_with_error, report_item_error, CliError},
+        query::get_nft_token_accounts,
+        rpc::FailoverClient,
+        summary::Summary,
+    },
+    clap::ArgMatches,
+    serde_json::{json, Value},
+    solana_clap_utils::input_parsers::pubkey_of,
+    solana_client::rpc_request::TokenAccountsFilter,
+    solana_program::{instruction::Instruction, program_pack::Pack},
+    solana_remote_wallet::remote_wallet::RemoteWalletManager,
+    solana_sdk::{
+        packet::PACKET_DATA_SIZE, pubkey::Pubkey, signature::Signer, transaction::Transaction,
+    },
+    spl_token::{
+        instruction::{
+            approve as approve_instruction, burn, close_account, freeze_account,
+            revoke as revoke_instruction, thaw_account,
+        },
+        state::{Account, Mint},
+    },
+    std::{fs, str::FromStr, sync::Arc},
+};
+
+/// Returns the token account of `owner` holding the token of `mint`, or an error when `owner`
+/// does not hold it.
+pub fn owned_token_account(
+    client: &FailoverClient,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Pubkey, CliError> {
+    for keyed in client.get_token_accounts_by_owner(owner, TokenAccountsFilter::Mint(*mint))? {
+        let key = Pubkey::from_str(&keyed.pubkey).unwrap();
+        let account = Account::unpack(&client.get_account(&key)?.data).unwrap();
+        if account.amount > 0 {
+            return Ok(key);
+        }
+    }
+    Err(CliError::InvalidInput(format!(
+        "{} does not hold a token of {}",
+        owner, mint
+    )))
+}
+
+/// Resolves the `--owner` signer and its token account of `--mint`, exiting when it holds none.
+fn owner_and_token_account(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) -> (Box<dyn Signer>, Pubkey) {
+    let owner = signer_of_or_keypair(app_matches, "owner", wallet_manager);
+    let mint = pubkey_of(app_matches, "mint").unwrap();
+    match owned_token_account(client, &owner.pubkey(), &mint) {
+        Ok(token_account) => (owner, token_account),
+        Err(err) => exit_with_error(
+            err,
+            json!({ "mint": mint.to_string(), "owner": owner.pubkey().to_string() }),
+        ),
+    }
+}
+
+pub fn approve(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    let (owner, token_account) = owner_and_token_account(app_matches, &client, wallet_manager);
+    let delegate = pubkey_of(app_matches, "delegate").unwrap();
+
+    let instructions = [approve_instruction(
+        &spl_token::id(),
+        &token_account,
+        &delegate,
+        &owner.pubkey(),
+        &[],
+        1,
+    )
+    .unwrap()];
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    let signers = vec![payer.as_ref(), owner.as_ref()];
+    sign_and_send(app_matches, &client, &mut transaction, &signers);
+    println!(
+        "Approved {} to transfer the token in {}",
+        delegate, token_account
+    );
+}
+
+pub fn revoke(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    let (owner, token_account) = owner_and_token_account(app_matches, &client, wallet_manager);
+
+    let instructions =
+        [revoke_instruction(&spl_token::id(), &token_account, &owner.pubkey(), &[]).unwrap()];
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    let signers = vec![payer.as_ref(), owner.as_ref()];
+    sign_and_send(app_matches, &client, &mut transaction, &signers);
+    println!("Revoked the delegate of {}", token_account);
+}
+
+/// Resolves the `--freeze-authority` signer, exiting unless it is the freeze authority of `mint`.
+fn freeze_authority_of(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    mint: &Pubkey,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) -> Box<dyn Signer> {
+    let freeze_authority = signer_of_or_keypair(app_matches, "freeze_authority", wallet_manager);
+    let context = json!({
+        "mint": mint.to_string(),
+        "freeze_authority": freeze_authority.pubkey().to_string(),
+    });
+    let mint_account = match client.get_account(mint) {
+        Ok(account) => Mint::unpack(&account.data).unwrap(),
+        Err(err) => exit_with_error(err.into(), context),
+    };
+    match Option::<Pubkey>::from(mint_account.freeze_authority) {
+        Some(key) if key == freeze_authority.pubkey() => freeze_authority,
+        Some(key) => exit_with_error(
+            CliError::InvalidInput(format!("The freeze authority of {} is {}", mint, key)),
+            context,
+        ),
+        None => exit_with_error(
+            CliError::InvalidInput(format!("{} has no freeze authority", mint)),
+            context,
+        ),
+    }
+}
+
+/// Freezes or thaws the `--account` token account of `--mint`.
+fn set_frozen(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+    freeze: bool,
+) {
+    let mint = pubkey_of(app_matches, "mint").unwrap();
+    let account = pubkey_of(app_matches, "account").unwrap();
+    let freeze_authority = freeze_authority_of(app_matches, &client, &mint, wallet_manager);
+
+    let instruction = if freeze {
+        freeze_account(
+            &spl_token::id(),
+            &account,
+            &mint,
+            &freeze_authority.pubkey(),
+            &[],
+        )
+    } else {
+        thaw_account(
+            &spl_token::id(),
+            &account,
+            &mint,
+            &freeze_authority.pubkey(),
+            &[],
+        )
+    };
+    let mut transaction =
+        Transaction::new_with_payer(&[instruction.unwrap()], Some(&payer.pubkey()));
+    let signers = vec![payer.as_ref(), freeze_authority.as_ref()];
+    sign_and_send(app_matches, &client, &mut transaction, &signers);
+    println!("{} {}", if freeze { "Froze" } else { "Thawed" }, account);
+}
+
+pub fn freeze(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    set_frozen(app_matches, payer, client, wallet_manager, true);
+}
+
+pub fn thaw(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    set_frozen(app_matches, payer, client, wallet_manager, false);
+}
+
+/// Where `bulk_burn` records the NFTs it burned.
+const BURN_RECORD: &str = "burned_nfts.json";
+
+/// The instructions burning the one token in `token_account` and closing the account, its rent
+/// going to `destination`.
+pub fn burn_instructions(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_account: &Pubkey,
+    destination: &Pubkey,
+) -> Vec<Instruction> {
+    vec![
+        burn(&spl_token::id(), token_account, mint, owner, &[], 1).unwrap(),
+        close_account(&spl_token::id(), token_account, destination, owner, &[]).unwrap(),
+    ]
+}
+
+/// Sends the burns of `batch` in one transaction, recording each mint as burned or failed.
+fn send_burn_batch(
+    app_matches: &ArgMatches,
+    client: &FailoverClient,
+    signers: &[&dyn Signer],
+    batch: &mut Vec<(Pubkey, Pubkey)>,
+    destination: &Pubkey,
+    burned: &mut Vec<Value>,
+    summary: &mut Summary,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let owner = signers[1].pubkey();
+    let instructions: Vec<Instruction> = batch
+        .iter()
+        .flat_map(|(mint, token_account)| {
+            burn_instructions(&owner, mint, token_account, destination)
+        })
+        .collect();
+    let mints: Vec<String> = batch.iter().map(|(mint, _)| mint.to_string()).collect();
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&signers[0].pubkey()));
+    let result = client
+        .get_recent_blockhash()
+        .and_then(|(recent_blockhash, _)| {
+            transaction.sign(&signers.to_vec(), recent_blockhash);
+            send_and_confirm(app_matches, client, &mut transaction, &signers.to_vec())
+        });
+    match result {
+        Ok(signature) => {
+            println!("Burned {}", mints.join(", "));
+            for (mint, token_account) in batch.iter() {
+                burned.push(json!({
+                    "mint": mint.to_string(),
+                    "token_account": token_account.to_string(),
+                    "signature": signature.to_string(),
+                }));
+                summary.succeeded();
+            }
+        }
+        Err(err) => {
+            report_item_error("Burn failed.", &err.into(), json!({ "mints": mints }));
+            batch.iter().for_each(|_| summary.failed());
+        }
+    }
+    batch.clear();
+}
+
+/// Burns the token of every mint in `--mints-file` that `--owner` holds and closes its token
+/// account, sending the rent to `--destination`. As many burns as fit go in each transaction.
+/// The program has no instruction to close metadata or edition accounts, so their rent stays
+/// where it is.
+pub fn bulk_burn(
+    app_matches: &ArgMatches,
+    payer: Box<dyn Signer>,
+    client: FailoverClient,
+    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+) {
+    let owner = signer_of_or_keypair(app_matches, "owner", wallet_manager);
+    let destination = pubkey_of(app_matches, "destination").unwrap_or_else(|| owner.pubkey());
+    let mints = read_mints_file(app_matches.value_of("mints_file").unwrap());
+    if !app_matches.is_present("yes") {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "bulk_burn destroys the tokens of up to {} mints held by {} and cannot be \
+                 undone, pass --yes to go ahead",
+                mints.len(),
+                owner.pubkey()
+            )),
+            json!({ "owner": owner.pubkey().to_string() }),
+        );
+    }
+    let held = get_nft_token_accounts(&client, &owner.pubkey(), "1");
+    let signers = [payer.as_ref(), owner.as_ref()];
+
+    let mut summary = Summary::start();
+    let mut burned = vec![];
+    let mut batch: Vec<(Pubkey, Pubkey)> = vec![];
+    for mint in mints {
+        let token_account = match held.iter().find(|(_, held_mint)| *held_mint == mint) {
+            Some((token_account, _)) => *token_account,
+            None => {
+                println!(
+                    "Skipping {} because {} does not hold it",
+                    mint,
+                    owner.pubkey()
+                );
+                summary.skipped();
+                continue;
+            }
+        };
+        let combined: Vec<Instruction> = batch
+            .iter()
+            .chain(std::iter::once(&(mint, token_account)))
+            .flat_map(|(mint, token_account)| {
+                burn_instructions(&owner.pubkey(), mint, token_account, &destination)
+            })
+            .collect();
+        let combined_size = bincode::serialized_size(&Transaction::new_with_payer(
+            &combined,
+            Some(&payer.pubkey()),
+        ))
+        .unwrap() as usize;
+        if combined_size > PACKET_DATA_SIZE {
+            send_burn_batch(
+                app_matches,
+                &client,
+                &signers,
+                &mut batch,
+                &destination,
+                &mut burned,
+                &mut summary,
+            );
+        }
+        batch.push((mint, token_account));
+    }
+    send_burn_batch(
+        app_matches,
+        &client,
+        &signers,
+        &mut batch,
+        &destination,
+        &mut burned,
+        &mut summary,
+    );
+
+    if let Err(err) = fs::write(BURN_RECORD, serde_json::to_string(&burned).unwrap()) {
+        exit_with_error(err.into(), json!({ "record": BURN_RECORD }));
+    }
+    summary.output(BURN_RECORD);
+    summary.print();
+}
@@ -0,0 +1,288 @@
+use {
+    super::{check_field_lengths, manifest_name_and_symbol},
+    crate::{
+        error::{exit_with_error, report_item_error, CliError},
+        schema::{AirdropGrant, LlamaKey, ManifestEntry, PubkeyString, Refund, UriUpdate},
+    },
+    clap::ArgMatches,
+    serde::de::DeserializeOwned,
+    serde_json::{json, Value},
+    solana_sdk::pubkey::Pubkey,
+    std::{fs, str::FromStr},
+};
+
+/// Bulk commands whose input files `validate_file` knows the layout of. `mints` covers every
+/// command taking a `--mints-file`.
+pub const VALIDATED_COMMANDS: &[&str] = &[
+    "airdrop",
+    "create_new_llamas",
+    "update_new_llamas",
+    "file_refunds",
+    "mints",
+];
+
+fn pubkey_field(value: &str, field: &str) -> Result<Pubkey, CliError> {
+    Pubkey::from_str(value).map_err(|_| {
+        CliError::InvalidInput(format!("The {} {} is not a valid pubkey", field, value))
+    })
+}
+
+/// `row` read as the input type `T` the command reads it as.
+fn typed<T: DeserializeOwned>(row: &Value) -> Result<T, CliError> {
+    serde_json::from_value(row.clone())
+        .map_err(|err| CliError::InvalidInput(format!("{} in {}", err, row)))
+}
+
+/// `[wallet, count]`, with a count an airdrop can grant.
+fn check_airdrop_row(row: &Value) -> Result<(), CliError> {
+    let AirdropGrant(wallet, count) = typed(row)?;
+    pubkey_field(&wallet.0, "wallet")?;
+    if count == 0 {
+        return Err(CliError::InvalidInput(format!(
+            "The count {} is not between 1 and {}",
+            count,
+            u32::MAX
+        )));
+    }
+    Ok(())
+}
+
+/// `[uri, manifest]`, with a manifest whose name and symbol fit on chain.
+fn check_create_new_llamas_row(row: &Value) -> Result<(), CliError> {
+    let LlamaKey(uri, manifest) = typed(row)?;
+    manifest_name_and_symbol(&uri.0, &manifest.0).map(|_| ())
+}
+
+/// `{"wallet": wallet, "uri": uri}` of `create_new_llamas --manifests`, with a uri and any
+/// embedded manifest that fit on chain.
+fn check_manifest_entry_row(row: &Value) -> Result<(), CliError> {
+    let entry: ManifestEntry = typed(row)?;
+    pubkey_field(&entry.wallet.0, "wallet")?;
+    match entry.manifest {
+        Some(manifest) => manifest_name_and_symbol(&entry.uri.0, &manifest.0).map(|_| ()),
+        None => check_field_lengths("", "", &entry.uri.0),
+    }
+}
+
+/// `[metadata, uri]`, with a uri that fits on chain.
+fn check_update_new_llamas_row(row: &Value) -> Result<(), CliError> {
+    let UriUpdate(metadata, uri) = typed(row)?;
+    pubkey_field(&metadata.0, "metadata")?;
+    check_field_lengths("", "", &uri.0)
+}
+
+/// `{"pubkey": wallet, "amount": lamports}`.
+fn check_file_refund_row(row: &Value) -> Result<(), CliError> {
+    let refund: Refund = typed(row)?;
+    pubkey_field(&refund.pubkey.0, "pubkey").map(|_| ())
+}
+
+/// A wallet of prints.json, receiving the llama at the same index of llamas_new.json.
+fn check_prints_row(row: &Value) -> Result<(), CliError> {
+    let wallet: PubkeyString = typed(row)?;
+    pubkey_field(&wallet.0, "wallet").map(|_| ())
+}
+
+fn check_mints_row(row: &Value) -> Result<(), CliError> {
+    let mint: PubkeyString = typed(row)?;
+    pubkey_field(&mint.0, "mint").map(|_| ())
+}
+
+/// Reads the rows of an airdrop CSV as `[wallet, count]` pairs, keeping counts that are not
+/// numbers as strings so they are reported rather than dropped.
+fn airdrop_csv_rows(contents: &str) -> Vec<Value> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(line_number, line)| (line_number, line.trim()))
+        .filter(|(line_number, line)| {
+            !(line.is_empty() || (*line_number == 0 && line.starts_with("wallet")))
+        })
+        .map(|(_, line)| match line.split_once(',') {
+            Some((wallet, count)) => match count.trim().parse::<u64>() {
+                Ok(count) => json!([wallet.trim(), count]),
+                Err(_) => json!([wallet.trim(), count.trim()]),
+            },
+            None => json!([line]),
+        })
+        .collect()
+}
+
+/// The rows of the JSON array in `path`, or of the airdrop CSV with `csv`.
+fn read_rows(path: &str, csv: bool) -> Vec<Value> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => exit_with_error(err.into(), json!({ "file": path })),
+    };
+    if csv {
+        return airdrop_csv_rows(&contents);
+    }
+    match serde_json::from_str::<Value>(&contents) {
+        Ok(Value::Array(rows)) => rows,
+        Ok(_) => exit_with_error(
+            CliError::InvalidInput(format!("{} does not hold a JSON array", path)),
+            json!({ "file": path }),
+        ),
+        Err(err) => exit_with_error(err.into(), json!({ "file": path })),
+    }
+}
+
+/// Reports each of `rows` of `path` that `check` rejects, returning how many it did.
+fn check_rows(path: &str, rows: &[Value], check: fn(&Value) -> Result<(), CliError>) -> usize {
+    let mut invalid = 0;
+    for (index, row) in rows.iter().enumerate() {
+        if let Err(err) = check(row) {
+            report_item_error(
+                &format!("Entry {}:", index),
+                &err,
+                json!({ "file": path, "index": index }),
+            );
+            invalid += 1;
+        }
+    }
+    invalid
+}
+
+pub fn validate_file(app_matches: &ArgMatches) {
+    let command = app_matches.value_of("command").unwrap();
+    let path = app_matches.value_of("file").unwrap();
+    let manifests = app_matches.is_present("manifests");
+    if manifests && command != "create_new_llamas" {
+        exit_with_error(
+            CliError::InvalidInput("Only create_new_llamas takes --manifests".to_owned()),
+            json!({ "command": command }),
+        );
+    }
+    let rows = read_rows(path, command == "airdrop" && path.ends_with(".csv"));
+
+    let check: fn(&Value) -> Result<(), CliError> = match command {
+        "airdrop" => check_airdrop_row,
+        "create_new_llamas" if manifests => check_manifest_entry_row,
+        "create_new_llamas" => check_create_new_llamas_row,
+        "update_new_llamas" => check_update_new_llamas_row,
+        "file_refunds" => check_file_refund_row,
+        _ => check_mints_row,
+    };
+    let mut invalid = check_rows(path, &rows, check);
+    let mut total = rows.len();
+    let mut files = path.to_owned();
+    let mut mismatched = false;
+    // The wallets of llamas_new.json are in prints.json, paired by index.
+    if command == "create_new_llamas" && !manifests {
+        let prints_path = app_matches.value_of("prints").unwrap();
+        let prints = read_rows(prints_path, false);
+        invalid += check_rows(prints_path, &prints, check_prints_row);
+        total += prints.len();
+        files = format!("{} and {}", path, prints_path);
+        if prints.len() != rows.len() {
+            report_item_error(
+                "Mismatched files.",
+                &CliError::InvalidInput(format!(
+                    "{} holds {} wallets for the {} llamas of {}",
+                    prints_path,
+                    prints.len(),
+                    rows.len(),
+                    path
+                )),
+                json!({ "file": path, "prints": prints_path }),
+            );
+            mismatched = true;
+        }
+    }
+
+    if invalid > 0 || mismatched {
+        exit_with_error(
+            CliError::InvalidInput(format!(
+                "{} of the {} entries of {} are invalid{}",
+                invalid,
+                total,
+                files,
+                if mismatched {
+                    ", and they do not pair up"
+                } else {
+                    ""
+                }
+            )),
+            json!({ "file": path }),
+        );
+    }
+    println!("All {} entries of {} are valid", total, files);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wallet() -> String {
+        Pubkey::new_unique().to_string()
+    }
+
+    #[test]
+    fn airdrop_rows_are_checked() {
+        assert!(check_airdrop_row(&json!([wallet(), 2])).is_ok());
+        assert!(check_airdrop_row(&json!([wallet(), 0])).is_err());
+        assert!(check_airdrop_row(&json!([wallet(), u32::MAX as u64 + 1])).is_err());
+        assert!(check_airdrop_row(&json!(["llama", 1])).is_err());
+        assert!(check_airdrop_row(&json!([wallet()])).is_err());
+    }
+
+    #[test]
+    fn airdrop_csv_rows_keep_what_they_cannot_read() {
+        let wallet = wallet();
+        let rows = airdrop_csv_rows(&format!("wallet,count\n{0},2\n\n{0}, two\n{0}\n", wallet));
+        assert_eq!(
+            rows,
+            vec![json!([wallet, 2]), json!([wallet, "two"]), json!([wallet])]
+        );
+        let checked: Vec<bool> = rows
+            .iter()
+            .map(|row| check_airdrop_row(row).is_ok())
+            .collect();
+        assert_eq!(checked, vec![true, false, false]);
+    }
+
+    #[test]
+    fn create_new_llamas_rows_are_checked() {
+        let manifest = json!({ "name": "Llama", "symbol": "LLAMA" });
+        assert!(
+            check_create_new_llamas_row(&json!(["https://arweave.net/llama", manifest])).is_ok()
+        );
+        assert!(check_create_new_llamas_row(&json!(["https://arweave.net/llama", {}])).is_err());
+        assert!(check_create_new_llamas_row(&json!([1, manifest])).is_err());
+
+        let uri = "https://arweave.net/llama";
+        assert!(check_manifest_entry_row(&json!({ "wallet": wallet(), "uri": uri })).is_ok());
+        assert!(check_manifest_entry_row(&json!({
+            "wallet": wallet(),
+            "uri": uri,
+            "manifest": { "name": "L".repeat(64), "symbol": "LLAMA" },
+        }))
+        .is_err());
+        assert!(check_manifest_entry_row(&json!({ "wallet": "llama", "uri": uri })).is_err());
+        assert!(check_manifest_entry_row(&json!({ "wallet": wallet() })).is_err());
+
+        assert!(check_prints_row(&json!(wallet())).is_ok());
+        assert!(check_prints_row(&json!("llama")).is_err());
+    }
+
+    #[test]
+    fn update_new_llamas_rows_are_checked() {
+        assert!(
+            check_update_new_llamas_row(&json!([wallet(), "https://arweave.net/llama"])).is_ok()
+        );
+        assert!(check_update_new_llamas_row(&json!([wallet(), "u".repeat(201)])).is_err());
+        assert!(
+            check_update_new_llamas_row(&json!(["llama", "https://arweave.net/llama"])).is_err()
+        );
+    }
+
+    #[test]
+    fn file_refund_and_mints_rows_are_checked() {
+        assert!(check_file_refund_row(&json!({ "pubkey": wallet(), "amount": 5 })).is_ok());
+        assert!(check_file_refund_row(&json!({ "pubkey": wallet(), "amount": -5 })).is_err());
+        assert!(check_file_refund_row(&json!({ "pubkey": wallet() })).is_err());
+
+        assert!(check_mints_row(&json!(wallet())).is_ok());
+        assert!(check_mints_row(&json!(["llama"])).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_program::{borsh::try_from_slice_unchecked, pubkey::Pubkey},
+    spl_token_metadata::state::{Data, Key, Metadata, MAX_METADATA_LEN},
+    std::fmt,
+};
+
+/// The layouts a metadata account has had over the program's history.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MetadataVersion {
+    /// Written before `edition_nonce` was added, ending at `is_mutable`.
+    NoEditionNonce,
+    /// The layout of the program this client is built against.
+    Current,
+    /// Longer than this program allocates, written by a later program that appended fields the
+    /// client does not know of. Those fields are ignored.
+    Newer,
+}
+
+impl fmt::Display for MetadataVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MetadataVersion::NoEditionNonce => "v1 without edition nonce",
+            MetadataVersion::Current => "v1",
+            MetadataVersion::Newer => "newer than v1",
+        })
+    }
+}
+
+/// `Metadata` as it was before `edition_nonce`.
+#[derive(BorshSerialize, BorshDeserialize)]
+struct MetadataNoEditionNonce {
+    key: Key,
+    update_authority: Pubkey,
+    mint: Pubkey,
+    data: Data,
+    primary_sale_happened: bool,
+    is_mutable: bool,
+}
+
+impl From<MetadataNoEditionNonce> for Metadata {
+    fn from(metadata: MetadataNoEditionNonce) -> Self {
+        Metadata {
+            key: metadata.key,
+            update_authority: metadata.update_authority,
+            mint: metadata.mint,
+            data: metadata.data,
+            primary_sale_happened: metadata.primary_sale_happened,
+            is_mutable: metadata.is_mutable,
+            edition_nonce: None,
+        }
+    }
+}
+
+/// Decodes a metadata account whatever its version, choosing the layout from the key byte and
+/// the account length. Accounts written before `edition_nonce` can hold leftover bytes where
+/// the nonce now is, which the current layout rejects, so those fall back to the older layout.
+pub fn read_metadata(data: &[u8]) -> Result<(Metadata, MetadataVersion), String> {
+    match data.first() {
+        Some(key) if *key == Key::MetadataV1 as u8 => {}
+        Some(key) => return Err(format!("Key {} is not metadata", key)),
+        None => return Err("Account is empty".to_owned()),
+    }
+    let current = try_from_slice_unchecked::<Metadata>(data);
+    if data.len() > MAX_METADATA_LEN {
+        return current
+            .map(|metadata| (metadata, MetadataVersion::Newer))
+            .map_err(|err| err.to_string());
+    }
+    match current {
+        Ok(metadata) if metadata.edition_nonce.is_some() => {
+            Ok((metadata, MetadataVersion::Current))
+        }
+        current => try_from_slice_unchecked::<MetadataNoEditionNonce>(data)
+            .map(|metadata| (metadata.into(), MetadataVersion::NoEditionNonce))
+            .map_err(|err| match current {
+                Err(current) => current.to_string(),
+                Ok(_) => err.to_string(),
+            }),
+    }
+}
+
+/// Decodes a metadata account whatever its version.
+pub fn decode_metadata(data: &[u8]) -> Result<Metadata, String> {
+    read_metadata(data).map(|(metadata, _)| metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, spl_token_metadata::utils::puff_out_data_fields};
+
+    fn metadata(edition_nonce: Option<u8>) -> Metadata {
+        let mut metadata = Metadata {
+            key: Key::MetadataV1,
+            update_authority: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            data: Data {
+                name: "Llama".to_owned(),
+                symbol: "LLAMA".to_owned(),
+                uri: "https://example.com/1.json".to_owned(),
+                seller_fee_basis_points: 500,
+                creators: None,
+            },
+            primary_sale_happened: false,
+            is_mutable: true,
+            edition_nonce,
+        };
+        puff_out_data_fields(&mut metadata);
+        metadata
+    }
+
+    #[test]
+    fn metadata_versions_are_detected() {
+        let mut data = metadata(Some(254)).try_to_vec().unwrap();
+        data.resize(MAX_METADATA_LEN, 0);
+        assert_eq!(read_metadata(&data).unwrap().1, MetadataVersion::Current);
+
+        // A leftover byte where the nonce now is.
+        let old = metadata(None);
+        let mut data = old.try_to_vec().unwrap();
+        let nonce_offset = data.len() - 1;
+        data.resize(MAX_METADATA_LEN, 0);
+        data[nonce_offset] = 7;
+        assert!(try_from_slice_unchecked::<Metadata>(&data).is_err());
+        let (decoded, version) = read_metadata(&data).unwrap();
+        assert_eq!(version, MetadataVersion::NoEditionNonce);
+        assert_eq!(decoded.data, old.data);
+        assert_eq!(decoded.edition_nonce, None);
+
+        let mut data = metadata(Some(254)).try_to_vec().unwrap();
+        data.resize(MAX_METADATA_LEN + 64, 1);
+        assert_eq!(read_metadata(&data).unwrap().1, MetadataVersion::Newer);
+
+        assert!(read_metadata(&[Key::EditionV1 as u8]).is_err());
+    }
+}